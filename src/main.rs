@@ -17,7 +17,10 @@
 use clap::Parser;
 mod node;
 use node::blockchain::Blockchain;
+use node::cli::Command;
 use node::configuration::AppConfig;
+use node::dev_chain::DevAccount;
+use node::transactions::transaction_pool::TransactionPoolConfig;
 use node::tracing::setup_tracing;
 
 #[derive(Parser, Debug)]
@@ -25,25 +28,41 @@ use node::tracing::setup_tracing;
 struct Args {
     #[clap(short, long, default_value = "default")]
     env: String,
+    #[clap(subcommand)]
+    command: Option<Command>,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let env = &Args::parse().env;
-    let config = AppConfig::load_configuration(env)?;
+    let args = Args::parse();
+    if let Some(command) = args.command {
+        command.run().await?;
+        return Ok(());
+    }
+
+    let config = AppConfig::load_configuration(&args.env)?;
     setup_tracing(&config.log_level, &config.seq_url, &config.seq_api_key)?;
 
-    let blockchain = initialize_blockchain(&config);
+    let blockchain = initialize_blockchain(&config)?;
+    if config.dev_accounts > 0 {
+        DevAccount::print_all(config.dev_accounts)?;
+    }
     blockchain.start_network_services(&config).await;
     Ok(())
 }
 
-fn initialize_blockchain(config: &AppConfig) -> Blockchain {
-    Blockchain::new(
+fn initialize_blockchain(config: &AppConfig) -> Result<Blockchain, String> {
+    let mut blockchain = Blockchain::new(
         config.blockchain_name.clone(),
         config.author_public_key.clone(),
-        config.author_secret_key.clone(),
+        config.load_author_secret_key()?,
         config.developer_mode.clone(),
-        config.authorities.clone(),
-    )
+        config.chain_spec()?,
+        TransactionPoolConfig {
+            max_transactions: config.tx_pool_max_transactions,
+            max_transactions_per_account: config.tx_pool_max_transactions_per_account,
+        },
+    )?;
+    blockchain.set_keep_database(config.keep_database);
+    Ok(blockchain)
 }
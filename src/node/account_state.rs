@@ -47,6 +47,39 @@ impl AccountState {
         (from_key, from_serialized_balance)
     }
 
+    // Applies a balance change on top of an update of the same account already in `states`,
+    // since each update is computed from the stored state and a second one would overwrite it
+    pub fn apply_balance_change(
+        states: &mut Vec<Option<(Vec<u8>, Vec<u8>)>>,
+        public_key: &str,
+        balance_change: i64,
+        db: &Database,
+    ) -> Result<(), String> {
+        let key = Self::construct_account_state_key(public_key);
+        let staged = states
+            .iter_mut()
+            .flatten()
+            .find(|(state_key, _)| *state_key == key);
+        let mut account_state = match &staged {
+            Some((_, state_value)) => serde_json::from_slice(state_value)
+                .map_err(|e| format!("Invalid account state of {}: {}", public_key, e))?,
+            None => AccountState::get_current_state(&public_key.to_string(), db),
+        };
+        account_state.balance = account_state
+            .balance
+            .checked_add_signed(balance_change)
+            .ok_or_else(|| format!("Balance of {} out of range", public_key))?;
+        let serialized = serde_json::to_string(&account_state)
+            .map_err(|e| format!("Failed to serialize account state: {}", e))?
+            .into_bytes();
+
+        match staged {
+            Some((_, state_value)) => *state_value = serialized,
+            None => states.push(Some((key, serialized))),
+        }
+        Ok(())
+    }
+
     pub fn get_current_nonce(public_key: &String, db: &Database) -> Result<u64, String> {
         let key = Self::construct_account_nonce_key(public_key);
         match db.get("state", &key) {
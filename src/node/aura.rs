@@ -1,19 +1,23 @@
-use crate::node::consensus::Consensus;
+use crate::node::consensus::{
+    timed_after_parent, verify_header_authority, verify_timestamp_drift, Consensus,
+};
 
-use super::{blocks::block::Block, time_utils::get_current_timespan};
+use std::time::Duration;
 
+use super::{
+    blocks::{block::Block, block_headers::BlockHeader},
+    time_utils::{get_current_duration, get_current_timespan},
+};
+
+// The authorities rotate through fixed time slots
 #[derive(Debug)]
 pub struct Aura {
-    pub authorities: Vec<String>, // List of validators
-    pub step_duration: u64,       // Duration of each step in seconds
+    pub step_duration: u64, // Duration of each step in seconds
 }
 
 impl Aura {
-    pub fn new(authorities: Vec<String>, step_duration: u64) -> Self {
-        Self {
-            authorities,
-            step_duration,
-        }
+    pub fn new(step_duration: u64) -> Self {
+        Self { step_duration }
     }
 
     // Determine the slot number based on a given timestamp
@@ -28,30 +32,85 @@ impl Aura {
     }
 
     // Determine the author based on a given slot number
-    fn author_at_slot(&self, slot: u64) -> &String {
-        &self.authorities[slot as usize % self.authorities.len()]
+    fn author_at_slot<'a>(&self, slot: u64, authorities: &'a [String]) -> Result<&'a String, String> {
+        if authorities.is_empty() {
+            return Err("There are no authorities".to_string());
+        }
+        Ok(&authorities[slot as usize % authorities.len()])
     }
-}
 
-impl Consensus for Aura {
-    fn current_author(&self) -> &String {
-        let current_slot = self.current_slot();
-        self.author_at_slot(current_slot)
+    fn verify_block_author(&self, block: &Block, authorities: &[String]) -> Result<(), String> {
+        self.verify_author_at(block.timestamp, &block.author, authorities)
     }
 
-    fn verify_block_author(&self, block: &Block) -> Result<(), String> {
-        let block_slot = self.slot_at_time(block.timestamp);
-        let expected_author = self.author_at_slot(block_slot);
+    fn verify_author_at(&self, timestamp: u64, author: &str, authorities: &[String]) -> Result<(), String> {
+        let slot = self.slot_at_time(timestamp);
+        let expected_author = self.author_at_slot(slot, authorities)?;
 
-        if &block.author == expected_author {
+        if author == expected_author {
             Ok(())
         } else {
             Err(format!(
                 "Block author verification failed: expected author {}, but found {}",
-                expected_author, block.author
+                expected_author, author
             ))
         }
     }
+
+    // At most one block per slot
+    fn verify_block_slot(&self, block: &Block, parent: &Block) -> Result<(), String> {
+        verify_timestamp_drift(block)?;
+
+        let block_slot = self.slot_at_time(block.timestamp);
+        let parent_slot = self.slot_at_time(parent.timestamp);
+        if timed_after_parent(parent) && block_slot <= parent_slot {
+            return Err(format!(
+                "Block {} is in slot {}, which is not after slot {} of its parent",
+                block.index, block_slot, parent_slot
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl Consensus for Aura {
+    // Wake the authoring job at slot boundaries
+    fn duration_until_next_slot(&self) -> Duration {
+        let now = get_current_duration();
+        let next_slot_start = Duration::from_secs((self.slot_at_time(now.as_secs()) + 1) * self.step_duration);
+        next_slot_start - now
+    }
+
+    fn current_author<'a>(&self, _parent: &Block, authorities: &'a [String]) -> Result<&'a String, String> {
+        let current_slot = self.current_slot();
+        self.author_at_slot(current_slot, authorities)
+    }
+
+    fn prepare_block(&self, author: &str, parent: &Block, authorities: &[String]) -> Result<(), String> {
+        let current_slot = self.current_slot();
+        let slot_author = self.author_at_slot(current_slot, authorities)?;
+        if slot_author.as_str() != author {
+            return Err(format!("Slot {} belongs to {}", current_slot, slot_author));
+        }
+        if timed_after_parent(parent) && self.slot_at_time(parent.timestamp) >= current_slot {
+            return Err(format!("A block was already authored in slot {}", current_slot));
+        }
+        Ok(())
+    }
+
+    fn verify_header(&self, block: &Block, parent: &Block, authorities: &[String]) -> Result<(), String> {
+        self.verify_block_author(block, authorities)?;
+        self.verify_block_slot(block, parent)
+    }
+
+    // Headers from peers that do not send the timestamp can only be checked for membership
+    fn verify_header_author(&self, header: &BlockHeader, authorities: &[String]) -> Result<(), String> {
+        if header.timestamp == 0 {
+            return verify_header_authority(header, authorities);
+        }
+        self.verify_author_at(header.timestamp, &header.author, authorities)
+    }
 }
 
 #[cfg(test)]
@@ -59,16 +118,63 @@ mod tests {
     use tracing::error;
 
     use super::*;
+    use crate::node::consensus::MAX_TIMESTAMP_DRIFT;
 
     #[test]
     fn test_current_author() {
-        let aura = Aura::new(vec!["node_1".to_string(), "node_2".to_string()], 60);
+        let aura = Aura::new(60);
+        let authorities = vec!["node_1".to_string(), "node_2".to_string()];
         let slot = aura.current_slot() as usize;
-        let expected_author = &aura.authorities[slot % aura.authorities.len()];
+        let expected_author = &authorities[slot % authorities.len()];
         error!(
             "current slot: {:?}, expected_author: {:?}",
             slot, expected_author
         );
-        assert_eq!(aura.current_author(), expected_author);
+        let parent = Block::new_block(1, String::new(), vec![]);
+        assert_eq!(aura.current_author(&parent, &authorities).unwrap(), expected_author);
+    }
+
+    #[test]
+    fn test_verify_block_slot() {
+        let aura = Aura::new(5);
+        let mut parent = Block::new_block(1, String::new(), vec![]);
+        parent.timestamp = 1000;
+
+        let mut block = Block::new_block(2, String::new(), vec![]);
+        block.timestamp = 1004;
+        assert!(aura.verify_block_slot(&block, &parent).is_err());
+
+        block.timestamp = 1005;
+        assert!(aura.verify_block_slot(&block, &parent).is_ok());
+
+        block.timestamp = get_current_timespan() + MAX_TIMESTAMP_DRIFT + 1;
+        assert!(aura.verify_block_slot(&block, &parent).is_err());
+    }
+
+    #[test]
+    fn test_verify_header_author() {
+        let aura = Aura::new(5);
+        let authorities = vec!["node_1".to_string(), "node_2".to_string()];
+        let mut block = Block::new_block(1, String::new(), vec![]);
+        block.author = "node_2".to_string();
+
+        // Slot 201 belongs to node_2
+        block.timestamp = 1005;
+        assert!(aura.verify_header_author(&block.to_block_header(), &authorities).is_ok());
+        block.timestamp = 1010;
+        assert!(aura.verify_header_author(&block.to_block_header(), &authorities).is_err());
+
+        // Without a timestamp only membership is checked
+        block.timestamp = 0;
+        assert!(aura.verify_header_author(&block.to_block_header(), &authorities).is_ok());
+        block.author = "node_3".to_string();
+        assert!(aura.verify_header_author(&block.to_block_header(), &authorities).is_err());
+    }
+
+    #[test]
+    fn test_duration_until_next_slot() {
+        let aura = Aura::new(5);
+        let duration = aura.duration_until_next_slot();
+        assert!(duration > Duration::ZERO && duration <= Duration::from_secs(5));
     }
 }
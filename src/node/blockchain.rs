@@ -1,24 +1,42 @@
-use tracing::{error, info};
+use libp2p::PeerId;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, Notify};
+use tracing::{error, info, warn};
+use zeroize::Zeroizing;
 
 use super::blocks::block::Block;
+use super::blocks::block_headers::BlockHeader;
+use super::chain_spec::ChainSpec;
 use super::configuration::AppConfig;
 use super::consensus::Consensus;
-use super::p2p_server::handshake::Handshake;
-use super::transactions::transaction_pool::TransactionPool;
+use super::equivocation::{Equivocation, EquivocationProof};
+use super::finality::{Finality, Precommit};
+use super::p2p_server::handshake::{Handshake, PROTOCOL_VERSION};
+use super::p2p_server::node_key::NodeIdentity;
+use super::transactions::transaction_pool::{TransactionPool, TransactionPoolConfig};
 use crate::node::account_state::AccountState;
-use crate::node::aura::Aura;
 use crate::node::database::Database;
 use crate::node::file_utils::write_to_file;
 use crate::node::node_services::NodeServices;
+use crate::node::transactions::function_call::FunctionCall;
+use crate::node::transactions::report_equivocation::ReportEquivocation;
 use crate::node::transactions::transaction::Transaction;
+use crate::node::validator_set::ValidatorSets;
 
 pub struct Blockchain {
     pub name: String,
     db: Database,
     developer_mode: bool,
-    consensus: Aura,
+    keep_database: bool,
+    consensus: Box<dyn Consensus>,
     author_public_key: String,
-    author_secret_key: String,
+    author_secret_key: Zeroizing<String>,
+    chain_spec: ChainSpec,
+    transaction_pool: Mutex<TransactionPool>,
+    finalized_blocks: broadcast::Sender<Block>,
+    // Woken whenever a transaction enters the pool, for engines that seal on demand
+    transactions_ready: Arc<Notify>,
 }
 
 impl Blockchain {
@@ -27,21 +45,39 @@ impl Blockchain {
         author_public_key: String,
         author_secret_key: String,
         developer_mode: bool,
-        authorities: Vec<String>,
-    ) -> Blockchain {
+        chain_spec: ChainSpec,
+        transaction_pool_config: TransactionPoolConfig,
+    ) -> Result<Blockchain, String> {
         let db = Database::new_db(&name);
-        let step_duration = 60 / authorities.len() as u64;
         let blockchain = Blockchain {
             name,
             db,
             developer_mode,
-            consensus: Aura::new(authorities, step_duration),
+            keep_database: false,
+            consensus: chain_spec.consensus.build(chain_spec.block_time),
             author_public_key,
-            author_secret_key,
+            author_secret_key: Zeroizing::new(author_secret_key),
+            transaction_pool: Mutex::new(TransactionPool::new(
+                transaction_pool_config,
+                chain_spec.chain_id,
+            )),
+            chain_spec,
+            finalized_blocks: broadcast::channel(16).0,
+            transactions_ready: Arc::new(Notify::new()),
         };
 
-        Block::genesis_import_block(&blockchain.db);
-        blockchain
+        Block::genesis_import_block(&blockchain.db, &blockchain.chain_spec.genesis_allocations)?;
+        ValidatorSets::initialize(
+            &blockchain.db,
+            &blockchain.chain_spec.authorities,
+            blockchain.chain_spec.epoch_length,
+            blockchain
+                .chain_spec
+                .consensus
+                .slot_duration(blockchain.chain_spec.block_time),
+        )
+        .map_err(|e| format!("Failed to initialize the validator set: {}", e))?;
+        Ok(blockchain)
     }
 
     pub fn get_latest_block(&self) -> Option<Block> {
@@ -61,10 +97,17 @@ impl Blockchain {
         AccountState::get_current_nonce(public_key, &self.db)
     }
 
+    // Keeps the database of a developer mode node across restarts
+    pub fn set_keep_database(&mut self, keep_database: bool) {
+        self.keep_database = keep_database;
+    }
+
     pub fn shutdown_blockchain(&mut self) {
         if self.developer_mode {
             self.blockchain_write_to_file();
-            self.cleanup_db();
+            if !self.keep_database {
+                self.cleanup_db();
+            }
         }
     }
 
@@ -79,14 +122,104 @@ impl Blockchain {
     }
 
     pub fn import_block(&self, block: &Block) -> Result<(), String> {
-        self.consensus.verify_block_author(&block)?;
-        block.validate_block(&self.db)?;
-        Transaction::validate_transactions(&self.db, &block.transactions)?;
-        Block::add_block_to_chain(&self.db, block);
+        let parent = self
+            .get_latest_block()
+            .ok_or_else(|| "Failed to get the latest block in import_block".to_string())?;
+        let authorities = ValidatorSets::authorities_at(&self.db, block.index)?;
+        self.consensus.fork_choice(&parent, block)?;
+        block.verify_signature_version(&self.chain_spec)?;
+        self.consensus.verify_header(block, &parent, &authorities)?;
+        block.validate_block(&self.db, &self.chain_spec)?;
+        Transaction::validate_transactions(&self.db, &block.transactions, self.chain_spec.chain_id)?;
+        Block::add_block_to_chain(&self.db, block)?;
+        self.transaction_pool.lock().unwrap().prune(&self.db);
+
+        // Precommits that arrived before the block
+        if let Some(finalized_block) = Finality::try_finalize(&self.db, block.index)? {
+            self.on_finalized(finalized_block);
+        }
 
         Ok(())
     }
 
+    // Precommits to an imported block when this node is one of its authorities. The
+    // precommit is counted locally and returned to be gossiped.
+    pub fn precommit_block(&self, block: &Block) -> Result<Option<Precommit>, String> {
+        let authorities = ValidatorSets::authorities_at(&self.db, block.index)?;
+        if !authorities.contains(&self.author_public_key) {
+            return Ok(None);
+        }
+
+        let precommit = Precommit::new(
+            block,
+            &self.author_public_key,
+            &self.author_secret_key,
+            self.chain_spec.chain_id,
+        );
+        self.add_precommit(&precommit)?;
+        Ok(Some(precommit))
+    }
+
+    pub fn add_precommit(&self, precommit: &Precommit) -> Result<Option<Block>, String> {
+        let finalized_block = Finality::add_precommit(&self.db, precommit, self.chain_spec.chain_id)?;
+        if let Some(block) = &finalized_block {
+            self.on_finalized(block.clone());
+        }
+        Ok(finalized_block)
+    }
+
+    pub fn get_finalized_block(&self) -> Option<Block> {
+        Finality::get_finalized_block(&self.db)
+    }
+
+    pub fn subscribe_finalized_blocks(&self) -> broadcast::Receiver<Block> {
+        self.finalized_blocks.subscribe()
+    }
+
+    fn on_finalized(&self, block: Block) {
+        if let Err(e) = Equivocation::prune(&self.db, block.index) {
+            warn!("Failed to prune seen headers up to block {}: {}", block.index, e);
+        }
+        // Sending only fails when nobody is subscribed
+        let _ = self.finalized_blocks.send(block);
+    }
+
+    // Checks a block from the network against the headers seen so far, before it is imported
+    pub fn observe_block(&self, block: &Block) -> Option<EquivocationProof> {
+        match Equivocation::observe_block(&self.db, self.consensus.as_ref(), block) {
+            Ok(proof) => proof,
+            Err(e) => {
+                warn!("Failed to observe block {}: {}", block.index, e);
+                None
+            }
+        }
+    }
+
+    pub fn verify_equivocation(&self, proof: &EquivocationProof) -> Result<(), String> {
+        proof.verify(&ValidatorSets::get(&self.db)?)
+    }
+
+    // Authorities submit a report transaction for every new equivocation they learn about.
+    // Returns the transaction to be gossiped.
+    pub fn report_equivocation(&self, proof: &EquivocationProof) -> Result<Option<Transaction>, String> {
+        self.verify_equivocation(proof)?;
+        if proof.is_reported(&self.db) || !self.get_authorities()?.contains(&self.author_public_key) {
+            return Ok(None);
+        }
+
+        let nonce = self.get_current_nonce(&self.author_public_key)? + 1;
+        let mut transaction = Transaction::new_transaction(
+            self.author_public_key.clone(),
+            nonce,
+            FunctionCall::ReportEquivocation(ReportEquivocation {
+                proof: Box::new(proof.clone()),
+            }),
+        );
+        transaction.sign(&self.author_secret_key);
+        self.add_transaction_to_pool(&transaction)?;
+        Ok(Some(transaction))
+    }
+
     pub fn get_blocks(&self) -> Result<Vec<Block>, String> {
         Block::get_blocks(&self.db)
     }
@@ -104,9 +237,53 @@ impl Blockchain {
         Block::get_blocks_by_indexes(&self.db, indexes)
     }
 
+    pub fn duration_until_next_slot(&self) -> Duration {
+        self.consensus.duration_until_next_slot()
+    }
+
+    // Notified when a transaction enters the pool, if the consensus engine seals blocks as
+    // soon as there are transactions rather than on a schedule
+    pub fn seal_trigger(&self) -> Option<Arc<Notify>> {
+        if self.consensus.seals_on_transaction() {
+            Some(Arc::clone(&self.transactions_ready))
+        } else {
+            None
+        }
+    }
+
     #[allow(dead_code)]
-    pub fn current_author(&self) -> &String {
-        self.consensus.current_author()
+    // Author expected for the next block among its authorities
+    pub fn current_author(&self) -> Result<String, String> {
+        let latest_block = self
+            .get_latest_block()
+            .ok_or_else(|| "Failed to get the latest block".to_string())?;
+        let authorities = ValidatorSets::authorities_at(&self.db, latest_block.index + 1)?;
+        self.consensus.current_author(&latest_block, &authorities).cloned()
+    }
+
+    // Consensus checks on downloaded headers before their bodies are fetched. Only heights up
+    // to the epoch of the next block have known authorities; validator set changes for later
+    // epochs are in blocks not imported yet, so those headers are checked on import.
+    pub fn verify_header_authors(&self, headers: &[BlockHeader]) -> Result<(), String> {
+        let latest_block = self
+            .get_latest_block()
+            .ok_or_else(|| "Failed to get the latest block".to_string())?;
+        let validator_sets = ValidatorSets::get(&self.db)?;
+        let known_epoch = validator_sets.epoch_of(latest_block.index + 1);
+
+        for header in headers {
+            let epoch = validator_sets.epoch_of(header.index);
+            if epoch > known_epoch {
+                break;
+            }
+            let authorities = validator_sets.authorities_at_epoch(epoch);
+            self.consensus.verify_header_author(header, authorities)?;
+        }
+        Ok(())
+    }
+
+    pub fn get_authorities(&self) -> Result<Vec<String>, String> {
+        ValidatorSets::next_block_authorities(&self.db)
     }
 
     pub fn handshake(&self) -> Result<Handshake, String> {
@@ -119,19 +296,60 @@ impl Blockchain {
             .ok_or_else(|| "Failed to get genesis block".to_string())?;
 
         Ok(Handshake {
+            chain_id: self.chain_spec.chain_id,
+            protocol_version: PROTOCOL_VERSION,
+            capabilities: Handshake::local_capabilities(),
             genesis_block_hash: genesis_block.hash,
             latest_block_hash: latest_block.hash,
             latest_block_index: latest_block.index,
+            node_identity: None,
+            legacy: false,
         })
     }
 
+    // Binds the node's libp2p peer id to the author key
+    pub fn node_identity(&self, peer_id: &PeerId) -> NodeIdentity {
+        NodeIdentity::new(peer_id, &self.author_public_key, &self.author_secret_key)
+    }
+
+    pub fn chain_id(&self) -> u64 {
+        self.chain_spec.chain_id
+    }
+
     pub fn add_transaction_to_pool(&self, transaction: &Transaction) -> Result<(), String> {
-        transaction.validate_transaction(&self.db)?;
-        TransactionPool::add_transaction(&self.db, &transaction)
+        let next_block_index = self
+            .get_latest_block()
+            .map(|block| block.index + 1)
+            .unwrap_or(0);
+        transaction
+            .verify_signature_version(next_block_index, self.chain_spec.digest_signature_activation_height)?;
+        self.transaction_pool
+            .lock()
+            .unwrap()
+            .add_transaction(&self.db, transaction)?;
+        self.transactions_ready.notify_one();
+        Ok(())
     }
 
     pub fn get_transactions_from_pool(&self) -> Result<Vec<Transaction>, String> {
-        TransactionPool::get_transactions(&self.db)
+        Ok(self.transaction_pool.lock().unwrap().get_transactions())
+    }
+
+    pub fn get_pooled_transaction_hashes(&self, limit: usize) -> Vec<String> {
+        self.transaction_pool.lock().unwrap().get_transaction_hashes(limit)
+    }
+
+    // Pooled transactions with the given hashes, hashes not in the pool are skipped
+    pub fn get_pooled_transactions(&self, tx_hashes: &[String]) -> Vec<Transaction> {
+        let transaction_pool = self.transaction_pool.lock().unwrap();
+        tx_hashes
+            .iter()
+            .filter_map(|tx_hash| transaction_pool.get_transaction(tx_hash))
+            .collect()
+    }
+
+    pub fn is_transaction_pooled(&self, tx_hash: &str) -> bool {
+        self.transaction_pool.lock().unwrap().contains(tx_hash)
     }
 
     pub fn author_new_block(&self) -> Result<Block, String> {
@@ -139,16 +357,24 @@ impl Blockchain {
             Some(block) => block,
             None => return Err("Failed to get the latest block in author_new_block".to_string()),
         };
+        let authorities = ValidatorSets::authorities_at(&self.db, latest_block.index + 1)?;
+        self.consensus
+            .prepare_block(&self.author_public_key, &latest_block, &authorities)?;
 
         let index = latest_block.index + 1;
         let previous_hash = latest_block.hash;
-        let transactions = match TransactionPool::get_transactions(&self.db) {
-            Ok(transactions) => transactions,
-            Err(e) => return Err(format!("Failed to get transactions from pool: {}", e)),
-        };
+        let transactions = self
+            .transaction_pool
+            .lock()
+            .unwrap()
+            .block_transactions(&self.db, &self.chain_spec.block_limits);
+        if transactions.is_empty() && !self.chain_spec.allow_empty_blocks {
+            return Err("No transactions to author a block with.".to_string());
+        }
 
         let mut new_block = Block::new_block(index, previous_hash, transactions);
-        new_block.sign(&self.author_public_key, &self.author_secret_key);
+        self.consensus
+            .seal_block(&mut new_block, &self.author_public_key, &self.author_secret_key);
         self.import_block(&new_block)?;
         Ok(new_block)
     }
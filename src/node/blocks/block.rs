@@ -1,16 +1,34 @@
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use tracing::{error, info, warn};
+use tracing::{info, warn};
 
-use crate::node::database::Database;
+use crate::node::chain_spec::ChainSpec;
+use crate::node::database::{Database, StateUpdate};
 use crate::node::time_utils::get_current_timespan;
 use crate::node::transactions::transaction::Transaction;
-use crate::node::transactions::transaction_pool::TransactionPool;
-use crate::node::{metric, signature_keys};
+use crate::node::metric;
+use crate::node::signature_keys::{SignatureKeys, SignatureVersion};
+use crate::node::validator_set::ValidatorSets;
 
 use super::block_headers::BlockHeader;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy)]
+pub struct BlockLimits {
+    pub max_transactions: usize,
+    // Sum of the RLP encoded sizes of the block transactions
+    pub max_bytes: usize,
+}
+
+impl Default for BlockLimits {
+    fn default() -> Self {
+        BlockLimits {
+            max_transactions: 1000,
+            max_bytes: 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Block {
     pub index: usize,
     pub timestamp: u64,
@@ -19,30 +37,50 @@ pub struct Block {
     pub signature_r: String,
     pub signature_s: String,
     pub signature_v: i32,
+    #[serde(default)]
+    pub signature_version: SignatureVersion,
     pub hash: String,
     pub transactions: Vec<Transaction>,
 }
 
 impl Block {
     fn calculate_hash(&self) -> String {
-        let mut hasher = Sha256::new();
+        let transaction_hashes: Vec<String> =
+            self.transactions.iter().map(|tx| tx.hash.clone()).collect();
+        Self::hash_of(
+            self.index,
+            &self.previous_hash,
+            &transaction_hashes,
+            self.timestamp,
+            self.signature_version,
+        )
+    }
 
-        let transactions_hash_string = self
-            .transactions
-            .iter()
-            .map(|tx| format!("{}", tx.hash))
-            .collect::<Vec<String>>()
-            .join("");
+    // The block hash commits to the index, the parent and the transactions. The timestamp is
+    // only committed to by blocks signed with a timestamped digest.
+    pub fn hash_of(
+        index: usize,
+        previous_hash: &str,
+        transaction_hashes: &[String],
+        timestamp: u64,
+        signature_version: SignatureVersion,
+    ) -> String {
+        let mut hasher = Sha256::new();
 
         hasher.update(format!(
             "{}{}{}",
-            self.index, self.previous_hash, transactions_hash_string
+            index,
+            previous_hash,
+            transaction_hashes.join("")
         ));
+        if signature_version == SignatureVersion::TimestampedDigest {
+            hasher.update(format!(":{}", timestamp));
+        }
         let result = hasher.finalize();
         format!("{:x}", result)
     }
 
-    pub fn new_genesis_block() -> Block {
+    pub fn new_genesis_block(allocations: &[(String, u64)]) -> Block {
         let mut genesis_block = Block {
             author: String::new(),
             index: 0,
@@ -51,11 +89,12 @@ impl Block {
             signature_r: String::new(),
             signature_s: String::new(),
             signature_v: 0,
+            signature_version: SignatureVersion::default(),
             hash: String::new(),
             transactions: vec![],
         };
 
-        genesis_block.transactions = Transaction::new_genesis_transactions();
+        genesis_block.transactions = Transaction::new_genesis_transactions(allocations);
         genesis_block.hash = genesis_block.calculate_hash();
         genesis_block
     }
@@ -69,6 +108,7 @@ impl Block {
             signature_r: String::new(),
             signature_s: String::new(),
             signature_v: 0,
+            signature_version: SignatureVersion::default(),
             hash: String::new(),
             transactions,
         };
@@ -78,23 +118,80 @@ impl Block {
     }
 
     pub fn sign(&mut self, author: &str, secret_key: &str) {
-        let hash_bytes = self.hash.as_bytes();
-        let (r, s, v) = signature_keys::SignatureKeys::sign(secret_key, hash_bytes);
+        self.sign_with_version(author, secret_key, SignatureVersion::TimestampedDigest)
+            .expect("Failed to compute block signing digest");
+    }
+
+    // The hash is recomputed first, since it depends on the signature version
+    pub fn sign_with_version(
+        &mut self,
+        author: &str,
+        secret_key: &str,
+        signature_version: SignatureVersion,
+    ) -> Result<(), String> {
+        self.signature_version = signature_version;
+        self.hash = self.calculate_hash();
+        let digest = Self::signing_digest(&self.hash, signature_version)?;
+        let (r, s, v) = SignatureKeys::sign_digest(secret_key, &digest);
 
         self.signature_r = r;
         self.signature_s = s;
         self.signature_v = v;
         self.author = author.to_string();
+        Ok(())
+    }
+
+    pub fn signing_digest(hash: &str, signature_version: SignatureVersion) -> Result<[u8; 32], String> {
+        match signature_version {
+            SignatureVersion::Legacy => Ok(SignatureKeys::keccak256(hash.as_bytes())),
+            SignatureVersion::Digest | SignatureVersion::TimestampedDigest => {
+                SignatureKeys::digest_from_hex(hash)
+            }
+            SignatureVersion::TypedData => {
+                Err("Typed data signatures are not supported for blocks".to_string())
+            }
+        }
     }
 
     fn verify_signature(&self) -> Result<bool, String> {
-        let author = &self.author;
-        let data = self.hash.as_bytes();
+        let digest = Self::signing_digest(&self.hash, self.signature_version)?;
         let r = &self.signature_r;
         let s = &self.signature_s;
         let v = self.signature_v;
 
-        signature_keys::SignatureKeys::verify(author, data, r, s, v)
+        let recovered_author = SignatureKeys::recover_address_from_digest(&digest, r, s, v)?;
+        Ok(recovered_author == self.author)
+    }
+
+    pub fn verify_signature_version(&self, chain_spec: &ChainSpec) -> Result<(), String> {
+        let digest_signature_activation_height = chain_spec.digest_signature_activation_height;
+        if !self
+            .signature_version
+            .is_allowed_at(self.index, digest_signature_activation_height)
+        {
+            return Err(format!(
+                "Invalid block: Legacy signatures are not accepted from block {}, block {} was signed with {:?}.",
+                digest_signature_activation_height, self.index, self.signature_version
+            ));
+        }
+
+        // Slot and turn rules rely on the timestamp, so from the activation height on it has to
+        // be signed
+        let timestamp_activation_height = chain_spec.timestamp_signature_activation_height;
+        if self.index >= timestamp_activation_height
+            && self.signature_version != SignatureVersion::TimestampedDigest
+        {
+            return Err(format!(
+                "Invalid block: Blocks from {} must sign their timestamp, block {} was signed with {:?}.",
+                timestamp_activation_height, self.index, self.signature_version
+            ));
+        }
+
+        for transaction in self.transactions.iter() {
+            transaction.verify_signature_version(self.index, digest_signature_activation_height)?;
+        }
+
+        Ok(())
     }
 
     pub fn get_latest_block(db: &Database) -> Option<Block> {
@@ -109,9 +206,9 @@ impl Block {
         }
     }
 
-    pub fn validate_block(&self, db: &Database) -> Result<bool, String> {
+    pub fn validate_block(&self, db: &Database, chain_spec: &ChainSpec) -> Result<bool, String> {
         match Block::get_latest_block(db) {
-            Some(latest_block) => {
+            Some(_) => {
                 match self.verify_signature() {
                     Ok(is_verified) => {
                         if !is_verified {
@@ -124,20 +221,10 @@ impl Block {
                     Err(e) => return Err(format!("Signature verification error: {}", e)),
                 }
 
-                if self.index != latest_block.index + 1 {
-                    return Err(format!(
-                        "Invalid block: The block index should be {}, but it was {}.",
-                        latest_block.index + 1,
-                        self.index
-                    ));
-                }
-
-                if self.previous_hash != latest_block.hash {
-                    return Err(format!(
-                        "Invalid block: The previous hash should be {}, but it was {}.",
-                        latest_block.hash, self.previous_hash
-                    ));
+                if self.transactions.is_empty() && !chain_spec.allow_empty_blocks {
+                    return Err("Invalid block: Empty blocks are not allowed.".to_string());
                 }
+                self.verify_limits(&chain_spec.block_limits)?;
 
                 Ok(true)
             }
@@ -145,6 +232,26 @@ impl Block {
         }
     }
 
+    fn verify_limits(&self, limits: &BlockLimits) -> Result<(), String> {
+        if self.transactions.len() > limits.max_transactions {
+            return Err(format!(
+                "Invalid block: {} transactions exceed the limit of {}.",
+                self.transactions.len(),
+                limits.max_transactions
+            ));
+        }
+
+        let size: usize = self.transactions.iter().map(|tx| tx.encoded_size()).sum();
+        if size > limits.max_bytes {
+            return Err(format!(
+                "Invalid block: {} bytes of transactions exceed the limit of {}.",
+                size, limits.max_bytes
+            ));
+        }
+
+        Ok(())
+    }
+
     pub fn get_blocks(db: &Database) -> Result<Vec<Block>, String> {
         match db.get_keys_values_by_cf_name("block") {
             Ok(entries) => {
@@ -253,15 +360,16 @@ impl Block {
         Some((keys, values))
     }
 
-    pub fn genesis_import_block(db: &Database) {
+    pub fn genesis_import_block(db: &Database, allocations: &[(String, u64)]) -> Result<(), String> {
         match Self::get_genesis_block(db) {
             Some(_) => {
                 warn!("Genesis block already exists.");
+                Ok(())
             }
             None => {
                 info!("Genesis block does not exist, creating new one...");
-                let genesis_block = Self::new_genesis_block();
-                Self::add_block_to_chain(db, &genesis_block);
+                let genesis_block = Self::new_genesis_block(allocations);
+                Self::add_block_to_chain(db, &genesis_block)
             }
         }
     }
@@ -278,14 +386,13 @@ impl Block {
         }
     }
 
-    pub fn add_block_to_chain(db: &Database, block: &Block) {
+    pub fn add_block_to_chain(db: &Database, block: &Block) -> Result<(), String> {
         // Storage for keys and values
         let mut cf_storage: Vec<String> = Vec::new();
         let mut keys_storage: Vec<Vec<u8>> = Vec::new();
         let mut values_storage: Vec<Vec<u8>> = Vec::new();
 
         let mut operations: Vec<(&str, &[u8], Option<&[u8]>)> = Vec::new();
-        let mut tx_keys_to_delete: Vec<Vec<u8>> = Vec::new(); // Store tx keys to delete
 
         // Handle block state
         if let Some((block_keys, block_values)) = block.state_block() {
@@ -295,8 +402,7 @@ impl Block {
                 values_storage.push(value);
             }
         } else {
-            error!("Failed to serialize block for storage.");
-            return;
+            return Err("Failed to serialize block for storage.".to_string());
         }
 
         // Handle Blockchain State
@@ -307,25 +413,17 @@ impl Block {
                 values_storage.push(value);
             }
         } else {
-            error!("Failed to serialize block for storage.");
-            return;
+            return Err("Failed to serialize block for storage.".to_string());
         }
 
-        // Handle transactions State
-        for tx in block.transactions.iter() {
-            let updates = tx.state_transaction(&db);
-
-            for update in updates {
-                if let Some((key, value)) = update {
-                    cf_storage.push("state".to_string());
-                    keys_storage.push(key);
-                    values_storage.push(value);
-                }
-            }
-
-            // Prepare keys for deletion from tx_pool
-            let tx_key = TransactionPool::construct_tx_pool_key(&tx.hash);
-            tx_keys_to_delete.push(tx_key);
+        // Handle transactions State, staged so each transaction builds on the ones before it
+        db.begin_staging();
+        let updates = Self::state_transactions(db, block);
+        db.end_staging();
+        for (key, value) in updates? {
+            cf_storage.push("state".to_string());
+            keys_storage.push(key);
+            values_storage.push(value);
         }
 
         // Prepare operations for database write
@@ -337,11 +435,6 @@ impl Block {
             operations.push((cf_name, key.as_slice(), Some(value.as_slice())));
         }
 
-        // Delete transactions from tx_pool
-        for tx_key in tx_keys_to_delete.iter() {
-            operations.push(("tx_pool", tx_key.as_slice(), None));
-        }
-
         // Update the database
         match &db.write(operations) {
             Ok(_) => {
@@ -361,6 +454,31 @@ impl Block {
             }
             Err(e) => panic!("Failed add_block_to_chain: {}", e),
         }
+
+        Ok(())
+    }
+
+    // State updates of the block transactions followed by the validator set changes they make,
+    // all committed in the block's batch
+    fn state_transactions(db: &Database, block: &Block) -> Result<Vec<StateUpdate>, String> {
+        let mut updates = Vec::new();
+        for tx in block.transactions.iter() {
+            for (key, value) in tx.state_transaction(db)?.into_iter().flatten() {
+                db.stage("state", &key, &value);
+                updates.push((key, value));
+            }
+        }
+
+        for (key, value) in ValidatorSets::enact_proposals(db, block)? {
+            db.stage("state", &key, &value);
+            updates.push((key, value));
+        }
+        for (key, value) in ValidatorSets::remove_offenders(db, block)? {
+            db.stage("state", &key, &value);
+            updates.push((key, value));
+        }
+
+        Ok(updates)
     }
 
     pub fn to_block_header(&self) -> BlockHeader {
@@ -371,7 +489,9 @@ impl Block {
             signature_r: self.signature_r.clone(),
             signature_s: self.signature_s.clone(),
             signature_v: self.signature_v,
+            signature_version: self.signature_version,
             hash: self.hash.clone(),
+            timestamp: self.timestamp,
         }
     }
 }
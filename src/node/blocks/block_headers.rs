@@ -1,12 +1,15 @@
 use serde::{Deserialize, Serialize};
 
+use crate::node::blocks::block::Block;
+use crate::node::signature_keys::{SignatureKeys, SignatureVersion};
+
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BlockHeaders {
     pub block_headers: Vec<BlockHeader>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BlockHeader {
     pub index: usize,
     pub previous_hash: String,
@@ -14,15 +17,34 @@ pub struct BlockHeader {
     pub signature_r: String,
     pub signature_s: String,
     pub signature_v: i32,
+    #[serde(default)]
+    pub signature_version: SignatureVersion,
     pub hash: String,
+    // Carried so the slot of an Aura header can be checked before the body is downloaded, and
+    // only covered by the hash and signature of timestamped digest headers. Zero in headers
+    // from before it was added.
+    #[serde(default)]
+    pub timestamp: u64,
 }
 
-impl BlockHeaders {
-    pub fn to_block_indexes(&self) -> Vec<usize>{
-         self
-            .block_headers
-            .iter()
-            .map(|header| header.index)
-            .collect()
+impl BlockHeader {
+    // The hash commits to the transactions, so only the author's signature over it can be
+    // checked without the block body
+    pub fn verify_signature(&self) -> Result<(), String> {
+        let digest = Block::signing_digest(&self.hash, self.signature_version)?;
+        let signed_by_author = SignatureKeys::verify_digest(
+            &self.author,
+            &digest,
+            &self.signature_r,
+            &self.signature_s,
+            self.signature_v,
+        )?;
+        if !signed_by_author {
+            return Err(format!(
+                "Header {} is not signed by its author {}",
+                self.hash, self.author
+            ));
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,60 @@
+use super::blocks::block::BlockLimits;
+use super::consensus::ConsensusEngine;
+
+pub const DEFAULT_CHAIN_ID: u64 = 1;
+
+// Consensus parameters every node of a chain has to agree on
+#[derive(Debug, Clone)]
+pub struct ChainSpec {
+    // Tells networks apart in handshakes, peers of another chain are disconnected
+    pub chain_id: u64,
+    pub consensus: ConsensusEngine,
+    // Genesis authorities, later changed by validator governance transactions
+    pub authorities: Vec<String>,
+    // Validator set changes take effect at the start of the next epoch
+    pub epoch_length: u64,
+    // Duration of an Aura slot or the Clique period in seconds, unused by instant seal
+    pub block_time: u64,
+    pub allow_empty_blocks: bool,
+    pub digest_signature_activation_height: usize,
+    // Blocks from this height on must be signed with a timestamped digest
+    pub timestamp_signature_activation_height: usize,
+    pub block_limits: BlockLimits,
+    // Balances funded at genesis on top of the built-in ones, e.g. dev accounts
+    pub genesis_allocations: Vec<(String, u64)>,
+}
+
+impl Default for ChainSpec {
+    fn default() -> Self {
+        ChainSpec {
+            chain_id: DEFAULT_CHAIN_ID,
+            consensus: ConsensusEngine::Aura,
+            authorities: vec![],
+            epoch_length: 100,
+            block_time: 5,
+            allow_empty_blocks: false,
+            digest_signature_activation_height: 0,
+            timestamp_signature_activation_height: 0,
+            block_limits: BlockLimits::default(),
+            genesis_allocations: vec![],
+        }
+    }
+}
+
+impl ChainSpec {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.authorities.is_empty() {
+            return Err("Chain spec has no authorities".to_string());
+        }
+        if self.consensus == ConsensusEngine::InstantSeal && self.authorities.len() > 1 {
+            return Err("Instant seal supports a single authority".to_string());
+        }
+        if self.epoch_length == 0 {
+            return Err("Chain spec epoch_length must be at least 1 block".to_string());
+        }
+        if self.block_time == 0 {
+            return Err("Chain spec block_time must be at least 1 second".to_string());
+        }
+        Ok(())
+    }
+}
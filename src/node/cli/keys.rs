@@ -0,0 +1,170 @@
+use std::fs;
+use std::path::PathBuf;
+
+use clap::{ArgGroup, Args, Subcommand};
+use zeroize::Zeroizing;
+
+use crate::node::keystore::{read_passphrase, Keystore};
+use crate::node::signature_keys::SignatureKeys;
+
+#[derive(Args, Debug)]
+#[clap(group(ArgGroup::new("secret").required(true)))]
+pub struct SecretKeyArgs {
+    /// Hex secret key (visible in shell history, prefer --secret-key-file)
+    #[clap(long, group = "secret")]
+    secret_key: Option<String>,
+    /// File containing the hex secret key
+    #[clap(long, group = "secret")]
+    secret_key_file: Option<PathBuf>,
+    /// Encrypted keystore file holding the secret key
+    #[clap(long, group = "secret")]
+    keystore: Option<PathBuf>,
+    /// File containing the keystore passphrase (falls back to CLUTCH_KEYSTORE_PASSPHRASE)
+    #[clap(long, default_value = "")]
+    passphrase_file: String,
+}
+
+#[derive(Args, Debug)]
+#[clap(group(ArgGroup::new("payload").required(true)))]
+pub struct PayloadArgs {
+    /// UTF-8 message, signed as keccak256(message)
+    #[clap(long, group = "payload")]
+    message: Option<String>,
+    /// Hex payload, signed as keccak256(bytes)
+    #[clap(long, group = "payload")]
+    hex: Option<String>,
+    /// 32-byte hex digest, signed as is
+    #[clap(long, group = "payload")]
+    digest: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum KeysCommand {
+    /// Generate a new secp256k1 key pair
+    Generate,
+    /// Show the public key and address of a secret key
+    Inspect {
+        #[clap(flatten)]
+        secret: SecretKeyArgs,
+    },
+    /// Print the address of a secret key
+    Address {
+        #[clap(flatten)]
+        secret: SecretKeyArgs,
+    },
+    /// Sign a payload and print the r, s and v signature values
+    Sign {
+        #[clap(flatten)]
+        secret: SecretKeyArgs,
+        #[clap(flatten)]
+        payload: PayloadArgs,
+    },
+    /// Check that a signature over a payload was made by an address
+    Verify {
+        #[clap(long)]
+        address: String,
+        #[clap(long)]
+        r: String,
+        #[clap(long)]
+        s: String,
+        #[clap(long)]
+        v: i32,
+        #[clap(flatten)]
+        payload: PayloadArgs,
+    },
+}
+
+impl KeysCommand {
+    pub fn run(self) -> Result<(), String> {
+        match self {
+            KeysCommand::Generate => {
+                let keys = SignatureKeys::generate_new_keypair();
+                println!("Address: {}", keys.address_key);
+                println!("Public key: {}", keys.public_key);
+                println!("Secret key: {}", keys.secret_key);
+            }
+            KeysCommand::Inspect { secret } => {
+                let secret_key = secret.load()?;
+                println!(
+                    "Address: {}",
+                    SignatureKeys::address_from_secret_key(&secret_key)?
+                );
+                println!(
+                    "Public key: {}",
+                    SignatureKeys::public_key_from_secret_key(&secret_key)?
+                );
+            }
+            KeysCommand::Address { secret } => {
+                let secret_key = secret.load()?;
+                println!("{}", SignatureKeys::address_from_secret_key(&secret_key)?);
+            }
+            KeysCommand::Sign { secret, payload } => {
+                let secret_key = secret.load()?;
+                let (r, s, v) = SignatureKeys::sign_digest(&secret_key, &payload.digest()?);
+                println!("r: {}", r);
+                println!("s: {}", s);
+                println!("v: {}", v);
+            }
+            KeysCommand::Verify {
+                address,
+                r,
+                s,
+                v,
+                payload,
+            } => {
+                let recovered_address = SignatureKeys::recover_address_from_digest(
+                    &payload.digest()?,
+                    r.trim_start_matches("0x"),
+                    s.trim_start_matches("0x"),
+                    v,
+                )?;
+                if recovered_address != address.to_lowercase() {
+                    return Err(format!(
+                        "Signature was made by {}, not {}",
+                        recovered_address, address
+                    ));
+                }
+                println!("Signature is valid");
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SecretKeyArgs {
+    // Returns the hex secret key without 0x prefix, checked to be a valid secp256k1 key
+    pub fn load(&self) -> Result<Zeroizing<String>, String> {
+        let secret_key = if let Some(secret_key) = &self.secret_key {
+            Zeroizing::new(secret_key.clone())
+        } else if let Some(path) = &self.secret_key_file {
+            Zeroizing::new(fs::read_to_string(path).map_err(|e| {
+                format!("Failed to read secret key file '{}': {}", path.display(), e)
+            })?)
+        } else if let Some(path) = &self.keystore {
+            let passphrase = read_passphrase(&self.passphrase_file)?;
+            Keystore::load(path)?.decrypt(&passphrase)?
+        } else {
+            return Err("No secret key given".to_string());
+        };
+
+        let secret_key = Zeroizing::new(secret_key.trim().trim_start_matches("0x").to_string());
+        SignatureKeys::address_from_secret_key(&secret_key)?;
+        Ok(secret_key)
+    }
+}
+
+impl PayloadArgs {
+    fn digest(&self) -> Result<[u8; 32], String> {
+        if let Some(message) = &self.message {
+            Ok(SignatureKeys::keccak256(message.as_bytes()))
+        } else if let Some(data) = &self.hex {
+            let bytes = hex::decode(data.trim_start_matches("0x"))
+                .map_err(|_| "Invalid hex payload".to_string())?;
+            Ok(SignatureKeys::keccak256(&bytes))
+        } else if let Some(digest) = &self.digest {
+            SignatureKeys::digest_from_hex(digest)
+        } else {
+            Err("No payload given".to_string())
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::{Args, Subcommand};
+use zeroize::Zeroizing;
+
+use crate::node::keystore::{read_passphrase, Keystore, KeystoreCipher, KeystoreKdf};
+use crate::node::signature_keys::SignatureKeys;
+
+#[derive(Args, Debug)]
+pub struct EncryptionArgs {
+    /// Directory holding the keystore files
+    #[clap(long, default_value = "keystore")]
+    keystore_dir: PathBuf,
+    /// File containing the passphrase (falls back to CLUTCH_KEYSTORE_PASSPHRASE)
+    #[clap(long, default_value = "")]
+    passphrase_file: String,
+    /// Key derivation function: scrypt or argon2id
+    #[clap(long, default_value = "scrypt")]
+    kdf: String,
+    /// Cipher: aes-128-ctr or aes-256-gcm
+    #[clap(long, default_value = "aes-128-ctr")]
+    cipher: String,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum KeystoreCommand {
+    /// Generate a new key and store it encrypted
+    Create {
+        #[clap(flatten)]
+        encryption: EncryptionArgs,
+    },
+    /// Encrypt an existing hex secret key read from a file
+    Import {
+        #[clap(long)]
+        secret_key_file: PathBuf,
+        #[clap(flatten)]
+        encryption: EncryptionArgs,
+    },
+    /// List the addresses of the keys in the keystore directory
+    List {
+        #[clap(long, default_value = "keystore")]
+        keystore_dir: PathBuf,
+    },
+}
+
+impl KeystoreCommand {
+    pub fn run(self) -> Result<(), String> {
+        match self {
+            KeystoreCommand::Create { encryption } => {
+                let keys = SignatureKeys::generate_new_keypair();
+                let secret_key = Zeroizing::new(keys.secret_key);
+                encryption.store(&secret_key)
+            }
+            KeystoreCommand::Import {
+                secret_key_file,
+                encryption,
+            } => {
+                let content = Zeroizing::new(fs::read_to_string(&secret_key_file).map_err(|e| {
+                    format!(
+                        "Failed to read secret key file '{}': {}",
+                        secret_key_file.display(),
+                        e
+                    )
+                })?);
+                encryption.store(content.trim())
+            }
+            KeystoreCommand::List { keystore_dir } => list(&keystore_dir),
+        }
+    }
+}
+
+impl EncryptionArgs {
+    fn store(&self, secret_key: &str) -> Result<(), String> {
+        let kdf = KeystoreKdf::from_name(&self.kdf)?;
+        let cipher = KeystoreCipher::from_name(&self.cipher)?;
+        let passphrase = read_passphrase(&self.passphrase_file)?;
+
+        let keystore = Keystore::encrypt(secret_key, &passphrase, kdf, cipher)?;
+        let path = keystore.save(&self.keystore_dir)?;
+        println!("Address: {}", keystore.address());
+        println!("Keystore: {}", path.display());
+        Ok(())
+    }
+}
+
+fn list(keystore_dir: &Path) -> Result<(), String> {
+    for (path, keystore) in Keystore::list(keystore_dir)? {
+        println!("{} {}", keystore.address(), path.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,46 @@
+pub mod keys;
+pub mod keystore;
+pub mod node_key;
+pub mod tx;
+
+use clap::Subcommand;
+
+use keys::KeysCommand;
+use keystore::KeystoreCommand;
+use node_key::NodeKeyCommand;
+use tx::TxCommand;
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Generate, inspect and sign with secp256k1 keys
+    Keys {
+        #[clap(subcommand)]
+        command: KeysCommand,
+    },
+    /// Manage encrypted author keys
+    Keystore {
+        #[clap(subcommand)]
+        command: KeystoreCommand,
+    },
+    /// Manage the libp2p node key
+    NodeKey {
+        #[clap(subcommand)]
+        command: NodeKeyCommand,
+    },
+    /// Build, sign, encode and send transactions offline
+    Tx {
+        #[clap(subcommand)]
+        command: TxCommand,
+    },
+}
+
+impl Command {
+    pub async fn run(self) -> Result<(), String> {
+        match self {
+            Command::Keys { command } => command.run(),
+            Command::Keystore { command } => command.run(),
+            Command::NodeKey { command } => command.run(),
+            Command::Tx { command } => command.run().await,
+        }
+    }
+}
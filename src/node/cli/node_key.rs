@@ -0,0 +1,36 @@
+use std::path::PathBuf;
+
+use clap::Subcommand;
+
+use crate::node::p2p_server::node_key::{generate_node_key, load_node_key};
+
+#[derive(Subcommand, Debug)]
+pub enum NodeKeyCommand {
+    /// Generate a libp2p node key file and print its peer id
+    Generate {
+        #[clap(long)]
+        file: PathBuf,
+    },
+    /// Print the peer id of a node key file, to put in other nodes' bootstrap_nodes
+    PeerId {
+        #[clap(long)]
+        file: PathBuf,
+    },
+}
+
+impl NodeKeyCommand {
+    pub fn run(self) -> Result<(), String> {
+        match self {
+            NodeKeyCommand::Generate { file } => {
+                let node_key = generate_node_key(&file)?;
+                println!("Peer id: {}", node_key.public().to_peer_id());
+                println!("Node key: {}", file.display());
+            }
+            NodeKeyCommand::PeerId { file } => {
+                let node_key = load_node_key(&file)?;
+                println!("{}", node_key.public().to_peer_id());
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,431 @@
+use std::fs;
+use std::io::Read;
+
+use clap::{ArgGroup, Args, Subcommand};
+use futures::{SinkExt, StreamExt};
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::protocol::Message;
+
+use super::keys::SecretKeyArgs;
+use crate::node::chain_spec::DEFAULT_CHAIN_ID;
+use crate::node::coordinate::Coordinates;
+use crate::node::rlp_encoding::encode;
+use crate::node::signature_keys::{SignatureKeys, SignatureVersion};
+use crate::node::transactions::{
+    complain_arrival::ComplainArrival, confirm_arrival::ConfirmArrival,
+    function_call::FunctionCall, propose_validator::ProposeValidator,
+    remove_validator::RemoveValidator, report_equivocation::ReportEquivocation,
+    ride_acceptance::RideAcceptance, ride_cancel::RideCancel, ride_offer::RideOffer,
+    ride_pay::RidePay, ride_request::RideRequest, transaction::Transaction, transfer::Transfer,
+    vote_validator::VoteValidator,
+};
+
+const DEFAULT_RPC_URL: &str = "ws://127.0.0.1:8081";
+
+#[derive(Args, Debug)]
+pub struct TxInputArgs {
+    /// Transaction JSON file, or - for stdin
+    #[clap(long, default_value = "-")]
+    tx: String,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TxCommand {
+    /// Build an unsigned transaction and print it as JSON
+    Build {
+        #[clap(long)]
+        from: String,
+        /// Nonce to use, fetched with get_next_nonce from --rpc when omitted
+        #[clap(long)]
+        nonce: Option<u64>,
+        /// Fee burned from the sender, higher fees are included first
+        #[clap(long, default_value = "0")]
+        fee: u64,
+        #[clap(long, default_value = DEFAULT_RPC_URL)]
+        rpc: String,
+        #[clap(subcommand)]
+        call: FunctionCallCommand,
+    },
+    /// Build an unsigned transaction that cancels the pending transaction with the same nonce
+    Cancel {
+        #[clap(long)]
+        from: String,
+        #[clap(long)]
+        nonce: u64,
+        /// At least the fee of the pending transaction
+        #[clap(long)]
+        fee: u64,
+    },
+    /// Sign a transaction JSON and print the signed transaction
+    Sign {
+        #[clap(flatten)]
+        input: TxInputArgs,
+        #[clap(flatten)]
+        secret: SecretKeyArgs,
+        /// Signing scheme: digest, typed-data or legacy
+        #[clap(long, default_value = "digest")]
+        signature_version: String,
+        /// Chain a typed-data signature is valid on
+        #[clap(long, default_value_t = DEFAULT_CHAIN_ID)]
+        chain_id: u64,
+    },
+    /// Print the RLP hex of a signed transaction for send_raw_transaction
+    Encode {
+        #[clap(flatten)]
+        input: TxInputArgs,
+    },
+    /// Decode RLP hex into transaction JSON, recovering the sender
+    Decode {
+        #[clap(long)]
+        raw: String,
+        /// Chain a typed-data signature is recovered for
+        #[clap(long, default_value_t = DEFAULT_CHAIN_ID)]
+        chain_id: u64,
+    },
+    /// Submit a signed transaction through send_raw_transaction
+    #[clap(group(ArgGroup::new("source").required(true)))]
+    Send {
+        /// Transaction JSON file, or - for stdin
+        #[clap(long, group = "source")]
+        tx: Option<String>,
+        /// RLP hex as printed by `tx encode`
+        #[clap(long, group = "source")]
+        raw: Option<String>,
+        /// Chain a typed-data signature of --raw is recovered for
+        #[clap(long, default_value_t = DEFAULT_CHAIN_ID)]
+        chain_id: u64,
+        #[clap(long, default_value = DEFAULT_RPC_URL)]
+        rpc: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum FunctionCallCommand {
+    Transfer {
+        #[clap(long)]
+        to: String,
+        #[clap(long)]
+        value: u64,
+    },
+    RideRequest {
+        #[clap(long, allow_hyphen_values = true)]
+        pickup_latitude: f64,
+        #[clap(long, allow_hyphen_values = true)]
+        pickup_longitude: f64,
+        #[clap(long, allow_hyphen_values = true)]
+        dropoff_latitude: f64,
+        #[clap(long, allow_hyphen_values = true)]
+        dropoff_longitude: f64,
+        #[clap(long)]
+        fare: u64,
+    },
+    RideOffer {
+        #[clap(long)]
+        ride_request_transaction_hash: String,
+        #[clap(long)]
+        fare: u64,
+    },
+    RideAcceptance {
+        #[clap(long)]
+        ride_offer_transaction_hash: String,
+    },
+    RidePay {
+        #[clap(long)]
+        ride_acceptance_transaction_hash: String,
+        #[clap(long)]
+        fare: u64,
+    },
+    RideCancel {
+        #[clap(long)]
+        ride_acceptance_transaction_hash: String,
+    },
+    ConfirmArrival {
+        #[clap(long)]
+        ride_acceptance_transaction_hash: String,
+    },
+    ComplainArrival {
+        #[clap(long)]
+        ride_acceptance_transaction_hash: String,
+    },
+    /// Propose adding an authority, the proposer votes for it
+    ProposeValidator {
+        #[clap(long)]
+        validator: String,
+    },
+    /// Vote for a pending validator proposal
+    VoteValidator {
+        #[clap(long)]
+        proposal_transaction_hash: String,
+    },
+    /// Propose removing an authority, the proposer votes for it
+    RemoveValidator {
+        #[clap(long)]
+        validator: String,
+    },
+    /// Report an authority that signed two blocks in one slot
+    ReportEquivocation {
+        /// Equivocation proof JSON file, as gossiped by the node that detected it
+        #[clap(long)]
+        proof: String,
+    },
+    /// Any function call as JSON, e.g. {"function_call_type":"Transfer","arguments":{...}}
+    Json { function_call: String },
+}
+
+impl TxCommand {
+    pub async fn run(self) -> Result<(), String> {
+        match self {
+            TxCommand::Build {
+                from,
+                nonce,
+                fee,
+                rpc,
+                call,
+            } => {
+                let nonce = match nonce {
+                    Some(nonce) => nonce,
+                    None => get_next_nonce(&rpc, &from).await?,
+                };
+                let transaction = Transaction::new_transaction_with_fee(
+                    from,
+                    nonce,
+                    fee,
+                    call.into_function_call()?,
+                );
+                print_transaction(&transaction)
+            }
+            TxCommand::Cancel { from, nonce, fee } => {
+                print_transaction(&Transaction::new_cancel_transaction(from, nonce, fee))
+            }
+            TxCommand::Sign {
+                input,
+                secret,
+                signature_version,
+                chain_id,
+            } => {
+                let mut transaction = input.load()?;
+                let secret_key = secret.load()?;
+                let address = SignatureKeys::address_from_secret_key(&secret_key)?;
+                if address != transaction.from.to_lowercase() {
+                    return Err(format!(
+                        "Secret key belongs to {}, but the transaction is from {}",
+                        address, transaction.from
+                    ));
+                }
+                transaction.sign_with_version(
+                    &secret_key,
+                    parse_signature_version(&signature_version)?,
+                    chain_id,
+                )?;
+                print_transaction(&transaction)
+            }
+            TxCommand::Encode { input } => {
+                let transaction = input.load()?;
+                println!("0x{}", encode_raw_transaction(&transaction)?);
+                Ok(())
+            }
+            TxCommand::Decode { raw, chain_id } => {
+                let transaction = decode_raw_transaction(&raw, chain_id)?;
+                print_transaction(&transaction)
+            }
+            TxCommand::Send {
+                tx,
+                raw,
+                chain_id,
+                rpc,
+            } => {
+                let raw = match (tx, raw) {
+                    (_, Some(raw)) => {
+                        // Refused before sending when the signature does not recover its sender
+                        decode_raw_transaction(&raw, chain_id)?;
+                        raw
+                    }
+                    (Some(tx), None) => {
+                        let transaction = TxInputArgs { tx }.load()?;
+                        format!("0x{}", encode_raw_transaction(&transaction)?)
+                    }
+                    (None, None) => return Err("No transaction given".to_string()),
+                };
+                let result = rpc_call(&rpc, "send_raw_transaction", serde_json::json!(raw)).await?;
+                println!("{}", result);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl TxInputArgs {
+    fn load(&self) -> Result<Transaction, String> {
+        let content = if self.tx == "-" {
+            let mut content = String::new();
+            std::io::stdin()
+                .read_to_string(&mut content)
+                .map_err(|e| format!("Failed to read transaction from stdin: {}", e))?;
+            content
+        } else {
+            fs::read_to_string(&self.tx)
+                .map_err(|e| format!("Failed to read transaction file '{}': {}", self.tx, e))?
+        };
+        serde_json::from_str(&content).map_err(|e| format!("Invalid transaction JSON: {}", e))
+    }
+}
+
+impl FunctionCallCommand {
+    fn into_function_call(self) -> Result<FunctionCall, String> {
+        let function_call = match self {
+            FunctionCallCommand::Transfer { to, value } => {
+                FunctionCall::Transfer(Transfer { to, value })
+            }
+            FunctionCallCommand::RideRequest {
+                pickup_latitude,
+                pickup_longitude,
+                dropoff_latitude,
+                dropoff_longitude,
+                fare,
+            } => FunctionCall::RideRequest(RideRequest {
+                pickup_location: Coordinates {
+                    latitude: pickup_latitude,
+                    longitude: pickup_longitude,
+                },
+                dropoff_location: Coordinates {
+                    latitude: dropoff_latitude,
+                    longitude: dropoff_longitude,
+                },
+                fare,
+            }),
+            FunctionCallCommand::RideOffer {
+                ride_request_transaction_hash,
+                fare,
+            } => FunctionCall::RideOffer(RideOffer {
+                ride_request_transaction_hash,
+                fare,
+            }),
+            FunctionCallCommand::RideAcceptance {
+                ride_offer_transaction_hash,
+            } => FunctionCall::RideAcceptance(RideAcceptance {
+                ride_offer_transaction_hash,
+            }),
+            FunctionCallCommand::RidePay {
+                ride_acceptance_transaction_hash,
+                fare,
+            } => FunctionCall::RidePay(RidePay {
+                ride_acceptance_transaction_hash,
+                fare,
+            }),
+            FunctionCallCommand::RideCancel {
+                ride_acceptance_transaction_hash,
+            } => FunctionCall::RideCancel(RideCancel {
+                ride_acceptance_transaction_hash,
+            }),
+            FunctionCallCommand::ConfirmArrival {
+                ride_acceptance_transaction_hash,
+            } => FunctionCall::ConfirmArrival(ConfirmArrival {
+                ride_acceptance_transaction_hash,
+            }),
+            FunctionCallCommand::ComplainArrival {
+                ride_acceptance_transaction_hash,
+            } => FunctionCall::ComplainArrival(ComplainArrival {
+                ride_acceptance_transaction_hash,
+            }),
+            FunctionCallCommand::ProposeValidator { validator } => {
+                FunctionCall::ProposeValidator(ProposeValidator { validator })
+            }
+            FunctionCallCommand::VoteValidator {
+                proposal_transaction_hash,
+            } => FunctionCall::VoteValidator(VoteValidator {
+                proposal_transaction_hash,
+            }),
+            FunctionCallCommand::RemoveValidator { validator } => {
+                FunctionCall::RemoveValidator(RemoveValidator { validator })
+            }
+            FunctionCallCommand::ReportEquivocation { proof } => {
+                let content = fs::read_to_string(&proof)
+                    .map_err(|e| format!("Failed to read proof file '{}': {}", proof, e))?;
+                let proof = serde_json::from_str(&content)
+                    .map_err(|e| format!("Invalid equivocation proof JSON: {}", e))?;
+                FunctionCall::ReportEquivocation(ReportEquivocation { proof })
+            }
+            FunctionCallCommand::Json { function_call } => serde_json::from_str(&function_call)
+                .map_err(|e| format!("Invalid function call JSON: {}", e))?,
+        };
+        Ok(function_call)
+    }
+}
+
+fn parse_signature_version(name: &str) -> Result<SignatureVersion, String> {
+    match name {
+        "legacy" => Ok(SignatureVersion::Legacy),
+        "digest" => Ok(SignatureVersion::Digest),
+        "typed-data" => Ok(SignatureVersion::TypedData),
+        _ => Err(format!("Unknown signature version '{}'", name)),
+    }
+}
+
+fn encode_raw_transaction(transaction: &Transaction) -> Result<String, String> {
+    if transaction.signature_r.is_empty() || transaction.signature_s.is_empty() {
+        return Err("Transaction is not signed, run `tx sign` first".to_string());
+    }
+    Ok(hex::encode(encode(&transaction.to_raw_transaction())))
+}
+
+fn decode_raw_transaction(raw: &str, chain_id: u64) -> Result<Transaction, String> {
+    let bytes = hex::decode(raw.trim().trim_start_matches("0x"))
+        .map_err(|e| format!("Failed to decode hex: {}", e))?;
+    Transaction::from_raw_bytes(&bytes, chain_id)
+}
+
+fn print_transaction(transaction: &Transaction) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(transaction)
+        .map_err(|e| format!("Failed to serialize transaction: {}", e))?;
+    println!("{}", json);
+    Ok(())
+}
+
+async fn get_next_nonce(rpc_url: &str, address: &str) -> Result<u64, String> {
+    let result = rpc_call(
+        rpc_url,
+        "get_next_nonce",
+        serde_json::json!({ "address": address }),
+    )
+    .await?;
+    result
+        .get("nonce")
+        .and_then(|nonce| nonce.as_u64())
+        .ok_or_else(|| format!("Unexpected get_next_nonce result: {}", result))
+}
+
+async fn rpc_call(
+    rpc_url: &str,
+    method: &str,
+    params: serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    let (mut ws_stream, _) = connect_async(rpc_url)
+        .await
+        .map_err(|e| format!("Failed to connect to {}: {}", rpc_url, e))?;
+
+    let request = serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": method,
+        "params": params,
+        "id": 1
+    });
+    ws_stream
+        .send(Message::Text(request.to_string()))
+        .await
+        .map_err(|e| format!("Failed to send {} request: {}", method, e))?;
+
+    while let Some(message) = ws_stream.next().await {
+        let message = message.map_err(|e| format!("Failed to read {} response: {}", method, e))?;
+        if let Message::Text(text) = message {
+            let _ = ws_stream.close(None).await;
+            let response: serde_json::Value = serde_json::from_str(&text)
+                .map_err(|e| format!("Invalid {} response: {}", method, e))?;
+            if let Some(error) = response.get("error") {
+                return Err(format!("{} failed: {}", method, error));
+            }
+            return Ok(response.get("result").cloned().unwrap_or_default());
+        }
+    }
+
+    Err(format!("Connection closed before the {} response", method))
+}
@@ -0,0 +1,124 @@
+use std::time::Duration;
+
+use crate::node::consensus::{timed_after_parent, verify_timestamp_drift, Consensus};
+
+use super::{
+    blocks::block::Block,
+    time_utils::{get_current_duration, get_current_timespan},
+};
+
+// Clique-style proof of authority: the authorities take turns by height instead of by time.
+// The in-turn authority seals `period` seconds after the parent; any other authority may seal
+// out of turn one period later, so the chain keeps going while the in-turn one is offline.
+// An authority never seals two blocks in a row unless it is the only one.
+#[derive(Debug)]
+pub struct Clique {
+    pub period: u64, // Minimum seconds between blocks
+}
+
+impl Clique {
+    pub fn new(period: u64) -> Self {
+        Self { period }
+    }
+
+    fn in_turn_author<'a>(&self, block_index: usize, authorities: &'a [String]) -> Result<&'a String, String> {
+        if authorities.is_empty() {
+            return Err("There are no authorities".to_string());
+        }
+        Ok(&authorities[block_index % authorities.len()])
+    }
+
+    fn earliest_timestamp(&self, parent: &Block, in_turn: bool) -> u64 {
+        if !timed_after_parent(parent) {
+            return 0;
+        }
+        let delay = if in_turn { self.period } else { self.period * 2 };
+        parent.timestamp + delay
+    }
+
+    fn verify_author(&self, author: &str, parent: &Block, authorities: &[String]) -> Result<bool, String> {
+        let in_turn_author = self.in_turn_author(parent.index + 1, authorities)?;
+        if !authorities.iter().any(|authority| authority == author) {
+            return Err(format!("'{}' is not an authority", author));
+        }
+        if authorities.len() > 1 && parent.author == author {
+            return Err(format!("'{}' sealed the parent block {}", author, parent.index));
+        }
+        Ok(in_turn_author == author)
+    }
+}
+
+impl Consensus for Clique {
+    // Turns depend on the parent rather than the clock, so poll every second
+    fn duration_until_next_slot(&self) -> Duration {
+        Duration::from_secs(1) - Duration::from_nanos(get_current_duration().subsec_nanos() as u64)
+    }
+
+    fn current_author<'a>(&self, parent: &Block, authorities: &'a [String]) -> Result<&'a String, String> {
+        self.in_turn_author(parent.index + 1, authorities)
+    }
+
+    fn prepare_block(&self, author: &str, parent: &Block, authorities: &[String]) -> Result<(), String> {
+        let in_turn = self.verify_author(author, parent, authorities)?;
+        let earliest = self.earliest_timestamp(parent, in_turn);
+        if get_current_timespan() < earliest {
+            return Err(format!(
+                "Block {} can be sealed {} turn from {}",
+                parent.index + 1,
+                if in_turn { "in" } else { "out of" },
+                earliest
+            ));
+        }
+        Ok(())
+    }
+
+    fn verify_header(&self, block: &Block, parent: &Block, authorities: &[String]) -> Result<(), String> {
+        verify_timestamp_drift(block)?;
+
+        let in_turn = self.verify_author(&block.author, parent, authorities)?;
+        let earliest = self.earliest_timestamp(parent, in_turn);
+        if block.timestamp < earliest {
+            return Err(format!(
+                "Block {} sealed {} turn at {}, before {}",
+                block.index,
+                if in_turn { "in" } else { "out of" },
+                block.timestamp,
+                earliest
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_header_turns() {
+        let clique = Clique::new(5);
+        let authorities = vec!["node_1".to_string(), "node_2".to_string(), "node_3".to_string()];
+        let mut parent = Block::new_block(1, String::new(), vec![]);
+        parent.timestamp = 1000;
+        parent.author = "node_2".to_string();
+
+        // node_3 is in turn for block 2
+        let mut block = Block::new_block(2, String::new(), vec![]);
+        block.author = "node_3".to_string();
+        block.timestamp = 1005;
+        assert!(clique.verify_header(&block, &parent, &authorities).is_ok());
+        block.timestamp = 1004;
+        assert!(clique.verify_header(&block, &parent, &authorities).is_err());
+
+        // Out of turn waits another period
+        block.author = "node_1".to_string();
+        block.timestamp = 1005;
+        assert!(clique.verify_header(&block, &parent, &authorities).is_err());
+        block.timestamp = 1010;
+        assert!(clique.verify_header(&block, &parent, &authorities).is_ok());
+
+        // No two blocks in a row
+        block.author = "node_2".to_string();
+        assert!(clique.verify_header(&block, &parent, &authorities).is_err());
+    }
+}
@@ -1,20 +1,68 @@
 use config::{Config, ConfigError, Environment, File};
 use dotenv::dotenv;
 use serde::Deserialize;
+use std::path::Path;
 use tracing::info;
 
+use super::blocks::block::BlockLimits;
+use super::chain_spec::ChainSpec;
+use super::dev_chain::DevAccount;
+use super::keystore::{read_passphrase, Keystore};
+use super::p2p_server::discovery::DEFAULT_TARGET_PEERS;
+use super::transactions::transaction_pool::TransactionPoolConfig;
+
+// Fields added after the first release default to ChainSpec::default() and
+// TransactionPoolConfig::default(), so older config files still load
 #[derive(Debug, Deserialize, Clone)]
 pub struct AppConfig {
     pub log_level: String,
     pub libp2p_topic_name: String,
     pub blockchain_name: String,
+    #[serde(default = "default_chain_id")]
+    pub chain_id: u64,
     pub author_public_key: String,
     pub author_secret_key: String,
+    #[serde(default)]
+    pub author_keystore_path: String,
+    #[serde(default)]
+    pub author_keystore_passphrase_file: String,
     pub developer_mode: bool,
+    #[serde(default)]
+    pub keep_database: bool,
+    #[serde(default)]
+    pub dev_accounts: usize,
     pub websocket_addr: String,
+    #[serde(default = "default_consensus")]
+    pub consensus: String,
     pub authorities: Vec<String>,
+    #[serde(default)]
+    pub digest_signature_activation_height: usize,
+    #[serde(default)]
+    pub timestamp_signature_activation_height: usize,
+    #[serde(default = "default_tx_pool_max_transactions")]
+    pub tx_pool_max_transactions: usize,
+    #[serde(default = "default_tx_pool_max_transactions_per_account")]
+    pub tx_pool_max_transactions_per_account: usize,
+    #[serde(default = "default_block_max_transactions")]
+    pub block_max_transactions: usize,
+    #[serde(default = "default_block_max_bytes")]
+    pub block_max_bytes: usize,
+    #[serde(default = "default_block_time")]
+    pub block_time: u64,
+    #[serde(default = "default_epoch_length")]
+    pub epoch_length: u64,
+    #[serde(default)]
+    pub allow_empty_blocks: bool,
     pub listen_addrs: Vec<String>,
     pub bootstrap_nodes: Vec<String>,
+    #[serde(default)]
+    pub peer_store_path: String,
+    #[serde(default = "default_target_peers")]
+    pub target_peers: usize,
+    #[serde(default)]
+    pub node_key_path: String,
+    #[serde(default)]
+    pub bind_node_key_to_author: bool,
     pub block_authoring_enabled: bool,
     pub sync_enabled: bool,
     pub serve_metric_enabled: bool,
@@ -23,6 +71,42 @@ pub struct AppConfig {
     pub seq_api_key: String,
 }
 
+fn default_chain_id() -> u64 {
+    ChainSpec::default().chain_id
+}
+
+fn default_consensus() -> String {
+    "aura".to_string()
+}
+
+fn default_tx_pool_max_transactions() -> usize {
+    TransactionPoolConfig::default().max_transactions
+}
+
+fn default_tx_pool_max_transactions_per_account() -> usize {
+    TransactionPoolConfig::default().max_transactions_per_account
+}
+
+fn default_block_max_transactions() -> usize {
+    BlockLimits::default().max_transactions
+}
+
+fn default_block_max_bytes() -> usize {
+    BlockLimits::default().max_bytes
+}
+
+fn default_block_time() -> u64 {
+    ChainSpec::default().block_time
+}
+
+fn default_epoch_length() -> u64 {
+    ChainSpec::default().epoch_length
+}
+
+fn default_target_peers() -> usize {
+    DEFAULT_TARGET_PEERS
+}
+
 impl AppConfig {
     fn from_env(env: &str) -> Result<Self, ConfigError> {
         dotenv().ok();
@@ -39,4 +123,106 @@ impl AppConfig {
         info!("Loaded configuration from env {:?}: {:?}", env, config);
         Ok(config)
     }
+
+    // Uses the encrypted keystore when author_keystore_path is set, otherwise the plaintext key
+    pub fn load_author_secret_key(&self) -> Result<String, String> {
+        if self.author_keystore_path.is_empty() {
+            return Ok(self.author_secret_key.clone());
+        }
+
+        let keystore = Keystore::load(Path::new(&self.author_keystore_path))?;
+        if keystore.address() != self.author_public_key.to_lowercase() {
+            return Err(format!(
+                "Keystore address {} does not match author_public_key {}",
+                keystore.address(),
+                self.author_public_key
+            ));
+        }
+
+        let passphrase = read_passphrase(&self.author_keystore_passphrase_file)?;
+        let secret_key = keystore.decrypt(&passphrase)?;
+        info!("Loaded author key {} from keystore", keystore.address());
+        Ok(secret_key.to_string())
+    }
+
+    pub fn chain_spec(&self) -> Result<ChainSpec, String> {
+        let chain_spec = ChainSpec {
+            chain_id: self.chain_id,
+            consensus: self.consensus.parse()?,
+            authorities: self.authorities.clone(),
+            epoch_length: self.epoch_length,
+            block_time: self.block_time,
+            allow_empty_blocks: self.allow_empty_blocks,
+            digest_signature_activation_height: self.digest_signature_activation_height,
+            timestamp_signature_activation_height: self.timestamp_signature_activation_height,
+            block_limits: BlockLimits {
+                max_transactions: self.block_max_transactions,
+                max_bytes: self.block_max_bytes,
+            },
+            genesis_allocations: DevAccount::genesis_allocations(self.dev_accounts)?,
+        };
+        chain_spec.validate()?;
+        Ok(chain_spec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::FileFormat;
+
+    #[test]
+    fn test_config_without_chain_settings_uses_defaults() {
+        let toml = r#"
+            log_level = "info"
+            libp2p_topic_name = "test-net"
+            blockchain_name = "clutch-node-test"
+            author_public_key = "0x9b6e8afff8329743cac73dbef83ca3cbf9a74c20"
+            author_secret_key = "0883ddd3d07303b87c954b0c9383f7b78f45e002520fc03a8adc80595dbf6509"
+            developer_mode = true
+            websocket_addr = "0.0.0.0:8081"
+            authorities = ["0x9b6e8afff8329743cac73dbef83ca3cbf9a74c20"]
+            listen_addrs = ["/ip4/0.0.0.0/tcp/4001"]
+            bootstrap_nodes = []
+            block_authoring_enabled = true
+            sync_enabled = true
+            serve_metric_enabled = false
+            serve_metric_addr = "0.0.0.0:3001"
+            seq_url = ""
+            seq_api_key = ""
+        "#;
+        let config = Config::builder()
+            .add_source(File::from_str(toml, FileFormat::Toml))
+            .build()
+            .unwrap()
+            .try_deserialize::<AppConfig>()
+            .unwrap();
+
+        let chain_spec = config.chain_spec().unwrap();
+        let default_chain_spec = ChainSpec::default();
+        assert_eq!(chain_spec.chain_id, default_chain_spec.chain_id);
+        assert_eq!(chain_spec.consensus, default_chain_spec.consensus);
+        assert_eq!(chain_spec.block_time, default_chain_spec.block_time);
+        assert_eq!(chain_spec.epoch_length, default_chain_spec.epoch_length);
+        assert_eq!(
+            chain_spec.block_limits.max_transactions,
+            default_chain_spec.block_limits.max_transactions
+        );
+        assert_eq!(
+            chain_spec.block_limits.max_bytes,
+            default_chain_spec.block_limits.max_bytes
+        );
+        assert_eq!(
+            config.tx_pool_max_transactions,
+            TransactionPoolConfig::default().max_transactions
+        );
+        assert_eq!(
+            config.tx_pool_max_transactions_per_account,
+            TransactionPoolConfig::default().max_transactions_per_account
+        );
+        assert_eq!(config.target_peers, DEFAULT_TARGET_PEERS);
+        assert!(config.author_keystore_path.is_empty());
+        assert!(config.peer_store_path.is_empty());
+        assert!(config.node_key_path.is_empty());
+    }
 }
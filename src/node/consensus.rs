@@ -1,7 +1,124 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use super::aura::Aura;
 use super::blocks::block::Block;
+use super::blocks::block_headers::BlockHeader;
+use super::clique::Clique;
+use super::instant_seal::InstantSeal;
+use super::time_utils::get_current_timespan;
+
+// How far into the future a block timestamp may be, to tolerate clock differences
+pub const MAX_TIMESTAMP_DRIFT: u64 = 5;
+
+// Hooks a consensus engine provides to block authoring and import. The authorities are passed
+// in by the caller since the active set depends on the height of the block (see validator_set.rs).
+pub trait Consensus: Send + Sync {
+    // Time until the authoring job should next try to author a block
+    fn duration_until_next_slot(&self) -> Duration;
+    // Whether the authoring job should also try to author as soon as a transaction enters the pool
+    fn seals_on_transaction(&self) -> bool {
+        false
+    }
+    // Authority expected to author the block on top of `parent` right now
+    fn current_author<'a>(&self, parent: &Block, authorities: &'a [String]) -> Result<&'a String, String>;
+    // Checks that `author` may author a block on top of `parent` now
+    fn prepare_block(&self, author: &str, parent: &Block, authorities: &[String]) -> Result<(), String>;
+    fn seal_block(&self, block: &mut Block, author: &str, secret_key: &str) {
+        block.sign(author, secret_key);
+    }
+    // Engine rules for the author and timestamp of a block on top of `parent`
+    fn verify_header(&self, block: &Block, parent: &Block, authorities: &[String]) -> Result<(), String>;
+    // The part of verify_header that needs neither the body nor the parent block, checked on
+    // headers during sync before their bodies are downloaded
+    fn verify_header_author(&self, header: &BlockHeader, authorities: &[String]) -> Result<(), String> {
+        verify_header_authority(header, authorities)
+    }
+    // Whether a block may become the new best block. State changes cannot be reverted, so only
+    // children of the best block are candidates; engines may restrict them further.
+    fn fork_choice(&self, best: &Block, candidate: &Block) -> Result<(), String> {
+        if candidate.index != best.index + 1 {
+            return Err(format!(
+                "Invalid block: The block index should be {}, but it was {}.",
+                best.index + 1,
+                candidate.index
+            ));
+        }
+        if candidate.previous_hash != best.hash {
+            return Err(format!(
+                "Invalid block: The previous hash should be {}, but it was {}.",
+                best.hash, candidate.previous_hash
+            ));
+        }
+        Ok(())
+    }
+}
+
+pub fn verify_header_authority(header: &BlockHeader, authorities: &[String]) -> Result<(), String> {
+    if !authorities.contains(&header.author) {
+        return Err(format!(
+            "Header {} author '{}' is not an authority",
+            header.index, header.author
+        ));
+    }
+    Ok(())
+}
+
+pub fn verify_timestamp_drift(block: &Block) -> Result<(), String> {
+    let max_timestamp = get_current_timespan() + MAX_TIMESTAMP_DRIFT;
+    if block.timestamp > max_timestamp {
+        return Err(format!(
+            "Block timestamp {} is too far in the future, the latest accepted is {}",
+            block.timestamp, max_timestamp
+        ));
+    }
+    Ok(())
+}
+
+// Whether a block is timed against its parent. Blocks on top of genesis are exempt, since the
+// genesis timestamp is just the time the database was created.
+pub fn timed_after_parent(parent: &Block) -> bool {
+    parent.index > 0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConsensusEngine {
+    Aura,
+    Clique,
+    InstantSeal,
+}
+
+impl ConsensusEngine {
+    pub fn build(&self, block_time: u64) -> Box<dyn Consensus> {
+        match self {
+            ConsensusEngine::Aura => Box::new(Aura::new(block_time)),
+            ConsensusEngine::Clique => Box::new(Clique::new(block_time)),
+            ConsensusEngine::InstantSeal => Box::new(InstantSeal),
+        }
+    }
+
+    // Length of the time slots an authority signs at most one block in, zero when the
+    // authorities take turns by height
+    pub fn slot_duration(&self, block_time: u64) -> u64 {
+        match self {
+            ConsensusEngine::Aura => block_time,
+            ConsensusEngine::Clique | ConsensusEngine::InstantSeal => 0,
+        }
+    }
+}
 
+impl FromStr for ConsensusEngine {
+    type Err = String;
 
-pub trait Consensus {
-    fn current_author(&self) -> &String;
-    fn verify_block_author(&self, block: &Block) -> Result<(), String>;
-}
\ No newline at end of file
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "aura" => Ok(ConsensusEngine::Aura),
+            "clique" => Ok(ConsensusEngine::Clique),
+            "instant_seal" => Ok(ConsensusEngine::InstantSeal),
+            _ => Err(format!(
+                "Unknown consensus engine '{}', expected aura, clique or instant_seal",
+                name
+            )),
+        }
+    }
+}
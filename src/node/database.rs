@@ -1,9 +1,22 @@
 use rocksdb::{ColumnFamilyDescriptor, DBWithThreadMode, Options, SingleThreaded, WriteBatch, DB};
+use std::collections::HashMap;
 use std::env;
+use std::sync::Mutex;
+
+// Key and value written to the state column family
+pub type StateUpdate = (Vec<u8>, Vec<u8>);
+// Key and value read from a column family
+pub type KeyValue = (Vec<u8>, Vec<u8>);
+
+// Staged values by column family and key
+type StagedWrites = HashMap<(String, Vec<u8>), Vec<u8>>;
 
 #[derive(Debug)]
 pub struct Database {
     db: Option<DBWithThreadMode<SingleThreaded>>,
+    // Writes of the transactions executed so far in a block, read ahead of the stored values
+    // so each transaction sees the state left by the ones before it
+    staged: Mutex<Option<StagedWrites>>,
 }
 
 impl Database {
@@ -25,7 +38,8 @@ impl Database {
             ColumnFamilyDescriptor::new("block", Options::default()),
             ColumnFamilyDescriptor::new("state", Options::default()),
             ColumnFamilyDescriptor::new("blockchain", Options::default()),
-            ColumnFamilyDescriptor::new("tx_pool", Options::default()),            
+            // No longer written since the transaction pool moved to memory, kept so existing databases open
+            ColumnFamilyDescriptor::new("tx_pool", Options::default()),
         ];
 
         let db = DBWithThreadMode::<SingleThreaded>::open_cf_descriptors(
@@ -35,10 +49,19 @@ impl Database {
         )
         .expect("Failed to open database with specified column families");
 
-        Database { db: Some(db) }
+        Database {
+            db: Some(db),
+            staged: Mutex::new(None),
+        }
     }
 
     pub fn get(&self, cf_name: &str, key: &[u8]) -> Result<Option<Vec<u8>>, String> {
+        if let Some(staged) = self.staged.lock().unwrap().as_ref() {
+            if let Some(value) = staged.get(&(cf_name.to_string(), key.to_vec())) {
+                return Ok(Some(value.clone()));
+            }
+        }
+
         match &self.db {
             Some(db) => {
                 let cf_handle = db.cf_handle(cf_name).ok_or("Column family not found")?;
@@ -48,6 +71,7 @@ impl Database {
         }
     }
 
+    #[allow(dead_code)]
     pub fn put(&self, cf_name: &str, key: &[u8], value: &[u8]) -> Result<(), String> {
         match &self.db {
             Some(db) => {
@@ -58,6 +82,7 @@ impl Database {
         }
     }
 
+    #[allow(dead_code)]
     pub fn delete(&self, cf_name: &str, key: &[u8]) -> Result<(), String> {
         match &self.db {
             Some(db) => {
@@ -90,6 +115,21 @@ impl Database {
     }
     
 
+    pub fn begin_staging(&self) {
+        *self.staged.lock().unwrap() = Some(HashMap::new());
+    }
+
+    // Only visible to reads until staging ends, nothing is written to the database
+    pub fn stage(&self, cf_name: &str, key: &[u8], value: &[u8]) {
+        if let Some(staged) = self.staged.lock().unwrap().as_mut() {
+            staged.insert((cf_name.to_string(), key.to_vec()), value.to_vec());
+        }
+    }
+
+    pub fn end_staging(&self) {
+        *self.staged.lock().unwrap() = None;
+    }
+
     pub fn close(&mut self) {
         let _ = self.db.take(); // Properly drops the database object, closing the connection
     }
@@ -123,6 +163,31 @@ impl Database {
         }
     }
 
+    pub fn get_keys_values_by_prefix(
+        &self,
+        cf_name: &str,
+        prefix: &[u8],
+    ) -> Result<Vec<KeyValue>, String> {
+        match &self.db {
+            Some(db) => {
+                let cf_handle = db
+                    .cf_handle(cf_name)
+                    .ok_or(format!("Column family '{}' not found", cf_name))?;
+                let mut entries = Vec::new();
+                // Without a prefix extractor the iterator runs past the prefix, so stop at the first other key
+                for item in db.prefix_iterator_cf(cf_handle, prefix) {
+                    let (key, value) = item.map_err(|e| e.to_string())?;
+                    if !key.starts_with(prefix) {
+                        break;
+                    }
+                    entries.push((key.to_vec(), value.to_vec()));
+                }
+                Ok(entries)
+            }
+            None => Err("Database connection is closed".to_string()),
+        }
+    }
+
     pub fn get_keys_values_by_cf_name(
         &self,
         cf_name: &str,
@@ -0,0 +1,44 @@
+use crate::node::signature_keys::SignatureKeys;
+
+// Balance every dev account is funded with at genesis
+pub const DEV_ACCOUNT_BALANCE: u64 = 1_000_000;
+
+// A pre-funded account of a local development chain. The keys are derived from a public
+// seed, so they are the same on every machine and must never hold real value.
+#[derive(Debug, Clone)]
+pub struct DevAccount {
+    pub address: String,
+    pub secret_key: String,
+}
+
+impl DevAccount {
+    // Secret key i is keccak256("clutch-dev-account-{i}")
+    pub fn derive(index: usize) -> Result<DevAccount, String> {
+        let seed = format!("clutch-dev-account-{}", index);
+        let secret_key = hex::encode(SignatureKeys::keccak256(seed.as_bytes()));
+        Ok(DevAccount {
+            address: SignatureKeys::address_from_secret_key(&secret_key)?,
+            secret_key,
+        })
+    }
+
+    pub fn derive_all(count: usize) -> Result<Vec<DevAccount>, String> {
+        (0..count).map(Self::derive).collect()
+    }
+
+    // Genesis balances for the chain spec
+    pub fn genesis_allocations(count: usize) -> Result<Vec<(String, u64)>, String> {
+        Ok(Self::derive_all(count)?
+            .into_iter()
+            .map(|account| (account.address, DEV_ACCOUNT_BALANCE))
+            .collect())
+    }
+
+    pub fn print_all(count: usize) -> Result<(), String> {
+        println!("Dev accounts, funded with {} each:", DEV_ACCOUNT_BALANCE);
+        for (index, account) in Self::derive_all(count)?.iter().enumerate() {
+            println!("({}) {} secret key: {}", index, account.address, account.secret_key);
+        }
+        Ok(())
+    }
+}
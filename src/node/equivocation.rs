@@ -0,0 +1,182 @@
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::node::blocks::block::Block;
+use crate::node::blocks::block_headers::BlockHeader;
+use crate::node::consensus::Consensus;
+use crate::node::database::Database;
+use crate::node::finality::Finality;
+use crate::node::validator_set::ValidatorSets;
+
+const SEEN_HEADER_PREFIX: &str = "seen_header_";
+
+// A block header with the transaction hashes its block hash commits to, which is enough
+// to check that the author signed a block at that index without the whole block
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SignedHeader {
+    pub header: BlockHeader,
+    pub transaction_hashes: Vec<String>,
+}
+
+// Two different blocks signed by the same authority for the same slot, which an honest authority
+// never does. Aura slots come from the timestamp, so both headers must have it signed.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EquivocationProof {
+    pub first: SignedHeader,
+    pub second: SignedHeader,
+}
+
+impl SignedHeader {
+    pub fn from_block(block: &Block) -> SignedHeader {
+        SignedHeader {
+            header: block.to_block_header(),
+            transaction_hashes: block.transactions.iter().map(|tx| tx.hash.clone()).collect(),
+        }
+    }
+
+    pub fn verify(&self) -> Result<(), String> {
+        let header = &self.header;
+        let hash = Block::hash_of(
+            header.index,
+            &header.previous_hash,
+            &self.transaction_hashes,
+            header.timestamp,
+            header.signature_version,
+        );
+        if hash != header.hash {
+            return Err(format!(
+                "Header hash {} does not match its contents, expected {}",
+                header.hash, hash
+            ));
+        }
+        header.verify_signature()
+    }
+
+    fn construct_seen_key(slot: u64, author: &str) -> Vec<u8> {
+        format!("{}{}_{}", SEEN_HEADER_PREFIX, slot, author).into_bytes()
+    }
+}
+
+impl EquivocationProof {
+    pub fn offender(&self) -> &String {
+        &self.first.header.author
+    }
+
+    // Headers of one slot may be at different heights, the lower one identifies the report
+    pub fn block_index(&self) -> usize {
+        self.first.header.index.min(self.second.header.index)
+    }
+
+    pub fn verify(&self, validator_sets: &ValidatorSets) -> Result<(), String> {
+        let (first, second) = (&self.first.header, &self.second.header);
+        if first.author != second.author {
+            return Err("Equivocation headers are signed by different authors".to_string());
+        }
+        let first_slot = validator_sets.signed_slot_of(first);
+        let second_slot = validator_sets.signed_slot_of(second);
+        if first_slot.is_none() || first_slot != second_slot {
+            return Err(format!(
+                "Equivocation headers {} and {} are not signed for the same slot",
+                first.hash, second.hash
+            ));
+        }
+        if first.hash == second.hash {
+            return Err("Equivocation headers are the same block".to_string());
+        }
+        self.first.verify()?;
+        self.second.verify()
+    }
+
+    pub fn construct_report_key(offender: &str, block_index: usize) -> Vec<u8> {
+        format!("equivocation_{}_{}", offender, block_index).into_bytes()
+    }
+
+    pub fn is_reported(&self, db: &Database) -> bool {
+        matches!(
+            db.get(
+                "state",
+                &Self::construct_report_key(self.offender(), self.block_index())
+            ),
+            Ok(Some(_))
+        )
+    }
+}
+
+pub struct Equivocation;
+
+impl Equivocation {
+    // Remembers the first header each authority signed per slot, in the blockchain CF, and
+    // returns a proof when a different block signed by the same authority shows up. Blocks
+    // are observed before import since the conflicting one never makes it into the chain.
+    // Only headers above the finalized height from the authority of their slot are recorded.
+    pub fn observe_block(
+        db: &Database,
+        consensus: &dyn Consensus,
+        block: &Block,
+    ) -> Result<Option<EquivocationProof>, String> {
+        let signed_header = SignedHeader::from_block(block);
+        signed_header.verify()?;
+
+        let finalized_index = Finality::get_finalized_block(db)
+            .map(|block| block.index)
+            .unwrap_or(0);
+        if block.index <= finalized_index {
+            return Ok(None);
+        }
+
+        let validator_sets = ValidatorSets::get(db)?;
+        let authorities = validator_sets.authorities_at_epoch(validator_sets.epoch_of(block.index));
+        consensus.verify_header_author(&signed_header.header, authorities)?;
+        let Some(slot) = validator_sets.signed_slot_of(&signed_header.header) else {
+            return Ok(None);
+        };
+
+        let key = SignedHeader::construct_seen_key(slot, &block.author);
+        let seen: SignedHeader = match db.get("blockchain", &key)? {
+            Some(value) => serde_json::from_slice(&value)
+                .map_err(|e| format!("Failed to deserialize seen header: {}", e))?,
+            None => {
+                db.write(vec![(
+                    "blockchain",
+                    key.as_slice(),
+                    Some(serde_json::to_vec(&signed_header).unwrap().as_slice()),
+                )])?;
+                return Ok(None);
+            }
+        };
+        if seen.header.hash == block.hash {
+            return Ok(None);
+        }
+
+        warn!(
+            "Authority {} signed two blocks in slot {}: {} and {}",
+            block.author, slot, seen.header.hash, block.hash
+        );
+        Ok(Some(EquivocationProof {
+            first: seen,
+            second: signed_header,
+        }))
+    }
+
+    // Headers at or below the finalized height can no longer conflict with the chain
+    pub fn prune(db: &Database, finalized_index: usize) -> Result<(), String> {
+        let stale_keys: Vec<Vec<u8>> = db
+            .get_keys_values_by_prefix("blockchain", SEEN_HEADER_PREFIX.as_bytes())?
+            .into_iter()
+            .filter_map(|(key, value)| {
+                let seen: SignedHeader = serde_json::from_slice(&value).ok()?;
+                (seen.header.index <= finalized_index).then_some(key)
+            })
+            .collect();
+        if stale_keys.is_empty() {
+            return Ok(());
+        }
+
+        db.write(
+            stale_keys
+                .iter()
+                .map(|key| ("blockchain", key.as_slice(), None))
+                .collect(),
+        )
+    }
+}
@@ -0,0 +1,188 @@
+use rlp::RlpStream;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::node::blocks::block::Block;
+use crate::node::database::Database;
+use crate::node::metric;
+use crate::node::signature_keys::SignatureKeys;
+use crate::node::validator_set::ValidatorSets;
+
+const FINALIZED_BLOCK_KEY: &[u8] = b"blockchain_finalized_block";
+const PRECOMMIT_DOMAIN: &[u8] = b"clutch-precommit";
+
+// An authority's vote that a block is part of its chain. Once more than two thirds of the
+// authorities of a height precommit to the same block it is final, and so are its ancestors.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Precommit {
+    pub block_index: usize,
+    pub block_hash: String,
+    pub voter: String,
+    pub signature_r: String,
+    pub signature_s: String,
+    pub signature_v: i32,
+}
+
+impl Precommit {
+    pub fn new(block: &Block, voter: &str, secret_key: &str, chain_id: u64) -> Precommit {
+        let mut precommit = Precommit {
+            block_index: block.index,
+            block_hash: block.hash.clone(),
+            voter: voter.to_string(),
+            signature_r: String::new(),
+            signature_s: String::new(),
+            signature_v: 0,
+        };
+        let (r, s, v) = SignatureKeys::sign_digest(secret_key, &precommit.signing_digest(chain_id));
+        precommit.signature_r = r;
+        precommit.signature_s = s;
+        precommit.signature_v = v;
+        precommit
+    }
+
+    // keccak256 of the domain tag, the big-endian chain id and the RLP list
+    // [block_index, block_hash], so a precommit signature is not valid as any other message
+    // or on another chain
+    fn signing_digest(&self, chain_id: u64) -> [u8; 32] {
+        let mut stream = RlpStream::new_list(2);
+        stream.append(&self.block_index);
+        stream.append(&self.block_hash);
+
+        let mut message = PRECOMMIT_DOMAIN.to_vec();
+        message.extend_from_slice(&chain_id.to_be_bytes());
+        message.extend_from_slice(&stream.out());
+        SignatureKeys::keccak256(&message)
+    }
+
+    pub fn verify_signature(&self, chain_id: u64) -> Result<(), String> {
+        let recovered_voter = SignatureKeys::recover_address_from_digest(
+            &self.signing_digest(chain_id),
+            &self.signature_r,
+            &self.signature_s,
+            self.signature_v,
+        )?;
+        if recovered_voter != self.voter {
+            return Err(format!(
+                "Precommit signature does not match voter {}",
+                self.voter
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn construct_precommit_key(block_index: usize, voter: &str) -> Vec<u8> {
+        format!("precommit_{}_{}", block_index, voter).into_bytes()
+    }
+}
+
+pub struct Finality;
+
+impl Finality {
+    // The genesis block is final until a later block is finalized
+    pub fn get_finalized_block(db: &Database) -> Option<Block> {
+        match db.get("blockchain", FINALIZED_BLOCK_KEY) {
+            Ok(Some(value)) => serde_json::from_slice(&value).ok(),
+            _ => Block::get_genesis_block(db),
+        }
+    }
+
+    // Stores a precommit from an authority of its height and returns the block it finalized,
+    // if any. Precommits may arrive before their block, so one height ahead of the latest
+    // block is accepted; they are counted again once the block is imported.
+    pub fn add_precommit(
+        db: &Database,
+        precommit: &Precommit,
+        chain_id: u64,
+    ) -> Result<Option<Block>, String> {
+        precommit.verify_signature(chain_id)?;
+
+        let finalized_index = Self::get_finalized_block(db)
+            .map(|block| block.index)
+            .unwrap_or(0);
+        if precommit.block_index <= finalized_index {
+            return Ok(None);
+        }
+
+        let latest_index = Block::get_latest_block(db)
+            .map(|block| block.index)
+            .unwrap_or(0);
+        if precommit.block_index > latest_index + 1 {
+            return Err(format!(
+                "Precommit for block {} is too far ahead of the latest block {}",
+                precommit.block_index, latest_index
+            ));
+        }
+
+        let authorities = ValidatorSets::authorities_at(db, precommit.block_index)?;
+        if !authorities.contains(&precommit.voter) {
+            return Err(format!(
+                "'{}' is not an authority at block {}",
+                precommit.voter, precommit.block_index
+            ));
+        }
+
+        let key = Precommit::construct_precommit_key(precommit.block_index, &precommit.voter);
+        if let Some(value) = db.get("blockchain", &key)? {
+            let existing: Precommit = serde_json::from_slice(&value)
+                .map_err(|e| format!("Failed to deserialize precommit: {}", e))?;
+            if existing.block_hash == precommit.block_hash {
+                return Ok(None);
+            }
+            return Err(format!(
+                "'{}' already precommitted to block {} with hash {}",
+                precommit.voter, precommit.block_index, existing.block_hash
+            ));
+        }
+        let serialized = serde_json::to_vec(precommit)
+            .map_err(|e| format!("Failed to serialize precommit: {}", e))?;
+        db.write(vec![("blockchain", key.as_slice(), Some(serialized.as_slice()))])?;
+
+        Self::try_finalize(db, precommit.block_index)
+    }
+
+    // Finalizes the local block at the index once a supermajority of its authorities
+    // precommitted to its hash
+    pub fn try_finalize(db: &Database, block_index: usize) -> Result<Option<Block>, String> {
+        // Precommits may arrive before their block
+        let block: Block = match db.get("block", format!("block_{}", block_index).as_bytes())? {
+            Some(value) => serde_json::from_slice(&value)
+                .map_err(|e| format!("Failed to deserialize block {}: {}", block_index, e))?,
+            None => return Ok(None),
+        };
+        let finalized_index = Self::get_finalized_block(db)
+            .map(|block| block.index)
+            .unwrap_or(0);
+        if block.index <= finalized_index {
+            return Ok(None);
+        }
+
+        let authorities = ValidatorSets::authorities_at(db, block.index)?;
+        let mut votes = 0;
+        for authority in authorities.iter() {
+            let key = Precommit::construct_precommit_key(block.index, authority);
+            if let Some(value) = db.get("blockchain", &key)? {
+                let precommit: Precommit = serde_json::from_slice(&value)
+                    .map_err(|e| format!("Failed to deserialize precommit: {}", e))?;
+                if precommit.block_hash == block.hash {
+                    votes += 1;
+                }
+            }
+        }
+        if !ValidatorSets::is_supermajority(votes, authorities.len()) {
+            return Ok(None);
+        }
+
+        let serialized = serde_json::to_vec(&block)
+            .map_err(|e| format!("Failed to serialize finalized block: {}", e))?;
+        db.write(vec![("blockchain", FINALIZED_BLOCK_KEY, Some(serialized.as_slice()))])?;
+        metric::FINALIZED_BLOCK_INDEX.set(block.index as i64);
+        info!(
+            "Finalized block {} with hash {} by {} of {} precommits.",
+            block.index,
+            block.hash,
+            votes,
+            authorities.len()
+        );
+        Ok(Some(block))
+    }
+}
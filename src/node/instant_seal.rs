@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use crate::node::consensus::{timed_after_parent, verify_timestamp_drift, Consensus};
+
+use super::blocks::block::Block;
+
+// Blocks are sealed when a transaction enters the pool; the poll only picks up transactions
+// that became executable without entering it, such as queued nonces after a block
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// Development engine for a single authority: a block is sealed as soon as there is
+// something to put in it, without slots or turns
+#[derive(Debug)]
+pub struct InstantSeal;
+
+impl InstantSeal {
+    fn verify_author(author: &str, authorities: &[String]) -> Result<(), String> {
+        if !authorities.iter().any(|authority| authority == author) {
+            return Err(format!("'{}' is not an authority", author));
+        }
+        Ok(())
+    }
+}
+
+impl Consensus for InstantSeal {
+    fn duration_until_next_slot(&self) -> Duration {
+        POLL_INTERVAL
+    }
+
+    fn seals_on_transaction(&self) -> bool {
+        true
+    }
+
+    fn current_author<'a>(&self, _parent: &Block, authorities: &'a [String]) -> Result<&'a String, String> {
+        authorities
+            .first()
+            .ok_or_else(|| "There are no authorities".to_string())
+    }
+
+    fn prepare_block(&self, author: &str, _parent: &Block, authorities: &[String]) -> Result<(), String> {
+        Self::verify_author(author, authorities)
+    }
+
+    fn verify_header(&self, block: &Block, parent: &Block, authorities: &[String]) -> Result<(), String> {
+        Self::verify_author(&block.author, authorities)?;
+
+        verify_timestamp_drift(block)?;
+        if timed_after_parent(parent) && block.timestamp < parent.timestamp {
+            return Err(format!(
+                "Block timestamp {} is before its parent's {}",
+                block.timestamp, parent.timestamp
+            ));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,484 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use aes::Aes128;
+use aes_gcm::aead::Aead;
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use argon2::{Algorithm, Argon2, Params as Argon2Params, Version};
+use ctr::cipher::{KeyIvInit, StreamCipher};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use scrypt::Params as ScryptParams;
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+use super::signature_keys::SignatureKeys;
+
+pub const KEYSTORE_PASSPHRASE_ENV: &str = "CLUTCH_KEYSTORE_PASSPHRASE";
+
+const KEYSTORE_VERSION: u32 = 3;
+const DERIVED_KEY_LENGTH: usize = 32;
+const SALT_LENGTH: usize = 32;
+const AES_CTR_IV_LENGTH: usize = 16;
+const AES_GCM_NONCE_LENGTH: usize = 12;
+
+type Aes128Ctr = ctr::Ctr128BE<Aes128>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeystoreKdf {
+    Scrypt { log_n: u8, r: u32, p: u32 },
+    Argon2id { m_cost: u32, t_cost: u32, p_cost: u32 },
+}
+
+impl KeystoreKdf {
+    // Same cost as the "standard" scrypt parameters used by geth
+    pub fn scrypt() -> Self {
+        KeystoreKdf::Scrypt { log_n: 18, r: 8, p: 1 }
+    }
+
+    pub fn argon2id() -> Self {
+        KeystoreKdf::Argon2id {
+            m_cost: 65536,
+            t_cost: 3,
+            p_cost: 1,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "scrypt" => Ok(Self::scrypt()),
+            "argon2id" => Ok(Self::argon2id()),
+            _ => Err(format!("Unsupported keystore kdf '{}'", name)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeystoreKdf::Scrypt { .. } => "scrypt",
+            KeystoreKdf::Argon2id { .. } => "argon2id",
+        }
+    }
+
+    fn derive_key(
+        &self,
+        passphrase: &str,
+        salt: &[u8],
+    ) -> Result<Zeroizing<[u8; DERIVED_KEY_LENGTH]>, String> {
+        let mut derived_key = Zeroizing::new([0u8; DERIVED_KEY_LENGTH]);
+        match *self {
+            KeystoreKdf::Scrypt { log_n, r, p } => {
+                let params = ScryptParams::new(log_n, r, p, DERIVED_KEY_LENGTH)
+                    .map_err(|e| format!("Invalid scrypt parameters: {}", e))?;
+                scrypt::scrypt(passphrase.as_bytes(), salt, &params, derived_key.as_mut())
+                    .map_err(|e| format!("Failed to derive key with scrypt: {}", e))?;
+            }
+            KeystoreKdf::Argon2id {
+                m_cost,
+                t_cost,
+                p_cost,
+            } => {
+                let params = Argon2Params::new(m_cost, t_cost, p_cost, Some(DERIVED_KEY_LENGTH))
+                    .map_err(|e| format!("Invalid argon2id parameters: {}", e))?;
+                Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                    .hash_password_into(passphrase.as_bytes(), salt, derived_key.as_mut())
+                    .map_err(|e| format!("Failed to derive key with argon2id: {}", e))?;
+            }
+        }
+        Ok(derived_key)
+    }
+
+    fn to_params(self, salt: &[u8]) -> KdfParams {
+        let mut params = KdfParams {
+            dklen: DERIVED_KEY_LENGTH as u32,
+            salt: hex::encode(salt),
+            n: None,
+            r: None,
+            p: None,
+            m: None,
+            t: None,
+        };
+        match self {
+            KeystoreKdf::Scrypt { log_n, r, p } => {
+                params.n = Some(1u64 << log_n);
+                params.r = Some(r);
+                params.p = Some(p);
+            }
+            KeystoreKdf::Argon2id {
+                m_cost,
+                t_cost,
+                p_cost,
+            } => {
+                params.m = Some(m_cost);
+                params.t = Some(t_cost);
+                params.p = Some(p_cost);
+            }
+        }
+        params
+    }
+
+    fn from_params(kdf: &str, params: &KdfParams) -> Result<Self, String> {
+        if params.dklen as usize != DERIVED_KEY_LENGTH {
+            return Err(format!("Unsupported kdf dklen {}", params.dklen));
+        }
+        let missing = |name: &str| format!("Missing kdf parameter '{}'", name);
+        match kdf {
+            "scrypt" => {
+                let n = params.n.ok_or_else(|| missing("n"))?;
+                if n < 2 || !n.is_power_of_two() {
+                    return Err(format!("Invalid scrypt parameter n={}", n));
+                }
+                Ok(KeystoreKdf::Scrypt {
+                    log_n: n.trailing_zeros() as u8,
+                    r: params.r.ok_or_else(|| missing("r"))?,
+                    p: params.p.ok_or_else(|| missing("p"))?,
+                })
+            }
+            "argon2id" => Ok(KeystoreKdf::Argon2id {
+                m_cost: params.m.ok_or_else(|| missing("m"))?,
+                t_cost: params.t.ok_or_else(|| missing("t"))?,
+                p_cost: params.p.ok_or_else(|| missing("p"))?,
+            }),
+            _ => Err(format!("Unsupported keystore kdf '{}'", kdf)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeystoreCipher {
+    // The cipher defined by Web3 Secret Storage v3
+    Aes128Ctr,
+    Aes256Gcm,
+}
+
+impl KeystoreCipher {
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "aes-128-ctr" => Ok(KeystoreCipher::Aes128Ctr),
+            "aes-256-gcm" => Ok(KeystoreCipher::Aes256Gcm),
+            _ => Err(format!("Unsupported keystore cipher '{}'", name)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeystoreCipher::Aes128Ctr => "aes-128-ctr",
+            KeystoreCipher::Aes256Gcm => "aes-256-gcm",
+        }
+    }
+
+    fn iv_length(&self) -> usize {
+        match self {
+            KeystoreCipher::Aes128Ctr => AES_CTR_IV_LENGTH,
+            KeystoreCipher::Aes256Gcm => AES_GCM_NONCE_LENGTH,
+        }
+    }
+
+    fn encrypt(&self, derived_key: &[u8], iv: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        match self {
+            KeystoreCipher::Aes128Ctr => {
+                let mut ciphertext = plaintext.to_vec();
+                Aes128Ctr::new_from_slices(&derived_key[..16], iv)
+                    .map_err(|_| "Invalid aes-128-ctr key or iv".to_string())?
+                    .apply_keystream(&mut ciphertext);
+                Ok(ciphertext)
+            }
+            KeystoreCipher::Aes256Gcm => Aes256Gcm::new_from_slice(derived_key)
+                .map_err(|_| "Invalid aes-256-gcm key".to_string())?
+                .encrypt(Nonce::from_slice(iv), plaintext)
+                .map_err(|_| "Failed to encrypt secret key".to_string()),
+        }
+    }
+
+    fn decrypt(
+        &self,
+        derived_key: &[u8],
+        iv: &[u8],
+        ciphertext: &[u8],
+    ) -> Result<Zeroizing<Vec<u8>>, String> {
+        if iv.len() != self.iv_length() {
+            return Err(format!("Invalid {} iv length {}", self.as_str(), iv.len()));
+        }
+        match self {
+            KeystoreCipher::Aes128Ctr => {
+                let mut plaintext = Zeroizing::new(ciphertext.to_vec());
+                Aes128Ctr::new_from_slices(&derived_key[..16], iv)
+                    .map_err(|_| "Invalid aes-128-ctr key or iv".to_string())?
+                    .apply_keystream(&mut plaintext);
+                Ok(plaintext)
+            }
+            KeystoreCipher::Aes256Gcm => Aes256Gcm::new_from_slice(derived_key)
+                .map_err(|_| "Invalid aes-256-gcm key".to_string())?
+                .decrypt(Nonce::from_slice(iv), ciphertext)
+                .map(Zeroizing::new)
+                .map_err(|_| "Failed to decrypt secret key".to_string()),
+        }
+    }
+}
+
+// Web3 Secret Storage (version 3) JSON layout
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Keystore {
+    pub address: String,
+    pub crypto: KeystoreCrypto,
+    pub id: String,
+    pub version: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeystoreCrypto {
+    pub cipher: String,
+    pub cipherparams: CipherParams,
+    pub ciphertext: String,
+    pub kdf: String,
+    pub kdfparams: KdfParams,
+    pub mac: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CipherParams {
+    pub iv: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub dklen: u32,
+    pub salt: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub n: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub r: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub p: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub m: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub t: Option<u32>,
+}
+
+impl Keystore {
+    pub fn encrypt(
+        secret_key: &str,
+        passphrase: &str,
+        kdf: KeystoreKdf,
+        cipher: KeystoreCipher,
+    ) -> Result<Keystore, String> {
+        let address = SignatureKeys::address_from_secret_key(secret_key)?;
+        let secret_key_bytes = Zeroizing::new(
+            hex::decode(secret_key.trim_start_matches("0x"))
+                .map_err(|_| "Invalid hex in secret key".to_string())?,
+        );
+
+        let mut salt = [0u8; SALT_LENGTH];
+        OsRng.fill_bytes(&mut salt);
+        let mut iv = vec![0u8; cipher.iv_length()];
+        OsRng.fill_bytes(&mut iv);
+
+        let derived_key = kdf.derive_key(passphrase, &salt)?;
+        let ciphertext = cipher.encrypt(derived_key.as_ref(), &iv, &secret_key_bytes)?;
+        let mac = Self::mac(derived_key.as_ref(), &ciphertext);
+
+        Ok(Keystore {
+            address: address.trim_start_matches("0x").to_string(),
+            crypto: KeystoreCrypto {
+                cipher: cipher.as_str().to_string(),
+                cipherparams: CipherParams {
+                    iv: hex::encode(iv),
+                },
+                ciphertext: hex::encode(ciphertext),
+                kdf: kdf.as_str().to_string(),
+                kdfparams: kdf.to_params(&salt),
+                mac: hex::encode(mac),
+            },
+            id: uuid::Uuid::new_v4().to_string(),
+            version: KEYSTORE_VERSION,
+        })
+    }
+
+    pub fn decrypt(&self, passphrase: &str) -> Result<Zeroizing<String>, String> {
+        if self.version != KEYSTORE_VERSION {
+            return Err(format!("Unsupported keystore version {}", self.version));
+        }
+
+        let crypto = &self.crypto;
+        let cipher = KeystoreCipher::from_name(&crypto.cipher)?;
+        let kdf = KeystoreKdf::from_params(&crypto.kdf, &crypto.kdfparams)?;
+        let salt = decode_hex_field("salt", &crypto.kdfparams.salt)?;
+        let iv = decode_hex_field("iv", &crypto.cipherparams.iv)?;
+        let ciphertext = decode_hex_field("ciphertext", &crypto.ciphertext)?;
+        let mac = decode_hex_field("mac", &crypto.mac)?;
+
+        let derived_key = kdf.derive_key(passphrase, &salt)?;
+        // Compared in constant time so the check leaks nothing about the expected MAC
+        let mac_matches = Self::mac(derived_key.as_ref(), &ciphertext).ct_eq(mac.as_slice());
+        if !bool::from(mac_matches) {
+            return Err("Keystore MAC mismatch, wrong passphrase?".to_string());
+        }
+
+        let secret_key_bytes = cipher.decrypt(derived_key.as_ref(), &iv, &ciphertext)?;
+        let secret_key = Zeroizing::new(hex::encode(secret_key_bytes.as_slice()));
+
+        if SignatureKeys::address_from_secret_key(&secret_key)? != self.address() {
+            return Err("Decrypted secret key does not match keystore address".to_string());
+        }
+        Ok(secret_key)
+    }
+
+    pub fn address(&self) -> String {
+        format!("0x{}", self.address.trim_start_matches("0x").to_lowercase())
+    }
+
+    pub fn load(path: &Path) -> Result<Keystore, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read keystore '{}': {}", path.display(), e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse keystore '{}': {}", path.display(), e))
+    }
+
+    // Writes the keystore as UTC--<timestamp>--<address> like other Web3 clients
+    pub fn save(&self, dir: &Path) -> Result<PathBuf, String> {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create keystore dir '{}': {}", dir.display(), e))?;
+
+        let file_name = format!(
+            "UTC--{}--{}",
+            chrono::Utc::now().format("%Y-%m-%dT%H-%M-%S%.9fZ"),
+            self.address.trim_start_matches("0x")
+        );
+        let path = dir.join(file_name);
+        let content = serde_json::to_string(self)
+            .map_err(|e| format!("Failed to serialize keystore: {}", e))?;
+
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options
+            .open(&path)
+            .map_err(|e| format!("Failed to create keystore '{}': {}", path.display(), e))?;
+        file.write_all(content.as_bytes())
+            .map_err(|e| format!("Failed to write keystore '{}': {}", path.display(), e))?;
+
+        Ok(path)
+    }
+
+    // Returns every file in `dir` that parses as a keystore, sorted by file name
+    pub fn list(dir: &Path) -> Result<Vec<(PathBuf, Keystore)>, String> {
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let entries = fs::read_dir(dir)
+            .map_err(|e| format!("Failed to read keystore dir '{}': {}", dir.display(), e))?;
+        let mut keystores: Vec<(PathBuf, Keystore)> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file())
+            .filter_map(|path| Keystore::load(&path).ok().map(|keystore| (path, keystore)))
+            .collect();
+        keystores.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(keystores)
+    }
+
+    fn mac(derived_key: &[u8], ciphertext: &[u8]) -> [u8; 32] {
+        let mut data = derived_key[16..32].to_vec();
+        data.extend_from_slice(ciphertext);
+        SignatureKeys::keccak256(&data)
+    }
+}
+
+// Reads the passphrase from `passphrase_file` if set, otherwise from CLUTCH_KEYSTORE_PASSPHRASE
+pub fn read_passphrase(passphrase_file: &str) -> Result<Zeroizing<String>, String> {
+    if !passphrase_file.is_empty() {
+        let mut content = Zeroizing::new(fs::read_to_string(passphrase_file).map_err(|e| {
+            format!(
+                "Failed to read passphrase file '{}': {}",
+                passphrase_file, e
+            )
+        })?);
+        let trimmed_len = content.trim_end_matches(['\r', '\n']).len();
+        content.truncate(trimmed_len);
+        return Ok(content);
+    }
+
+    std::env::var(KEYSTORE_PASSPHRASE_ENV)
+        .map(Zeroizing::new)
+        .map_err(|_| {
+            format!(
+                "No keystore passphrase: set {} or provide a passphrase file",
+                KEYSTORE_PASSPHRASE_ENV
+            )
+        })
+}
+
+fn decode_hex_field(name: &str, value: &str) -> Result<Vec<u8>, String> {
+    hex::decode(value.trim_start_matches("0x"))
+        .map_err(|_| format!("Invalid hex in keystore field '{}'", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET_KEY: &str = "0883ddd3d07303b87c954b0c9383f7b78f45e002520fc03a8adc80595dbf6509";
+    const ADDRESS: &str = "0x9b6e8afff8329743cac73dbef83ca3cbf9a74c20";
+
+    // Cheap parameters so the tests stay fast in debug builds
+    const TEST_SCRYPT: KeystoreKdf = KeystoreKdf::Scrypt { log_n: 10, r: 8, p: 1 };
+    const TEST_ARGON2ID: KeystoreKdf = KeystoreKdf::Argon2id {
+        m_cost: 1024,
+        t_cost: 1,
+        p_cost: 1,
+    };
+
+    #[test]
+    fn test_encrypt_and_decrypt_roundtrip() {
+        for (kdf, cipher) in [
+            (TEST_SCRYPT, KeystoreCipher::Aes128Ctr),
+            (TEST_ARGON2ID, KeystoreCipher::Aes256Gcm),
+        ] {
+            let keystore = Keystore::encrypt(SECRET_KEY, "passphrase", kdf, cipher).unwrap();
+            assert_eq!(keystore.address(), ADDRESS);
+
+            let json = serde_json::to_string(&keystore).unwrap();
+            let keystore: Keystore = serde_json::from_str(&json).unwrap();
+
+            let secret_key = keystore.decrypt("passphrase").unwrap();
+            assert_eq!(secret_key.as_str(), SECRET_KEY);
+            assert!(keystore.decrypt("wrong passphrase").is_err());
+        }
+    }
+
+    // Web3 Secret Storage file with geth's "light" scrypt parameters, produced by an
+    // independent implementation (Python hashlib.scrypt + AES-128-CTR)
+    #[test]
+    fn test_decrypt_web3_secret_storage_file() {
+        let json = r#"{
+            "crypto": {
+                "cipher": "aes-128-ctr",
+                "cipherparams": {"iv": "101112131415161718191a1b1c1d1e1f"},
+                "ciphertext": "c0df524ad23df08881dbe2052cd4b952b41d6bafda15b4a3424f604a77b0b3f7",
+                "kdf": "scrypt",
+                "kdfparams": {
+                    "dklen": 32,
+                    "n": 4096,
+                    "r": 8,
+                    "p": 6,
+                    "salt": "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f"
+                },
+                "mac": "3568c6a93ba74555891a72228b717cfac422693a5e04880a9f8a1f380f025419"
+            },
+            "id": "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+            "version": 3,
+            "address": "9b6e8afff8329743cac73dbef83ca3cbf9a74c20"
+        }"#;
+
+        let keystore: Keystore = serde_json::from_str(json).unwrap();
+        assert_eq!(keystore.address(), ADDRESS);
+        let secret_key = keystore.decrypt("testpassword").unwrap();
+        assert_eq!(secret_key.as_str(), SECRET_KEY);
+    }
+}
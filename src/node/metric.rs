@@ -1,4 +1,5 @@
 use axum::{routing::get, Router};
+use prometheus_client::metrics::counter::Counter;
 use prometheus_client::metrics::family::Family;
 use prometheus_client::{encoding::text::encode as prometheus_encode, metrics::gauge::Gauge};
 use prometheus_client::registry::Registry;
@@ -11,9 +12,27 @@ pub struct BlockLabels {
     pub block_hash: String,
 }
 
+#[derive(Clone, Debug, Hash, PartialEq, Eq, prometheus_client::encoding::EncodeLabelSet)]
+pub struct PeerLabels {
+    pub peer_id: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, prometheus_client::encoding::EncodeLabelSet)]
+pub struct MisbehaviourLabels {
+    pub misbehaviour: String,
+}
+
 lazy_static::lazy_static! {
     pub static ref LATEST_BLOCK_INDEX: Gauge = Gauge::default();
     pub static ref LATEST_BLOCK: Family<BlockLabels, Gauge> = Family::default();
+    pub static ref FINALIZED_BLOCK_INDEX: Gauge = Gauge::default();
+    pub static ref TX_POOL_PENDING: Gauge = Gauge::default();
+    pub static ref TX_POOL_QUEUED: Gauge = Gauge::default();
+    pub static ref TX_POOL_EVICTED: Counter = Counter::default();
+    pub static ref TX_POOL_REPLACED: Counter = Counter::default();
+    pub static ref PEER_SCORE: Family<PeerLabels, Gauge> = Family::default();
+    pub static ref BANNED_PEERS: Gauge = Gauge::default();
+    pub static ref PEER_MISBEHAVIOUR: Family<MisbehaviourLabels, Counter> = Family::default();
     
     static ref REGISTRY: Arc<Mutex<Registry>> = {
         let mut registry = Registry::default();
@@ -27,6 +46,46 @@ lazy_static::lazy_static! {
             "Current block of the clutch node",
             LATEST_BLOCK.clone(),
         );
+        registry.register(
+            "finalized_block_index",
+            "Index of the latest block finalized by authority precommits",
+            FINALIZED_BLOCK_INDEX.clone(),
+        );
+        registry.register(
+            "tx_pool_pending",
+            "Transactions in the pool that are executable in the next block",
+            TX_POOL_PENDING.clone(),
+        );
+        registry.register(
+            "tx_pool_queued",
+            "Transactions in the pool waiting for an earlier nonce",
+            TX_POOL_QUEUED.clone(),
+        );
+        registry.register(
+            "tx_pool_evicted",
+            "Transactions evicted from the full pool",
+            TX_POOL_EVICTED.clone(),
+        );
+        registry.register(
+            "tx_pool_replaced",
+            "Pending transactions replaced by one with the same sender and nonce",
+            TX_POOL_REPLACED.clone(),
+        );
+        registry.register(
+            "peer_score",
+            "Reputation of peers that misbehaved, 0 for peers in good standing",
+            PEER_SCORE.clone(),
+        );
+        registry.register(
+            "banned_peers",
+            "Peers currently banned for misbehaving",
+            BANNED_PEERS.clone(),
+        );
+        registry.register(
+            "peer_misbehaviour",
+            "Invalid or excess messages received from peers",
+            PEER_MISBEHAVIOUR.clone(),
+        );
         Arc::new(Mutex::new(registry))
     };
 }
@@ -2,11 +2,19 @@ pub mod account_state;
 pub mod aura;
 pub mod blocks;
 pub mod blockchain;
+pub mod chain_spec;
 pub mod configuration;
 pub mod consensus;
+pub mod cli;
+pub mod clique;
 pub mod coordinate;
 pub mod database;
+pub mod dev_chain;
+pub mod equivocation;
 pub mod file_utils;
+pub mod finality;
+pub mod instant_seal;
+pub mod keystore;
 pub mod metric;
 pub mod node_services;
 pub mod p2p_server;
@@ -16,4 +24,6 @@ pub mod signature_keys;
 pub mod time_utils;
 pub mod tracing;
 pub mod transactions;
+pub mod typed_data;
+pub mod validator_set;
 pub mod wss;
\ No newline at end of file
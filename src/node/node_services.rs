@@ -1,13 +1,14 @@
 use crate::node::blockchain::Blockchain;
 use crate::node::configuration::AppConfig;
 use crate::node::metric::serve_metrics;
-use crate::node::p2p_server::commands::DirectMessageType;
+use crate::node::p2p_server::node_key::load_or_generate_node_key;
+use crate::node::p2p_server::peer_store::PeerStore;
 use crate::node::p2p_server::{GossipMessageType, P2PServer, P2PServerCommand};
 use crate::node::rlp_encoding::encode;
 use crate::node::wss::websocket::WebSocket;
 
+use std::path::Path;
 use std::sync::Arc;
-use std::time::Duration;
 use tokio::signal;
 use tokio::sync::{mpsc, oneshot, Mutex};
 use tracing::{debug, error, info};
@@ -40,11 +41,7 @@ impl NodeServices {
         );
 
         if config.block_authoring_enabled {
-            Self::start_authoring_job(Arc::clone(&blockchain_arc), 1, command_tx_p2p.clone());
-        }
-
-        if config.sync_enabled {
-            Self::start_sync(Arc::clone(&blockchain_arc), command_tx_p2p.clone());
+            Self::start_authoring_job(Arc::clone(&blockchain_arc), command_tx_p2p.clone());
         }
 
         Self::wait_for_shutdown_signal(
@@ -86,16 +83,47 @@ impl NodeServices {
         let bootstrap_nodes: Vec<&str> =
             config.bootstrap_nodes.iter().map(|s| s.as_str()).collect();
 
-        let mut p2p_server =
-            match P2PServer::new(&config.libp2p_topic_name, &listen_addrs, &bootstrap_nodes) {
-                Ok(server) => server,
+        let p2p_server = if config.node_key_path.is_empty() {
+            P2PServer::new(&config.libp2p_topic_name, &listen_addrs, &bootstrap_nodes)
+        } else {
+            match load_or_generate_node_key(Path::new(&config.node_key_path)) {
+                Ok(node_key) => P2PServer::with_node_key(
+                    node_key,
+                    &config.libp2p_topic_name,
+                    &listen_addrs,
+                    &bootstrap_nodes,
+                ),
                 Err(e) => {
-                    error!("Failed to create P2PServer: {}", e);
+                    error!("Failed to load node key: {}", e);
                     return;
                 }
-            };
+            }
+        };
+        let mut p2p_server = match p2p_server {
+            Ok(server) => server,
+            Err(e) => {
+                error!("Failed to create P2PServer: {}", e);
+                return;
+            }
+        };
+        p2p_server.sync.set_enabled(config.sync_enabled);
+        p2p_server.discovery.set_target_peers(config.target_peers);
+        if !config.peer_store_path.is_empty() {
+            match PeerStore::open(Path::new(&config.peer_store_path)) {
+                Ok(peer_store) => p2p_server.discovery.set_peer_store(peer_store),
+                Err(e) => error!("Failed to open peer store, starting without known peers: {}", e),
+            }
+        }
+
+        let bind_node_key_to_author = config.bind_node_key_to_author;
 
         tokio::spawn(async move {
+            if bind_node_key_to_author {
+                let local_peer_id = *p2p_server.behaviour.local_peer_id();
+                let node_identity = blockchain.lock().await.node_identity(&local_peer_id);
+                info!("Peer id {} bound to author {}", local_peer_id, node_identity.authority);
+                p2p_server.sync.set_node_identity(node_identity);
+            }
             {
                 if let Err(e) = p2p_server.run(Arc::clone(&blockchain), command_rx).await {
                     error!("Error running libp2p: {}", e);
@@ -123,16 +151,35 @@ impl NodeServices {
 
     pub fn start_authoring_job(
         blockchain: Arc<Mutex<Blockchain>>,
-        interval_secs: u64,
         command_tx_p2p: tokio::sync::mpsc::Sender<P2PServerCommand>,
     ) {
         tokio::spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
             loop {
-                interval.tick().await;
-                let blockchain = blockchain.lock().await;
-                match blockchain.author_new_block() {
-                    Ok(block) => {
+                // Wake when the consensus engine may author the next block, or when a
+                // transaction arrives for engines that seal on demand
+                let (until_next_slot, seal_trigger) = {
+                    let blockchain = blockchain.lock().await;
+                    (blockchain.duration_until_next_slot(), blockchain.seal_trigger())
+                };
+                match seal_trigger {
+                    Some(seal_trigger) => {
+                        tokio::select! {
+                            _ = tokio::time::sleep(until_next_slot) => {}
+                            _ = seal_trigger.notified() => {}
+                        }
+                    }
+                    None => tokio::time::sleep(until_next_slot).await,
+                }
+
+                let authored = {
+                    let blockchain = blockchain.lock().await;
+                    blockchain.author_new_block().map(|block| {
+                        let precommit = blockchain.precommit_block(&block);
+                        (block, precommit)
+                    })
+                };
+                match authored {
+                    Ok((block, precommit)) => {
                         let encoded_block = encode(&block);
                         P2PServer::gossip_message_command(
                             command_tx_p2p.clone(),
@@ -140,6 +187,8 @@ impl NodeServices {
                             &encoded_block,
                         )
                         .await;
+                        P2PServer::gossip_precommit_command(command_tx_p2p.clone(), &block, precommit)
+                            .await;
                     }
                     Err(e) => {
                         debug!("Error authoring new block: {:?}", e);
@@ -148,38 +197,4 @@ impl NodeServices {
             }
         });
     }
-
-    pub fn start_sync(
-        blockchain: Arc<Mutex<Blockchain>>,
-        command_tx_p2p: tokio::sync::mpsc::Sender<P2PServerCommand>,
-    ) {
-        tokio::spawn(async move {
-            tokio::time::sleep(Duration::from_secs(3)).await;
-
-            let blockchain = blockchain.lock().await;
-            let connected_peers = P2PServer::get_connected_peers_command(command_tx_p2p.clone())
-                .await
-                .unwrap();
-
-            info!("connected peers: {:?}", connected_peers);
-
-            if let Some(peer_id) = connected_peers.iter().next() {
-                debug!("Selected peer for synchronization: {:?}", peer_id);
-
-                let handshake = blockchain.handshake().unwrap();
-                let encoded_handshake = encode(&handshake);
-
-                P2PServer::send_direct_message_command(
-                    command_tx_p2p.clone(),
-                    peer_id.clone(),
-                    DirectMessageType::Handshake,
-                    &encoded_handshake,
-                )
-                .await
-                .expect_err("Failed to send handshake message");
-            } else {
-                error!("Failed to select a peer from the connected peers set.");
-            }
-        });
-    }
 }
@@ -1,5 +1,7 @@
 use libp2p::{
-    gossipsub::Behaviour as GossipsubBehaviour, mdns::tokio::Behaviour as MsdnBehaviour,
+    gossipsub::Behaviour as GossipsubBehaviour, identify::Behaviour as IdentifyBehaviour,
+    kad::{store::MemoryStore, Behaviour as KademliaBehaviour},
+    mdns::tokio::Behaviour as MsdnBehaviour,
     request_response::cbor::Behaviour as RequestResponseBehavior, swarm::NetworkBehaviour,
 };
 
@@ -10,6 +12,8 @@ pub struct P2PBehaviour {
     pub gossipsub: GossipsubBehaviour,
     pub mdns: MsdnBehaviour,
     pub request_response: RequestResponseBehavior<DirectMessageRequest, DirectMessageResponse>,
+    pub kademlia: KademliaBehaviour<MemoryStore>,
+    pub identify: IdentifyBehaviour,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -7,6 +7,8 @@ use std::collections::HashSet;
 use tokio::sync::oneshot;
 
 use super::behaviour::DirectMessageRequest;
+use super::peer_reputation::PeerScore;
+use super::sync::AuthorityPeer;
 
 #[allow(dead_code)]
 pub enum P2PServerCommand {
@@ -25,12 +27,20 @@ pub enum P2PServerCommand {
     GetLocalPeerId {
         response_tx: oneshot::Sender<PeerId>,
     },
+    GetPeerScores {
+        response_tx: oneshot::Sender<Vec<PeerScore>>,
+    },
+    GetAuthorityPeers {
+        response_tx: oneshot::Sender<Vec<AuthorityPeer>>,
+    },
 }
 
 #[derive(Debug)]
 pub enum GossipMessageType {
     Transaction,
     Block,
+    Precommit,
+    Equivocation,
 }
 
 impl GossipMessageType {
@@ -38,6 +48,8 @@ impl GossipMessageType {
         match self {
             GossipMessageType::Transaction => 0x01,
             GossipMessageType::Block => 0x02,
+            GossipMessageType::Precommit => 0x03,
+            GossipMessageType::Equivocation => 0x04,
         }
     }
 
@@ -45,6 +57,8 @@ impl GossipMessageType {
         match byte {
             0x01 => Some(GossipMessageType::Transaction),
             0x02 => Some(GossipMessageType::Block),
+            0x03 => Some(GossipMessageType::Precommit),
+            0x04 => Some(GossipMessageType::Equivocation),
             _ => None,
         }
     }
@@ -56,7 +70,11 @@ pub enum DirectMessageType {
     GetBlockHeaders,
     BlockHeaders,
     GetBlockBodies,
-    BlockBodies 
+    BlockBodies,
+    GetPooledTransactionHashes,
+    PooledTransactionHashes,
+    GetPooledTransactions,
+    PooledTransactions,
 }
 
 impl DirectMessageType {
@@ -67,6 +85,10 @@ impl DirectMessageType {
             DirectMessageType::BlockHeaders => 0x03,
             DirectMessageType::GetBlockBodies => 0x04,
             DirectMessageType::BlockBodies => 0x05,
+            DirectMessageType::GetPooledTransactionHashes => 0x06,
+            DirectMessageType::PooledTransactionHashes => 0x07,
+            DirectMessageType::GetPooledTransactions => 0x08,
+            DirectMessageType::PooledTransactions => 0x09,
         }
     }
 
@@ -77,6 +99,10 @@ impl DirectMessageType {
             0x03 => Some(DirectMessageType::BlockHeaders),
             0x04 => Some(DirectMessageType::GetBlockBodies),
             0x05 => Some(DirectMessageType::BlockBodies),
+            0x06 => Some(DirectMessageType::GetPooledTransactionHashes),
+            0x07 => Some(DirectMessageType::PooledTransactionHashes),
+            0x08 => Some(DirectMessageType::GetPooledTransactions),
+            0x09 => Some(DirectMessageType::PooledTransactions),
             _ => None,
         }
     }
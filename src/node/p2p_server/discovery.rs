@@ -0,0 +1,174 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use libp2p::{
+    core::ConnectedPoint,
+    identify,
+    swarm::{
+        dial_opts::{DialOpts, PeerCondition},
+        Swarm,
+    },
+    Multiaddr, PeerId, StreamProtocol,
+};
+use tracing::{debug, info, warn};
+
+use super::peer_reputation::PeerReputation;
+use super::peer_store::PeerStore;
+use super::P2PBehaviour;
+
+pub const KADEMLIA_PROTOCOL: StreamProtocol = StreamProtocol::new("/clutch/kad/1.0.0");
+pub const IDENTIFY_PROTOCOL: &str = "/clutch/id/1.0.0";
+// How often the peer count is checked and the address book saved
+pub const DISCOVERY_TICK: Duration = Duration::from_secs(5);
+pub const DEFAULT_TARGET_PEERS: usize = 8;
+// A peer that could not be dialed is tried again after a delay doubling up to the maximum
+const MIN_REDIAL_DELAY: Duration = Duration::from_secs(5);
+const MAX_REDIAL_DELAY: Duration = Duration::from_secs(300);
+
+#[derive(Debug)]
+struct Redial {
+    next_at: Instant,
+    attempts: u32,
+}
+
+// Keeps the node connected to target_peers peers. Addresses learned from identify, Kademlia
+// and mDNS go into the persistent peer store, the listen addresses peers report with identify
+// into the Kademlia routing table. While the node has fewer peers than the target, known peers
+// are dialed (lost ones first, as they were seen last) and Kademlia is walked for new ones.
+pub struct Discovery {
+    target_peers: usize,
+    peer_store: PeerStore,
+    redials: HashMap<PeerId, Redial>,
+}
+
+impl Default for Discovery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Discovery {
+    pub fn new() -> Self {
+        Discovery {
+            target_peers: DEFAULT_TARGET_PEERS,
+            peer_store: PeerStore::new(),
+            redials: HashMap::new(),
+        }
+    }
+
+    pub fn set_target_peers(&mut self, target_peers: usize) {
+        self.target_peers = target_peers;
+    }
+
+    pub fn set_peer_store(&mut self, peer_store: PeerStore) {
+        self.peer_store = peer_store;
+    }
+
+    // Seeds the routing table with the peers known from earlier runs
+    pub fn start(&mut self, swarm: &mut Swarm<P2PBehaviour>) {
+        for peer in self.peer_store.peers() {
+            for address in self.peer_store.addresses(&peer) {
+                swarm.behaviour_mut().kademlia.add_address(&peer, address);
+            }
+        }
+    }
+
+    // mDNS addresses are only reachable on the local network, so they are not handed out to
+    // DHT peers
+    pub fn on_mdns_discovered(&mut self, peer: &PeerId, address: Multiaddr) {
+        self.peer_store.add_address(peer, address);
+    }
+
+    pub fn on_connection_established(&mut self, peer: &PeerId, endpoint: &ConnectedPoint) {
+        // The address of an inbound connection is the remote's ephemeral port, its listen
+        // addresses come with identify
+        if let ConnectedPoint::Dialer { address, .. } = endpoint {
+            self.peer_store.add_address(peer, address.clone());
+        }
+        self.peer_store.mark_seen(peer);
+        self.redials.remove(peer);
+    }
+
+    // A lost peer is redialed on the next tick
+    pub fn on_connection_closed(&mut self, peer: &PeerId) {
+        self.peer_store.mark_seen(peer);
+        self.redials.remove(peer);
+    }
+
+    // Only peers of this network, speaking the same Kademlia protocol, are added
+    pub fn on_identify(&mut self, swarm: &mut Swarm<P2PBehaviour>, peer: &PeerId, info: identify::Info) {
+        if info.protocol_version != IDENTIFY_PROTOCOL || !info.protocols.contains(&KADEMLIA_PROTOCOL) {
+            debug!("Peer {} does not speak {}", peer, KADEMLIA_PROTOCOL);
+            return;
+        }
+        for address in info.listen_addrs {
+            swarm.behaviour_mut().kademlia.add_address(peer, address.clone());
+            self.peer_store.add_address(peer, address);
+        }
+    }
+
+    pub fn on_routing_updated(&mut self, peer: &PeerId, addresses: Vec<Multiaddr>) {
+        // Listed most recent last
+        for address in addresses.into_iter().rev() {
+            self.peer_store.add_address(peer, address);
+        }
+    }
+
+    // Called every DISCOVERY_TICK
+    pub fn tick(&mut self, swarm: &mut Swarm<P2PBehaviour>, reputation: &PeerReputation) {
+        if let Err(e) = self.peer_store.save() {
+            warn!("{}", e);
+        }
+
+        let connected: HashSet<PeerId> = swarm.connected_peers().copied().collect();
+        if connected.len() >= self.target_peers {
+            return;
+        }
+        let now = Instant::now();
+        let mut missing = self.target_peers - connected.len();
+        for peer in self.peer_store.peers() {
+            if missing == 0 {
+                break;
+            }
+            let due = self
+                .redials
+                .get(&peer)
+                .is_none_or(|redial| redial.next_at <= now);
+            if connected.contains(&peer) || reputation.is_banned(&peer) || !due {
+                continue;
+            }
+            self.dial(swarm, peer, now);
+            missing -= 1;
+        }
+
+        // Not enough known peers, look up the peers closest to a random id
+        if missing > 0 {
+            debug!("{} peers below target, searching the DHT", missing);
+            swarm
+                .behaviour_mut()
+                .kademlia
+                .get_closest_peers(PeerId::random());
+        }
+    }
+
+    fn dial(&mut self, swarm: &mut Swarm<P2PBehaviour>, peer: PeerId, now: Instant) {
+        let redial = self.redials.entry(peer).or_insert(Redial {
+            next_at: now,
+            attempts: 0,
+        });
+        let delay = MIN_REDIAL_DELAY
+            .saturating_mul(2u32.saturating_pow(redial.attempts))
+            .min(MAX_REDIAL_DELAY);
+        redial.next_at = now + delay;
+        redial.attempts += 1;
+
+        let dial_opts = DialOpts::peer_id(peer)
+            .addresses(self.peer_store.addresses(&peer))
+            .condition(PeerCondition::DisconnectedAndNotDialing)
+            .build();
+        match swarm.dial(dial_opts) {
+            Ok(_) => info!("Dialing known peer {}", peer),
+            Err(e) => debug!("Failed to dial known peer {}: {}", peer, e),
+        }
+    }
+}
@@ -1,22 +1,33 @@
 use crate::node::{blockchain::Blockchain, blocks::block::Block};
-use crate::node::rlp_encoding::decode;
+use crate::node::equivocation::{EquivocationProof, SignedHeader};
+use crate::node::finality::Precommit;
+use crate::node::rlp_encoding::{decode, encode};
 use crate::node::transactions::transaction::Transaction;
-use crate::node::p2p_server::GossipMessageType;
+use crate::node::p2p_server::peer_reputation::Misbehaviour;
+use crate::node::p2p_server::sync::SyncManager;
+use crate::node::p2p_server::wire::{Envelope, GOSSIP_VERSION};
+use crate::node::p2p_server::{GossipMessageType, P2PBehaviour};
 
 use libp2p::{
-    gossipsub::{self, MessageId},
+    gossipsub::{self, IdentTopic, MessageId},
+    swarm::Swarm,
     PeerId,
 };
 use tracing::{error, info};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
+// Returns what the peer did wrong when the message is undecodable or fails verification, so
+// it is not relayed further
 pub async fn handle_gossipsub_message(
     peer_id: PeerId,
     id: MessageId,
     message: gossipsub::Message,
+    swarm: &mut Swarm<P2PBehaviour>,
+    topic: &IdentTopic,
     blockchain: &Arc<Mutex<Blockchain>>,
-) {
+    sync: &mut SyncManager,
+) -> Result<(), Misbehaviour> {
     info!(
         "Received gossip message from peer: {} with id:'{}': {} ",
         peer_id,
@@ -24,32 +35,81 @@ pub async fn handle_gossipsub_message(
         String::from_utf8_lossy(&message.data),
     );
 
-    let message_type = GossipMessageType::from_byte(message.data[0]);
-    let payload = &message.data[1..];
+    let envelope = Envelope::decode(&message.data).map_err(|e| {
+        error!("Received undecodable gossip message from peer {}: {}", peer_id, e);
+        Misbehaviour::UndecodableMessage
+    })?;
+    let message_type = GossipMessageType::from_byte(envelope.message_type);
+    let payload = &envelope.payload;
 
     match message_type {
         Some(GossipMessageType::Transaction) => match decode::<Transaction>(payload) {
             Ok(transaction) => {
                 info!("Decoded transaction: {:?}", &transaction);
+                let chain_id = blockchain.lock().await.chain_id();
+                if let Err(e) = transaction.verify_signature(chain_id) {
+                    error!("Received invalid transaction: {}", e);
+                    return Err(Misbehaviour::InvalidTransaction);
+                }
                 handle_received_transaction(&transaction, blockchain).await;
             }
             Err(e) => {
                 error!("Failed to decode transaction: {:?}", e);
+                return Err(Misbehaviour::UndecodableMessage);
             }
         },
         Some(GossipMessageType::Block) => match decode::<Block>(payload) {
             Ok(block) => {
                 info!("Decoded block: {:?}", &block);
-                handle_received_block(&block, blockchain).await;
+                // Blocks are relayed before they are imported, so at least their hash and
+                // signature are checked
+                if let Err(e) = SignedHeader::from_block(&block).verify() {
+                    error!("Received invalid block {}: {}", block.index, e);
+                    return Err(Misbehaviour::InvalidBlock);
+                }
+                handle_received_block(&peer_id, &block, swarm, topic, blockchain, sync).await;
             }
             Err(e) => {
                 error!("Failed to decode block: {:?}", e);
+                return Err(Misbehaviour::UndecodableMessage);
+            }
+        },
+        Some(GossipMessageType::Precommit) => match decode::<Precommit>(payload) {
+            Ok(precommit) => {
+                info!("Decoded precommit: {:?}", &precommit);
+                let chain_id = blockchain.lock().await.chain_id();
+                if let Err(e) = precommit.verify_signature(chain_id) {
+                    error!("Received invalid precommit: {}", e);
+                    return Err(Misbehaviour::InvalidPrecommit);
+                }
+                handle_received_precommit(&precommit, blockchain).await;
+            }
+            Err(e) => {
+                error!("Failed to decode precommit: {:?}", e);
+                return Err(Misbehaviour::UndecodableMessage);
+            }
+        },
+        Some(GossipMessageType::Equivocation) => match decode::<EquivocationProof>(payload) {
+            Ok(proof) => {
+                info!("Decoded equivocation proof: {:?}", &proof);
+                let verified = blockchain.lock().await.verify_equivocation(&proof);
+                if let Err(e) = verified {
+                    error!("Received invalid equivocation proof: {}", e);
+                    return Err(Misbehaviour::InvalidEquivocation);
+                }
+                handle_received_equivocation(&proof, swarm, topic, blockchain).await;
+            }
+            Err(e) => {
+                error!("Failed to decode equivocation proof: {:?}", e);
+                return Err(Misbehaviour::UndecodableMessage);
             }
         },
         _ => {
             error!("Unknown message type: {:?}", message_type);
+            return Err(Misbehaviour::UndecodableMessage);
         }
     }
+    Ok(())
 }
 
 async fn handle_received_transaction(
@@ -67,14 +127,101 @@ async fn handle_received_transaction(
     }
 }
 
-async fn handle_received_block(block: &Block, blockchain: &Arc<Mutex<Blockchain>>) {
-    let result = {
+async fn handle_received_block(
+    peer_id: &PeerId,
+    block: &Block,
+    swarm: &mut Swarm<P2PBehaviour>,
+    topic: &IdentTopic,
+    blockchain: &Arc<Mutex<Blockchain>>,
+    sync: &mut SyncManager,
+) {
+    let latest_index = blockchain
+        .lock()
+        .await
+        .get_latest_block()
+        .map(|block| block.index)
+        .unwrap_or(0);
+    // A block ahead of the local head is kept until the blocks below it are synced
+    if block.index > latest_index + 1 && sync.buffer_gossiped_block(peer_id, block) {
+        info!(
+            "Buffered block {} from P2P, syncing blocks {} to {} first",
+            block.index,
+            latest_index + 1,
+            block.index - 1
+        );
+        sync.progress(swarm, topic, blockchain).await;
+        return;
+    }
+
+    let (proof, result) = {
         let blockchain = blockchain.lock().await;
-        blockchain.import_block(&block)
+        let proof = blockchain.observe_block(block);
+        let result = blockchain
+            .import_block(block)
+            .and_then(|_| blockchain.precommit_block(block));
+        (proof, result)
     };
 
+    if let Some(proof) = proof {
+        publish(swarm, topic, GossipMessageType::Equivocation, encode(&proof));
+        handle_received_equivocation(&proof, swarm, topic, blockchain).await;
+    }
+
     match result {
-        Ok(_) => info!("Block added to blockchain from P2P"),
+        Ok(precommit) => {
+            info!("Block added to blockchain from P2P");
+            if let Some(precommit) = precommit {
+                publish(swarm, topic, GossipMessageType::Precommit, encode(&precommit));
+            }
+            // Buffered blocks that now continue the chain
+            sync.progress(swarm, topic, blockchain).await;
+        }
         Err(e) => error!("Failed to add block to blockchain: {:?}", e),
     }
 }
+
+pub async fn handle_received_equivocation(
+    proof: &EquivocationProof,
+    swarm: &mut Swarm<P2PBehaviour>,
+    topic: &IdentTopic,
+    blockchain: &Arc<Mutex<Blockchain>>,
+) {
+    let result = {
+        let blockchain = blockchain.lock().await;
+        blockchain.report_equivocation(proof)
+    };
+
+    match result {
+        Ok(Some(transaction)) => {
+            info!("Reported equivocation of {} with transaction {}", proof.offender(), transaction.hash);
+            publish(swarm, topic, GossipMessageType::Transaction, encode(&transaction));
+        }
+        Ok(None) => {}
+        Err(e) => error!("Failed to report equivocation: {:?}", e),
+    }
+}
+
+async fn handle_received_precommit(precommit: &Precommit, blockchain: &Arc<Mutex<Blockchain>>) {
+    let result = {
+        let blockchain = blockchain.lock().await;
+        blockchain.add_precommit(precommit)
+    };
+
+    match result {
+        Ok(Some(block)) => info!("Block {} finalized by precommits from P2P", block.index),
+        Ok(None) => info!("Precommit added from P2P"),
+        Err(e) => error!("Failed to add precommit: {:?}", e),
+    }
+}
+
+pub fn publish(
+    swarm: &mut Swarm<P2PBehaviour>,
+    topic: &IdentTopic,
+    message_type: GossipMessageType,
+    payload: Vec<u8>,
+) {
+    let message = Envelope::new(GOSSIP_VERSION, message_type.as_byte(), payload).encode();
+    if let Err(e) = swarm.behaviour_mut().gossipsub.publish(topic.clone(), message) {
+        error!("Failed to gossip {:?}: {:?}", message_type, e);
+    }
+}
@@ -1,8 +1,94 @@
 use serde::{Deserialize, Serialize};
 
+use super::node_key::NodeIdentity;
+
+// Version of the messages peers exchange, raised when they change incompatibly
+pub const PROTOCOL_VERSION: u32 = 2;
+// Peers below it are disconnected
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+// Requests a peer answers, sync asks only peers that announced them. Capabilities a node does
+// not know are ignored, so new ones can be added without raising the protocol version.
+pub const CAPABILITY_BLOCK_HEADERS: &str = "block_headers";
+pub const CAPABILITY_BLOCK_BODIES: &str = "block_bodies";
+pub const CAPABILITY_POOLED_TRANSACTIONS: &str = "pooled_transactions";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Handshake {
+    pub chain_id: u64,
+    pub protocol_version: u32,
+    pub capabilities: Vec<String>,
     pub genesis_block_hash: String,
     pub latest_block_hash: String,
     pub latest_block_index: usize,
+    // Set by nodes bound to an authority key
+    pub node_identity: Option<NodeIdentity>,
+    // Received in the layout of nodes from before chain ids, which carries no chain id, so
+    // their chain is told by the genesis block alone
+    #[serde(skip)]
+    pub legacy: bool,
+}
+
+impl Handshake {
+    pub fn local_capabilities() -> Vec<String> {
+        vec![
+            CAPABILITY_BLOCK_HEADERS.to_string(),
+            CAPABILITY_BLOCK_BODIES.to_string(),
+            CAPABILITY_POOLED_TRANSACTIONS.to_string(),
+        ]
+    }
+
+    // A peer is on the same chain when chain id and genesis block match and it speaks a
+    // protocol version this node supports
+    pub fn check_compatible(&self, local: &Handshake) -> Result<(), String> {
+        if !self.legacy && self.chain_id != local.chain_id {
+            return Err(format!("chain id {}, expected {}", self.chain_id, local.chain_id));
+        }
+        if self.genesis_block_hash != local.genesis_block_hash {
+            return Err(format!(
+                "genesis block {}, expected {}",
+                self.genesis_block_hash, local.genesis_block_hash
+            ));
+        }
+        if self.protocol_version < MIN_PROTOCOL_VERSION {
+            return Err(format!(
+                "protocol version {}, at least {} is supported",
+                self.protocol_version, MIN_PROTOCOL_VERSION
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handshake(chain_id: u64, genesis_block_hash: &str, protocol_version: u32) -> Handshake {
+        Handshake {
+            chain_id,
+            protocol_version,
+            capabilities: Handshake::local_capabilities(),
+            genesis_block_hash: genesis_block_hash.to_string(),
+            latest_block_hash: genesis_block_hash.to_string(),
+            latest_block_index: 0,
+            node_identity: None,
+            legacy: false,
+        }
+    }
+
+    #[test]
+    fn test_check_compatible() {
+        let local = handshake(1, "genesis", PROTOCOL_VERSION);
+        assert!(handshake(1, "genesis", PROTOCOL_VERSION).check_compatible(&local).is_ok());
+        assert!(handshake(2, "genesis", PROTOCOL_VERSION).check_compatible(&local).is_err());
+        assert!(handshake(1, "other", PROTOCOL_VERSION).check_compatible(&local).is_err());
+        assert!(handshake(1, "genesis", MIN_PROTOCOL_VERSION - 1).check_compatible(&local).is_err());
+
+        let mut legacy = handshake(0, "genesis", 1);
+        legacy.legacy = true;
+        assert!(legacy.check_compatible(&local).is_ok());
+        legacy.genesis_block_hash = "other".to_string();
+        assert!(legacy.check_compatible(&local).is_err());
+    }
 }
@@ -1,11 +1,18 @@
 pub mod behaviour;
 pub mod commands;
+pub mod discovery;
 pub mod get_block_bodies;
 pub mod get_block_header;
 pub mod gossipsub_handler;
 pub mod handshake;
+pub mod node_key;
+pub mod peer_reputation;
+pub mod peer_store;
+pub mod pooled_transactions;
 pub mod request_response_handler;
 pub mod server;
+pub mod sync;
+pub mod wire;
 pub use behaviour::P2PBehaviour;
 pub use commands::{GossipMessageType, P2PServerCommand};
 pub use server::P2PServer;
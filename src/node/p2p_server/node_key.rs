@@ -0,0 +1,150 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+use libp2p::{
+    identity::{ed25519, Keypair},
+    PeerId,
+};
+use rlp::RlpStream;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use zeroize::Zeroizing;
+
+use crate::node::signature_keys::SignatureKeys;
+
+// Signed messages of other kinds never start with this, so a node identity signature can not
+// be replayed as one of them
+const NODE_IDENTITY_DOMAIN: &str = "clutch-node-identity";
+
+// Loads the ed25519 libp2p identity from a file holding the hex secret key, so the peer id
+// stays the same across restarts. The file is created with a new key if it does not exist.
+pub fn load_or_generate_node_key(path: &Path) -> Result<Keypair, String> {
+    if path.exists() {
+        return load_node_key(path);
+    }
+    let keypair = generate_node_key(path)?;
+    info!(
+        "Generated node key {} with peer id {}",
+        path.display(),
+        keypair.public().to_peer_id()
+    );
+    Ok(keypair)
+}
+
+pub fn load_node_key(path: &Path) -> Result<Keypair, String> {
+    let content = Zeroizing::new(
+        fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read node key '{}': {}", path.display(), e))?,
+    );
+    let mut bytes = Zeroizing::new(
+        hex::decode(content.trim().trim_start_matches("0x"))
+            .map_err(|_| format!("Node key '{}' is not hex", path.display()))?,
+    );
+    let secret_key = ed25519::SecretKey::try_from_bytes(bytes.as_mut_slice())
+        .map_err(|e| format!("Invalid node key '{}': {}", path.display(), e))?;
+    Ok(Keypair::from(ed25519::Keypair::from(secret_key)))
+}
+
+// Fails if the file exists, a node key is never overwritten
+pub fn generate_node_key(path: &Path) -> Result<Keypair, String> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create node key dir '{}': {}", dir.display(), e))?;
+    }
+
+    let secret_key = ed25519::SecretKey::generate();
+    let content = Zeroizing::new(hex::encode(secret_key.as_ref()));
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(path)
+        .map_err(|e| format!("Failed to create node key '{}': {}", path.display(), e))?;
+    file.write_all(content.as_bytes())
+        .map_err(|e| format!("Failed to write node key '{}': {}", path.display(), e))?;
+
+    Ok(Keypair::from(ed25519::Keypair::from(secret_key)))
+}
+
+// An authority's signature over its node's peer id, sent in handshakes so peers know which
+// connection belongs to which authority
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct NodeIdentity {
+    pub authority: String,
+    pub signature_r: String,
+    pub signature_s: String,
+    pub signature_v: i32,
+}
+
+impl NodeIdentity {
+    pub fn new(peer_id: &PeerId, authority: &str, secret_key: &str) -> NodeIdentity {
+        let (r, s, v) = SignatureKeys::sign_digest(secret_key, &Self::signing_digest(peer_id));
+        NodeIdentity {
+            authority: authority.to_string(),
+            signature_r: r,
+            signature_s: s,
+            signature_v: v,
+        }
+    }
+
+    // keccak256 of the RLP list [NODE_IDENTITY_DOMAIN, peer_id]
+    fn signing_digest(peer_id: &PeerId) -> [u8; 32] {
+        let mut stream = RlpStream::new_list(2);
+        stream.append(&NODE_IDENTITY_DOMAIN);
+        stream.append(&peer_id.to_bytes());
+        SignatureKeys::keccak256(&stream.out())
+    }
+
+    // Checks the identity was signed by the authority for the peer it was received from
+    pub fn verify(&self, peer_id: &PeerId) -> Result<(), String> {
+        let recovered_authority = SignatureKeys::recover_address_from_digest(
+            &Self::signing_digest(peer_id),
+            &self.signature_r,
+            &self.signature_s,
+            self.signature_v,
+        )?;
+        if recovered_authority != self.authority {
+            return Err(format!(
+                "Node identity of peer {} is not signed by {}",
+                peer_id, self.authority
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AUTHORITY: &str = "0x9b6e8afff8329743cac73dbef83ca3cbf9a74c20";
+    const AUTHORITY_SECRET_KEY: &str =
+        "0883ddd3d07303b87c954b0c9383f7b78f45e002520fc03a8adc80595dbf6509";
+
+    #[test]
+    fn test_node_key_kept_across_restarts() {
+        let path = std::env::temp_dir().join(format!("clutch-node-key-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let keypair = load_or_generate_node_key(&path).unwrap();
+        let reloaded = load_or_generate_node_key(&path).unwrap();
+        assert_eq!(keypair.public().to_peer_id(), reloaded.public().to_peer_id());
+        assert!(generate_node_key(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_node_identity_bound_to_peer_id() {
+        let peer_id = PeerId::random();
+        let identity = NodeIdentity::new(&peer_id, AUTHORITY, AUTHORITY_SECRET_KEY);
+        assert!(identity.verify(&peer_id).is_ok());
+
+        // Replayed by another node
+        assert!(identity.verify(&PeerId::random()).is_err());
+    }
+}
@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use libp2p::{
+    gossipsub::{PeerScoreParams, PeerScoreThresholds},
+    swarm::Swarm,
+    PeerId,
+};
+use serde::Serialize;
+use tracing::{info, warn};
+
+use super::P2PBehaviour;
+use crate::node::metric;
+
+// How often scores recover and bans expire
+pub const REPUTATION_TICK: Duration = Duration::from_secs(10);
+// Score regained per tick, so a peer that stops misbehaving is back at 0 after a while
+const SCORE_RECOVERY: i32 = 10;
+// Peers at or below the threshold are disconnected and banned for BAN_DURATION
+const BAN_THRESHOLD: i32 = -100;
+const BAN_DURATION: Duration = Duration::from_secs(600);
+// Gossip messages and requests a peer may send per window, further ones are dropped
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(1);
+const MAX_MESSAGES_PER_WINDOW: u32 = 200;
+// Weight of the score as gossipsub application score, BAN_THRESHOLD maps to the graylist
+// threshold so gossipsub stops talking to a peer about when it is banned
+const GOSSIPSUB_SCORE_WEIGHT: f64 = 0.8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Misbehaviour {
+    UndecodableMessage,
+    InvalidTransaction,
+    InvalidBlock,
+    InvalidPrecommit,
+    InvalidEquivocation,
+    InvalidSyncResponse,
+    InvalidNodeIdentity,
+    // Another chain or an unsupported protocol version. Not malicious, but the peer is of no
+    // use, so it is banned right away rather than redialed.
+    IncompatibleHandshake,
+    TooManyMessages,
+}
+
+impl Misbehaviour {
+    // Two invalid blocks or sync responses, or ten undecodable messages, get a peer banned
+    pub fn penalty(&self) -> i32 {
+        match self {
+            Misbehaviour::UndecodableMessage => 10,
+            Misbehaviour::InvalidTransaction => 20,
+            Misbehaviour::InvalidBlock => 50,
+            Misbehaviour::InvalidPrecommit => 20,
+            Misbehaviour::InvalidEquivocation => 50,
+            Misbehaviour::InvalidSyncResponse => 50,
+            Misbehaviour::InvalidNodeIdentity => 50,
+            Misbehaviour::IncompatibleHandshake => -BAN_THRESHOLD,
+            Misbehaviour::TooManyMessages => 5,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Misbehaviour::UndecodableMessage => "undecodable_message",
+            Misbehaviour::InvalidTransaction => "invalid_transaction",
+            Misbehaviour::InvalidBlock => "invalid_block",
+            Misbehaviour::InvalidPrecommit => "invalid_precommit",
+            Misbehaviour::InvalidEquivocation => "invalid_equivocation",
+            Misbehaviour::InvalidSyncResponse => "invalid_sync_response",
+            Misbehaviour::InvalidNodeIdentity => "invalid_node_identity",
+            Misbehaviour::IncompatibleHandshake => "incompatible_handshake",
+            Misbehaviour::TooManyMessages => "too_many_messages",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PeerScore {
+    pub peer_id: String,
+    pub score: i32,
+    // Seconds left of the ban, none for peers that are not banned
+    pub banned_for: Option<u64>,
+}
+
+#[derive(Debug)]
+struct PeerRecord {
+    score: i32,
+    window_start: Instant,
+    messages_in_window: u32,
+}
+
+impl PeerRecord {
+    fn new(now: Instant) -> Self {
+        PeerRecord {
+            score: 0,
+            window_start: now,
+            messages_in_window: 0,
+        }
+    }
+}
+
+// Tracks how peers behave: invalid messages and message floods cost score, and peers whose
+// score drops to BAN_THRESHOLD are disconnected and refused until their ban expires. Scores
+// are also handed to gossipsub, so it stops relaying to and from peers going bad.
+pub struct PeerReputation {
+    peers: HashMap<PeerId, PeerRecord>,
+    banned: HashMap<PeerId, Instant>,
+}
+
+impl Default for PeerReputation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PeerReputation {
+    pub fn new() -> Self {
+        PeerReputation {
+            peers: HashMap::new(),
+            banned: HashMap::new(),
+        }
+    }
+
+    // Gossipsub scores peers by our score alone, the topic and delivery scores are not used
+    pub fn gossipsub_score_params() -> (PeerScoreParams, PeerScoreThresholds) {
+        let params = PeerScoreParams {
+            app_specific_weight: GOSSIPSUB_SCORE_WEIGHT,
+            ..PeerScoreParams::default()
+        };
+        (params, PeerScoreThresholds::default())
+    }
+
+    pub fn is_banned(&self, peer: &PeerId) -> bool {
+        self.banned.contains_key(peer)
+    }
+
+    pub fn score(&self, peer: &PeerId) -> i32 {
+        self.peers.get(peer).map(|record| record.score).unwrap_or(0)
+    }
+
+    // Counts a gossip message or request from the peer. Returns false for messages over the
+    // rate limit, which are dropped and cost the peer score.
+    pub fn allow_message(&mut self, swarm: &mut Swarm<P2PBehaviour>, peer: &PeerId) -> bool {
+        if self.count_message(peer, Instant::now()) {
+            return true;
+        }
+        self.report(swarm, peer, Misbehaviour::TooManyMessages);
+        false
+    }
+
+    pub fn report(&mut self, swarm: &mut Swarm<P2PBehaviour>, peer: &PeerId, misbehaviour: Misbehaviour) {
+        metric::PEER_MISBEHAVIOUR
+            .get_or_create(&metric::MisbehaviourLabels {
+                misbehaviour: misbehaviour.as_str().to_string(),
+            })
+            .inc();
+        if self.is_banned(peer) {
+            return;
+        }
+
+        let score = self.penalize(peer, misbehaviour);
+        warn!("Peer {} misbehaved ({}), score now {}", peer, misbehaviour.as_str(), score);
+        swarm
+            .behaviour_mut()
+            .gossipsub
+            .set_application_score(peer, score as f64);
+        if score <= BAN_THRESHOLD {
+            self.ban(swarm, peer);
+        }
+    }
+
+    // Banned peers are disconnected again as soon as they connect. Returns false for them.
+    pub fn on_connection_established(&mut self, swarm: &mut Swarm<P2PBehaviour>, peer: &PeerId) -> bool {
+        if !self.is_banned(peer) {
+            return true;
+        }
+        info!("Refusing connection from banned peer {}", peer);
+        let _ = swarm.disconnect_peer_id(*peer);
+        false
+    }
+
+    // Called every REPUTATION_TICK
+    pub fn tick(&mut self, swarm: &mut Swarm<P2PBehaviour>) {
+        for peer in self.expire_bans(Instant::now()) {
+            info!("Ban of peer {} expired", peer);
+            swarm.behaviour_mut().gossipsub.remove_blacklisted_peer(&peer);
+        }
+        for (peer, score) in self.recover_scores() {
+            swarm
+                .behaviour_mut()
+                .gossipsub
+                .set_application_score(&peer, score as f64);
+        }
+    }
+
+    // Scores of the connected peers and of every peer that misbehaved lately
+    pub fn peer_scores<'a>(&self, connected_peers: impl Iterator<Item = &'a PeerId>) -> Vec<PeerScore> {
+        let now = Instant::now();
+        let mut peers: Vec<PeerId> = connected_peers
+            .copied()
+            .chain(self.peers.keys().copied())
+            .chain(self.banned.keys().copied())
+            .collect();
+        peers.sort();
+        peers.dedup();
+
+        peers
+            .into_iter()
+            .map(|peer| PeerScore {
+                peer_id: peer.to_string(),
+                score: self.score(&peer),
+                banned_for: self
+                    .banned
+                    .get(&peer)
+                    .map(|until| until.saturating_duration_since(now).as_secs()),
+            })
+            .collect()
+    }
+
+    fn ban(&mut self, swarm: &mut Swarm<P2PBehaviour>, peer: &PeerId) {
+        warn!("Banning peer {} for {:?}", peer, BAN_DURATION);
+        self.banned.insert(*peer, Instant::now() + BAN_DURATION);
+        metric::BANNED_PEERS.set(self.banned.len() as i64);
+        swarm.behaviour_mut().gossipsub.blacklist_peer(peer);
+        swarm.behaviour_mut().kademlia.remove_peer(peer);
+        let _ = swarm.disconnect_peer_id(*peer);
+    }
+
+    fn count_message(&mut self, peer: &PeerId, now: Instant) -> bool {
+        let record = self.peers.entry(*peer).or_insert_with(|| PeerRecord::new(now));
+        if now.duration_since(record.window_start) >= RATE_LIMIT_WINDOW {
+            record.window_start = now;
+            record.messages_in_window = 0;
+        }
+        record.messages_in_window += 1;
+        record.messages_in_window <= MAX_MESSAGES_PER_WINDOW
+    }
+
+    fn penalize(&mut self, peer: &PeerId, misbehaviour: Misbehaviour) -> i32 {
+        let record = self
+            .peers
+            .entry(*peer)
+            .or_insert_with(|| PeerRecord::new(Instant::now()));
+        record.score -= misbehaviour.penalty();
+        Self::set_score_metric(peer, record.score);
+        record.score
+    }
+
+    // Banned peers are forgiven once the ban expires
+    fn expire_bans(&mut self, now: Instant) -> Vec<PeerId> {
+        let expired: Vec<PeerId> = self
+            .banned
+            .iter()
+            .filter(|(_, until)| **until <= now)
+            .map(|(peer, _)| *peer)
+            .collect();
+        for peer in expired.iter() {
+            self.banned.remove(peer);
+            self.peers.remove(peer);
+            Self::set_score_metric(peer, 0);
+        }
+        metric::BANNED_PEERS.set(self.banned.len() as i64);
+        expired
+    }
+
+    // Returns the peers whose score changed. Records of peers in good standing are dropped,
+    // their message counts start over.
+    fn recover_scores(&mut self) -> Vec<(PeerId, i32)> {
+        let mut recovered = Vec::new();
+        for (peer, record) in self.peers.iter_mut() {
+            if record.score < 0 && !self.banned.contains_key(peer) {
+                record.score = (record.score + SCORE_RECOVERY).min(0);
+                Self::set_score_metric(peer, record.score);
+                recovered.push((*peer, record.score));
+            }
+        }
+        self.peers.retain(|_, record| record.score < 0);
+        recovered
+    }
+
+    fn set_score_metric(peer: &PeerId, score: i32) {
+        let labels = metric::PeerLabels {
+            peer_id: peer.to_string(),
+        };
+        if score < 0 {
+            metric::PEER_SCORE.get_or_create(&labels).set(score as i64);
+        } else {
+            metric::PEER_SCORE.remove(&labels);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_penalties_recover_and_bans_expire() {
+        let mut reputation = PeerReputation::new();
+        let peer = PeerId::random();
+
+        assert_eq!(reputation.penalize(&peer, Misbehaviour::InvalidBlock), -50);
+        assert_eq!(reputation.recover_scores(), vec![(peer, -40)]);
+        assert_eq!(reputation.penalize(&peer, Misbehaviour::InvalidSyncResponse), -90);
+        assert!(reputation.penalize(&peer, Misbehaviour::UndecodableMessage) <= BAN_THRESHOLD);
+
+        // Banned scores do not recover, the record is dropped when the ban expires
+        let now = Instant::now();
+        reputation.banned.insert(peer, now + BAN_DURATION);
+        assert!(reputation.recover_scores().is_empty());
+        assert!(reputation.expire_bans(now).is_empty());
+        assert_eq!(reputation.expire_bans(now + BAN_DURATION), vec![peer]);
+        assert!(!reputation.is_banned(&peer));
+        assert_eq!(reputation.score(&peer), 0);
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let mut reputation = PeerReputation::new();
+        let peer = PeerId::random();
+        let now = Instant::now();
+
+        for _ in 0..MAX_MESSAGES_PER_WINDOW {
+            assert!(reputation.count_message(&peer, now));
+        }
+        assert!(!reputation.count_message(&peer, now));
+        assert!(reputation.count_message(&peer, now + RATE_LIMIT_WINDOW));
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use libp2p::{Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::node::time_utils::get_current_timespan;
+
+// Least recently seen peers are forgotten beyond this
+const MAX_KNOWN_PEERS: usize = 1000;
+const MAX_ADDRESSES_PER_PEER: usize = 8;
+
+#[derive(Debug, Clone)]
+struct KnownPeer {
+    // Most recently learned first
+    addresses: Vec<Multiaddr>,
+    last_seen: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredPeer {
+    peer_id: String,
+    addresses: Vec<String>,
+    last_seen: u64,
+}
+
+// Address book of peers this node was connected to or learned about, saved as JSON so the
+// node finds the network again after a restart without relying on bootstrap nodes
+#[derive(Debug, Default)]
+pub struct PeerStore {
+    // None keeps the peers in memory only
+    path: Option<PathBuf>,
+    peers: HashMap<PeerId, KnownPeer>,
+    dirty: bool,
+}
+
+impl PeerStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Loads the peers saved at the path, which does not have to exist yet
+    pub fn open(path: &Path) -> Result<PeerStore, String> {
+        let mut peer_store = PeerStore {
+            path: Some(path.to_path_buf()),
+            ..PeerStore::default()
+        };
+        if !path.exists() {
+            return Ok(peer_store);
+        }
+
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read peer store {}: {}", path.display(), e))?;
+        let stored_peers: Vec<StoredPeer> = serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse peer store {}: {}", path.display(), e))?;
+        for stored_peer in stored_peers {
+            let peer = match stored_peer.peer_id.parse::<PeerId>() {
+                Ok(peer) => peer,
+                Err(e) => {
+                    warn!("Skipping stored peer {}: {}", stored_peer.peer_id, e);
+                    continue;
+                }
+            };
+            let addresses: Vec<Multiaddr> = stored_peer
+                .addresses
+                .iter()
+                .filter_map(|address| address.parse().ok())
+                .collect();
+            if addresses.is_empty() {
+                continue;
+            }
+            peer_store.peers.insert(
+                peer,
+                KnownPeer {
+                    addresses,
+                    last_seen: stored_peer.last_seen,
+                },
+            );
+        }
+        info!(
+            "Loaded {} known peers from {}",
+            peer_store.peers.len(),
+            path.display()
+        );
+        Ok(peer_store)
+    }
+
+    pub fn add_address(&mut self, peer: &PeerId, address: Multiaddr) {
+        let known_peer = self.peers.entry(*peer).or_insert_with(|| KnownPeer {
+            addresses: Vec::new(),
+            last_seen: 0,
+        });
+        if known_peer.addresses.first() == Some(&address) {
+            return;
+        }
+        known_peer.addresses.retain(|known| *known != address);
+        known_peer.addresses.insert(0, address);
+        known_peer.addresses.truncate(MAX_ADDRESSES_PER_PEER);
+        self.dirty = true;
+
+        while self.peers.len() > MAX_KNOWN_PEERS {
+            let oldest = self
+                .peers
+                .iter()
+                .filter(|(known, _)| *known != peer)
+                .min_by_key(|(_, known_peer)| known_peer.last_seen)
+                .map(|(peer, _)| *peer)
+                .unwrap();
+            self.peers.remove(&oldest);
+        }
+    }
+
+    pub fn mark_seen(&mut self, peer: &PeerId) {
+        if let Some(known_peer) = self.peers.get_mut(peer) {
+            known_peer.last_seen = get_current_timespan();
+            self.dirty = true;
+        }
+    }
+
+    pub fn addresses(&self, peer: &PeerId) -> Vec<Multiaddr> {
+        self.peers
+            .get(peer)
+            .map(|known_peer| known_peer.addresses.clone())
+            .unwrap_or_default()
+    }
+
+    // Most recently seen first
+    pub fn peers(&self) -> Vec<PeerId> {
+        let mut peers: Vec<(&PeerId, &KnownPeer)> = self.peers.iter().collect();
+        peers.sort_by(|a, b| b.1.last_seen.cmp(&a.1.last_seen).then(a.0.cmp(b.0)));
+        peers.into_iter().map(|(peer, _)| *peer).collect()
+    }
+
+    // Writes the peers to the file when they changed since the last save
+    pub fn save(&mut self) -> Result<(), String> {
+        let path = match &self.path {
+            Some(path) if self.dirty => path,
+            _ => return Ok(()),
+        };
+
+        let stored_peers: Vec<StoredPeer> = self
+            .peers()
+            .iter()
+            .map(|peer| {
+                let known_peer = &self.peers[peer];
+                StoredPeer {
+                    peer_id: peer.to_string(),
+                    addresses: known_peer
+                        .addresses
+                        .iter()
+                        .map(|address| address.to_string())
+                        .collect(),
+                    last_seen: known_peer.last_seen,
+                }
+            })
+            .collect();
+        let content = serde_json::to_string_pretty(&stored_peers)
+            .map_err(|e| format!("Failed to serialize peer store: {}", e))?;
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|e| {
+                format!(
+                    "Failed to create peer store directory {}: {}",
+                    dir.display(),
+                    e
+                )
+            })?;
+        }
+        fs::write(path, content)
+            .map_err(|e| format!("Failed to write peer store {}: {}", path.display(), e))?;
+
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peer_store_saved_across_restarts() {
+        let path = std::env::temp_dir().join(format!(
+            "clutch-node-peer-store-{}.json",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        let first_peer = PeerId::random();
+        let second_peer = PeerId::random();
+
+        let mut peer_store = PeerStore::open(&path).unwrap();
+        assert!(peer_store.peers().is_empty());
+        peer_store.add_address(&first_peer, "/ip4/127.0.0.1/tcp/4001".parse().unwrap());
+        peer_store.add_address(&first_peer, "/ip4/10.0.0.1/tcp/4001".parse().unwrap());
+        peer_store.add_address(&second_peer, "/ip4/127.0.0.1/tcp/4002".parse().unwrap());
+        peer_store.mark_seen(&second_peer);
+        peer_store.save().unwrap();
+
+        let peer_store = PeerStore::open(&path).unwrap();
+        assert_eq!(peer_store.peers(), vec![second_peer, first_peer]);
+        assert_eq!(
+            peer_store.addresses(&first_peer),
+            vec![
+                "/ip4/10.0.0.1/tcp/4001".parse::<Multiaddr>().unwrap(),
+                "/ip4/127.0.0.1/tcp/4001".parse::<Multiaddr>().unwrap(),
+            ]
+        );
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+use crate::node::transactions::transaction::Transaction;
+
+// Pooled transactions are fetched in two steps: a peer lists the hashes in its pool, and only
+// the transactions missing from the local pool are requested by hash
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GetPooledTransactionHashes {
+    pub limit: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PooledTransactionHashes {
+    pub hashes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GetPooledTransactions {
+    pub hashes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PooledTransactions {
+    pub transactions: Vec<Transaction>,
+}
@@ -1,5 +1,6 @@
 use super::behaviour::{DirectMessageRequest, DirectMessageResponse};
 use super::handshake::Handshake;
+use super::peer_reputation::{Misbehaviour, PeerReputation};
 use super::P2PBehaviour;
 use crate::node::blockchain::Blockchain;
 use crate::node::blocks::block_bodies::BlockBodies;
@@ -7,23 +8,36 @@ use crate::node::blocks::block_headers::{BlockHeader, BlockHeaders};
 use crate::node::p2p_server::commands::DirectMessageType;
 use crate::node::p2p_server::get_block_bodies::GetBlockBodies;
 use crate::node::p2p_server::get_block_header::GetBlockHeaders;
-use crate::node::rlp_encoding::{decode, encode};
+use crate::node::p2p_server::pooled_transactions::{
+    GetPooledTransactionHashes, GetPooledTransactions, PooledTransactionHashes, PooledTransactions,
+};
+use crate::node::p2p_server::sync::{
+    SyncManager, BODIES_PER_REQUEST, HEADERS_PER_REQUEST, MAX_POOLED_TRANSACTION_HASHES,
+    POOLED_TRANSACTIONS_PER_REQUEST,
+};
+use crate::node::p2p_server::wire::Envelope;
+use crate::node::rlp_encoding::{decode, decode_handshake, encode, encode_legacy_handshake};
 use libp2p::request_response::OutboundRequestId;
 use libp2p::{
+    gossipsub::IdentTopic,
     request_response::{Event as RequestResponseEvent, Message as RequestResponseMessage},
     swarm::Swarm,
     PeerId,
 };
 use rlp::Encodable;
-use tracing::{debug, error, warn};
+use tracing::{debug, error};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
+// Returns the peer and what it did wrong when it sent an invalid request or response
 pub async fn handle_request_response(
     event: RequestResponseEvent<DirectMessageRequest, DirectMessageResponse>,
     swarm: &mut Swarm<P2PBehaviour>,
+    topic: &IdentTopic,
     blockchain: &Arc<Mutex<Blockchain>>,
-) {
+    sync: &mut SyncManager,
+    reputation: &mut PeerReputation,
+) -> Result<(), (PeerId, Misbehaviour)> {
     match event {
         RequestResponseEvent::Message { peer, message } => match message {
             RequestResponseMessage::Request {
@@ -31,12 +45,24 @@ pub async fn handle_request_response(
                 request,
                 channel,
             } => {
-                handle_request_message(peer, request_id, request, channel, swarm, blockchain).await
+                // Requests over the rate limit are answered with an empty message
+                if !reputation.allow_message(swarm, &peer) {
+                    debug!("Rejecting request {:?} from rate limited peer {:?}", request_id, peer);
+                    send_response(Vec::new(), swarm, channel);
+                    return Ok(());
+                }
+                handle_request_message(peer, request_id, request, channel, swarm, blockchain, sync)
+                    .await
+                    .map_err(|misbehaviour| (peer, misbehaviour))?
             }
             RequestResponseMessage::Response {
                 request_id,
                 response,
-            } => handle_response_message(peer, request_id, response, swarm, blockchain).await,
+            } => {
+                handle_response_message(peer, request_id, response, swarm, topic, blockchain, sync)
+                    .await
+                    .map_err(|misbehaviour| (peer, misbehaviour))?
+            }
         },
         RequestResponseEvent::OutboundFailure {
             peer,
@@ -47,6 +73,7 @@ pub async fn handle_request_response(
                 "Failed to send request to peer {:?} with request_id {:?}: {:?}",
                 peer, request_id, outbound_failure
             );
+            sync.on_request_failed(&request_id);
         }
         RequestResponseEvent::InboundFailure {
             peer,
@@ -62,6 +89,7 @@ pub async fn handle_request_response(
             debug!("Response sent to peer {} for request {}", peer, request_id);
         }
     }
+    Ok(())
 }
 
 async fn handle_request_message(
@@ -71,33 +99,54 @@ async fn handle_request_message(
     channel: libp2p::request_response::ResponseChannel<DirectMessageResponse>,
     swarm: &mut Swarm<P2PBehaviour>,
     blockchain: &Arc<Mutex<Blockchain>>,
-) {
+    sync: &mut SyncManager,
+) -> Result<(), Misbehaviour> {
     debug!(
         "Send direct message from peer:{:?} with id {:?}",
         peer, request_id,
     );
 
-    let message_type = DirectMessageType::from_byte(request.message[0]);
-    let payload = &request.message[1..];
+    let envelope = Envelope::decode(&request.message).map_err(|e| {
+        error!("Received undecodable direct message from peer {:?}: {}", peer, e);
+        Misbehaviour::UndecodableMessage
+    })?;
+    let message_type = DirectMessageType::from_byte(envelope.message_type);
+    let payload = &envelope.payload;
+    // Answered in the version the request was written in
+    let version = envelope.version;
 
     let response_message = match message_type {
-        Some(DirectMessageType::Handshake) => handle_handshake_request(payload, blockchain).await,
+        Some(DirectMessageType::Handshake) => {
+            handle_handshake_request(payload, version, &peer, blockchain, sync).await?
+        }
         Some(DirectMessageType::GetBlockHeaders) => {
-            handle_get_block_headers_request(payload, blockchain).await
+            handle_get_block_headers_request(payload, version, blockchain).await
         }
         Some(DirectMessageType::GetBlockBodies) => {
-            handle_get_block_bodies_request(payload, blockchain).await
+            handle_get_block_bodies_request(payload, version, blockchain).await
+        }
+        Some(DirectMessageType::GetPooledTransactionHashes) => {
+            handle_get_pooled_transaction_hashes_request(payload, version, blockchain).await
+        }
+        Some(DirectMessageType::GetPooledTransactions) => {
+            handle_get_pooled_transactions_request(payload, version, blockchain).await
         }
         _ => {
             error!(
                 "Received unknown DirectMessageType from peer {:?}: {:?}",
                 peer, message_type
             );
-            return;
+            return Err(Misbehaviour::UndecodableMessage);
         }
     };
 
+    // Requests that could not be decoded are answered with an empty message
+    let undecodable = response_message.is_empty();
     send_response(response_message, swarm, channel);
+    if undecodable {
+        return Err(Misbehaviour::UndecodableMessage);
+    }
+    Ok(())
 }
 
 async fn handle_response_message(
@@ -105,36 +154,66 @@ async fn handle_response_message(
     request_id: libp2p::request_response::OutboundRequestId,
     response: DirectMessageResponse,
     swarm: &mut Swarm<P2PBehaviour>,
+    topic: &IdentTopic,
     blockchain: &Arc<Mutex<Blockchain>>,
-) {
+    sync: &mut SyncManager,
+) -> Result<(), Misbehaviour> {
     debug!(
         "Received direct message response from {:?} with request_id {:?}",
         peer_id, request_id,
     );
 
-    let message_type = DirectMessageType::from_byte(response.message[0]);
-    let payload = &response.message[1..];
+    // Requests that could not be decoded are answered with an empty message
+    if response.message.is_empty() {
+        error!("Received empty direct message response from peer {:?}", peer_id);
+        sync.on_request_failed(&request_id);
+        return Ok(());
+    }
+    let envelope = match Envelope::decode(&response.message) {
+        Ok(envelope) => envelope,
+        Err(e) => {
+            error!("Received undecodable response from peer {:?}: {}", peer_id, e);
+            sync.on_request_failed(&request_id);
+            return Err(Misbehaviour::UndecodableMessage);
+        }
+    };
+    let message_type = DirectMessageType::from_byte(envelope.message_type);
+    let payload = &envelope.payload;
 
-    match message_type {
+    let result = match message_type {
         Some(DirectMessageType::Handshake) => {
-            handle_handshake_response(payload, &peer_id, swarm, blockchain).await
+            handle_handshake_response(payload, envelope.version, &peer_id, &request_id, blockchain, sync)
+                .await
         }
         Some(DirectMessageType::BlockHeaders) => {
-            handle_block_headers_response(payload, &peer_id, swarm, blockchain).await
+            handle_block_headers_response(payload, &peer_id, &request_id, blockchain, sync).await
         }
         Some(DirectMessageType::BlockBodies) => {
-            handle_block_bodies_response(payload, &peer_id, swarm, blockchain).await
+            handle_block_bodies_response(payload, &peer_id, &request_id, sync)
+        }
+        Some(DirectMessageType::PooledTransactionHashes) => {
+            handle_pooled_transaction_hashes_response(payload, &peer_id, &request_id, blockchain, sync)
+                .await
+        }
+        Some(DirectMessageType::PooledTransactions) => {
+            handle_pooled_transactions_response(payload, &peer_id, &request_id, blockchain, sync)
+                .await
         }
         _ => {
             error!(
                 "Unknown DirectMessageType in response from peer {:?}: {:?}",
                 peer_id, message_type
             );
+            sync.on_request_failed(&request_id);
+            return Err(Misbehaviour::UndecodableMessage);
         }
-    }
+    };
+
+    sync.progress(swarm, topic, blockchain).await;
+    result
 }
 
-fn send_request(
+pub fn send_request(
     peer_id: &PeerId,
     request_message: Vec<u8>,
     swarm: &mut Swarm<P2PBehaviour>,
@@ -167,21 +246,29 @@ fn send_response(
     }
 }
 
-async fn handle_handshake_request(payload: &[u8], blockchain: &Arc<Mutex<Blockchain>>) -> Vec<u8> {
-    match decode::<Handshake>(payload) {
+async fn handle_handshake_request(
+    payload: &[u8],
+    version: u32,
+    peer_id: &PeerId,
+    blockchain: &Arc<Mutex<Blockchain>>,
+    sync: &mut SyncManager,
+) -> Result<Vec<u8>, Misbehaviour> {
+    match decode_handshake(payload, version) {
         Ok(handshake) => {
             debug!("Received and decoded handshake: {:?}", handshake);
-            handshake_response(&handshake, blockchain).await
+            sync.on_handshake(peer_id, &handshake, blockchain).await?;
+            Ok(handshake_response(&handshake, version, blockchain, sync).await)
         }
         Err(e) => {
             error!("Failed to decode handshake: {:?}", e);
-            Vec::new()
+            Ok(Vec::new())
         }
     }
 }
 
 async fn handle_get_block_headers_request(
     payload: &[u8],
+    version: u32,
     blockchain: &Arc<Mutex<Blockchain>>,
 ) -> Vec<u8> {
     match decode::<GetBlockHeaders>(payload) {
@@ -190,7 +277,7 @@ async fn handle_get_block_headers_request(
                 "Received and decoded getBlockHeader: {:?}",
                 get_block_header
             );
-            get_block_headers_response(&get_block_header, blockchain).await
+            get_block_headers_response(&get_block_header, version, blockchain).await
         }
         Err(e) => {
             error!("Failed to decode getBlockHeader: {:?}", e);
@@ -201,6 +288,7 @@ async fn handle_get_block_headers_request(
 
 async fn handle_get_block_bodies_request(
     payload: &[u8],
+    version: u32,
     blockchain: &Arc<Mutex<Blockchain>>,
 ) -> Vec<u8> {
     match decode::<GetBlockBodies>(payload) {
@@ -209,7 +297,7 @@ async fn handle_get_block_bodies_request(
                 "Received and decoded GetBlockBodies: {:?}",
                 get_block_bodies
             );
-            get_block_bodies_response(&get_block_bodies, blockchain).await
+            get_block_bodies_response(&get_block_bodies, version, blockchain).await
         }
         Err(e) => {
             error!("Failed to decode GetBlockBodies: {:?}", e);
@@ -218,35 +306,64 @@ async fn handle_get_block_bodies_request(
     }
 }
 
+async fn handle_get_pooled_transaction_hashes_request(
+    payload: &[u8],
+    version: u32,
+    blockchain: &Arc<Mutex<Blockchain>>,
+) -> Vec<u8> {
+    match decode::<GetPooledTransactionHashes>(payload) {
+        Ok(get_pooled_transaction_hashes) => {
+            debug!(
+                "Received and decoded GetPooledTransactionHashes: {:?}",
+                get_pooled_transaction_hashes
+            );
+            get_pooled_transaction_hashes_response(&get_pooled_transaction_hashes, version, blockchain)
+                .await
+        }
+        Err(e) => {
+            error!("Failed to decode GetPooledTransactionHashes: {:?}", e);
+            Vec::new()
+        }
+    }
+}
+
+async fn handle_get_pooled_transactions_request(
+    payload: &[u8],
+    version: u32,
+    blockchain: &Arc<Mutex<Blockchain>>,
+) -> Vec<u8> {
+    match decode::<GetPooledTransactions>(payload) {
+        Ok(get_pooled_transactions) => {
+            debug!(
+                "Received and decoded GetPooledTransactions: {:?}",
+                get_pooled_transactions
+            );
+            get_pooled_transactions_response(&get_pooled_transactions, version, blockchain).await
+        }
+        Err(e) => {
+            error!("Failed to decode GetPooledTransactions: {:?}", e);
+            Vec::new()
+        }
+    }
+}
+
 async fn handle_handshake_response(
     payload: &[u8],
+    version: u32,
     peer_id: &PeerId,
-    swarm: &mut Swarm<P2PBehaviour>,
+    request_id: &OutboundRequestId,
     blockchain: &Arc<Mutex<Blockchain>>,
-) {
-    match decode::<Handshake>(payload) {
+    sync: &mut SyncManager,
+) -> Result<(), Misbehaviour> {
+    sync.on_handshake_response(request_id);
+    match decode_handshake(payload, version) {
         Ok(handshake) => {
             debug!("Decoded Handshake: {:?}", handshake);
-            let blockchain = blockchain.lock().await;
-            let current_block_index = blockchain.handshake().unwrap().latest_block_index;
-            let received_block_index = handshake.latest_block_index;
-
-            if current_block_index < received_block_index {
-                warn!("this node is needed to syncing!");
-
-                let get_block_headers = GetBlockHeaders {
-                    start_block_index: current_block_index,
-                    skip: 1,
-                    limit: 100,
-                };
-
-                let encoded_headers =
-                    encode_message(DirectMessageType::GetBlockHeaders, &get_block_headers);
-                send_request(peer_id, encoded_headers, swarm);
-            }
+            sync.on_handshake(peer_id, &handshake, blockchain).await
         }
         Err(e) => {
             error!("Failed to decode Handshake: {:?}", e);
+            Err(Misbehaviour::UndecodableMessage)
         }
     }
 }
@@ -254,68 +371,106 @@ async fn handle_handshake_response(
 async fn handle_block_headers_response(
     payload: &[u8],
     peer_id: &PeerId,
-    swarm: &mut Swarm<P2PBehaviour>,
-    _blockchain: &Arc<Mutex<Blockchain>>,
-) {
+    request_id: &OutboundRequestId,
+    blockchain: &Arc<Mutex<Blockchain>>,
+    sync: &mut SyncManager,
+) -> Result<(), Misbehaviour> {
     match decode::<BlockHeaders>(payload) {
         Ok(block_headers) => {
             debug!("Decoded BlockHeaders: {:?}", block_headers);
-
-            let block_indexes = block_headers.to_block_indexes();
-            let get_block_bodies = GetBlockBodies { block_indexes };
-
-            let encoded_bodies =
-                encode_message(DirectMessageType::GetBlockBodies, &get_block_bodies);
-            send_request(peer_id, encoded_bodies, swarm);
+            sync.on_block_headers(request_id, peer_id, block_headers.block_headers, blockchain)
+                .await
         }
         Err(e) => {
             error!("Failed to decode BlockHeaders: {:?}", e);
+            sync.on_request_failed(request_id);
+            Err(Misbehaviour::UndecodableMessage)
         }
     }
 }
 
-async fn handle_block_bodies_response(
+fn handle_block_bodies_response(
     payload: &[u8],
-    _peer_id: &PeerId,
-    _swarm: &mut Swarm<P2PBehaviour>,
-    blockchain: &Arc<Mutex<Blockchain>>,
-) {
+    peer_id: &PeerId,
+    request_id: &OutboundRequestId,
+    sync: &mut SyncManager,
+) -> Result<(), Misbehaviour> {
     match decode::<BlockBodies>(payload) {
         Ok(block_bodies) => {
             debug!("Decoded BlockBodies: {:?}", block_bodies);
+            sync.on_block_bodies(request_id, peer_id, block_bodies.blocks)
+        }
+        Err(e) => {
+            error!("Failed to decode BlockBodies: {:?}", e);
+            sync.on_request_failed(request_id);
+            Err(Misbehaviour::UndecodableMessage)
+        }
+    }
+}
 
-            let blockchain = blockchain.lock().await;
+async fn handle_pooled_transaction_hashes_response(
+    payload: &[u8],
+    peer_id: &PeerId,
+    request_id: &OutboundRequestId,
+    blockchain: &Arc<Mutex<Blockchain>>,
+    sync: &mut SyncManager,
+) -> Result<(), Misbehaviour> {
+    match decode::<PooledTransactionHashes>(payload) {
+        Ok(pooled_transaction_hashes) => {
+            debug!("Decoded PooledTransactionHashes: {:?}", pooled_transaction_hashes);
+            sync.on_pooled_transaction_hashes(
+                request_id,
+                peer_id,
+                pooled_transaction_hashes.hashes,
+                blockchain,
+            )
+            .await
+        }
+        Err(e) => {
+            error!("Failed to decode PooledTransactionHashes: {:?}", e);
+            sync.on_request_failed(request_id);
+            Err(Misbehaviour::UndecodableMessage)
+        }
+    }
+}
 
-            for block in block_bodies.blocks {
-                match blockchain.import_block(&block) {
-                    Ok(_) => {
-                        debug!("Successfully imported block with index: {}", block.index);
-                    }
-                    Err(e) => {
-                        error!("Failed to import block with index {}: {:?}", block.index, e);
-                    }
-                }
-            }
+async fn handle_pooled_transactions_response(
+    payload: &[u8],
+    peer_id: &PeerId,
+    request_id: &OutboundRequestId,
+    blockchain: &Arc<Mutex<Blockchain>>,
+    sync: &mut SyncManager,
+) -> Result<(), Misbehaviour> {
+    match decode::<PooledTransactions>(payload) {
+        Ok(pooled_transactions) => {
+            debug!("Decoded PooledTransactions: {:?}", pooled_transactions);
+            sync.on_pooled_transactions(request_id, peer_id, pooled_transactions.transactions, blockchain)
+                .await
         }
         Err(e) => {
-            error!("Failed to decode BlockBodies: {:?}", e);
+            error!("Failed to decode PooledTransactions: {:?}", e);
+            sync.on_request_failed(request_id);
+            Err(Misbehaviour::UndecodableMessage)
         }
     }
 }
 
 async fn handshake_response(
-    _handshake: &Handshake,
+    handshake: &Handshake,
+    version: u32,
     blockchain: &Arc<Mutex<Blockchain>>,
+    sync: &SyncManager,
 ) -> Vec<u8> {
     let blockchain = blockchain.lock().await;
-    let response_handshake = blockchain
-        .handshake()
+    let response_handshake = sync
+        .local_handshake(&blockchain)
         .expect("error get handshake response");
-    encode_message(DirectMessageType::Handshake, &response_handshake)
+    encode_handshake(version, &response_handshake, handshake.legacy)
 }
 
 async fn get_block_headers_response(
     get_block_header: &GetBlockHeaders,
+    version: u32,
     blockchain: &Arc<Mutex<Blockchain>>,
 ) -> Vec<u8> {
     let blockchain = blockchain.lock().await;
@@ -323,37 +478,103 @@ async fn get_block_headers_response(
         .get_blocks_with_limit_and_skip(
             get_block_header.start_block_index,
             get_block_header.skip,
-            get_block_header.limit,
+            get_block_header.limit.min(HEADERS_PER_REQUEST),
         )
-        .expect("Failed to get blocks");
+        .unwrap_or_else(|e| {
+            error!("Failed to get blocks for headers: {}", e);
+            Vec::new()
+        });
 
     let block_headers: Vec<BlockHeader> =
         blocks.iter().map(|block| block.to_block_header()).collect();
 
     let response_block_headers = BlockHeaders { block_headers };
-    encode_message(DirectMessageType::BlockHeaders, &response_block_headers)
+    encode_message(version, DirectMessageType::BlockHeaders, &response_block_headers)
 }
 
 async fn get_block_bodies_response(
     get_block_bodies: &GetBlockBodies,
+    version: u32,
     blockchain: &Arc<Mutex<Blockchain>>,
 ) -> Vec<u8> {
+    // Only the blocks this node has, which the requester asks elsewhere
     let blockchain = blockchain.lock().await;
+    let latest_index = blockchain
+        .get_latest_block()
+        .map(|block| block.index)
+        .unwrap_or(0);
+    let block_indexes: Vec<usize> = get_block_bodies
+        .block_indexes
+        .iter()
+        .filter(|index| **index <= latest_index)
+        .take(BODIES_PER_REQUEST)
+        .copied()
+        .collect();
     let blocks = blockchain
-        .get_blocks_by_indexes(get_block_bodies.block_indexes.clone())
-        .expect("Failed to get blocks");
+        .get_blocks_by_indexes(block_indexes)
+        .unwrap_or_else(|e| {
+            error!("Failed to get blocks for bodies: {}", e);
+            Vec::new()
+        });
 
     let response_block_bodies = BlockBodies { blocks };
-    encode_message(DirectMessageType::BlockBodies, &response_block_bodies)
+    encode_message(version, DirectMessageType::BlockBodies, &response_block_bodies)
+}
+
+async fn get_pooled_transaction_hashes_response(
+    get_pooled_transaction_hashes: &GetPooledTransactionHashes,
+    version: u32,
+    blockchain: &Arc<Mutex<Blockchain>>,
+) -> Vec<u8> {
+    let hashes = blockchain.lock().await.get_pooled_transaction_hashes(
+        get_pooled_transaction_hashes
+            .limit
+            .min(MAX_POOLED_TRANSACTION_HASHES),
+    );
+
+    let response_pooled_transaction_hashes = PooledTransactionHashes { hashes };
+    encode_message(
+        version,
+        DirectMessageType::PooledTransactionHashes,
+        &response_pooled_transaction_hashes,
+    )
+}
+
+async fn get_pooled_transactions_response(
+    get_pooled_transactions: &GetPooledTransactions,
+    version: u32,
+    blockchain: &Arc<Mutex<Blockchain>>,
+) -> Vec<u8> {
+    // Transactions no longer in the pool are left out
+    let hashes: Vec<String> = get_pooled_transactions
+        .hashes
+        .iter()
+        .take(POOLED_TRANSACTIONS_PER_REQUEST)
+        .cloned()
+        .collect();
+    let transactions = blockchain.lock().await.get_pooled_transactions(&hashes);
+
+    let response_pooled_transactions = PooledTransactions { transactions };
+    encode_message(
+        version,
+        DirectMessageType::PooledTransactions,
+        &response_pooled_transactions,
+    )
+}
+
+// Peers that handshake in the layout from before chain ids are answered in it
+pub fn encode_handshake(version: u32, handshake: &Handshake, legacy: bool) -> Vec<u8> {
+    if !legacy {
+        return encode_message(version, DirectMessageType::Handshake, handshake);
+    }
+    Envelope::new(version, DirectMessageType::Handshake.as_byte(), encode_legacy_handshake(handshake))
+        .encode()
 }
 
-fn encode_message<T: serde::Serialize + Encodable>(
+pub fn encode_message<T: serde::Serialize + Encodable>(
+    version: u32,
     message_type: DirectMessageType,
     message: &T,
 ) -> Vec<u8> {
-    let encoded_message = encode(message);
-    let mut message_with_type = Vec::with_capacity(1 + encoded_message.len());
-    message_with_type.push(message_type.as_byte());
-    message_with_type.extend(encoded_message);
-    message_with_type
+    Envelope::new(version, message_type.as_byte(), encode(message)).encode()
 }
@@ -1,8 +1,14 @@
 use crate::node::blockchain::Blockchain;
+use crate::node::blocks::block::Block;
+use crate::node::rlp_encoding::encode;
+use crate::node::finality::Precommit;
 use futures::stream::StreamExt;
 use futures::FutureExt;
 use libp2p::{
-    gossipsub::{self, Event as GossipsubEvent, IdentTopic, MessageId},
+    gossipsub::{self, Event as GossipsubEvent, IdentTopic, MessageAcceptance, MessageId},
+    identify::{self, Event as IdentifyEvent},
+    identity::Keypair,
+    kad::{self, store::MemoryStore, Event as KademliaEvent},
     mdns::{self, Event as MdnsEvent},
     noise,
     request_response::{
@@ -10,7 +16,7 @@ use libp2p::{
         OutboundRequestId, ProtocolSupport as RequestResponseProtocolSupport,
     },
     swarm::{Swarm, SwarmEvent},
-    tcp, yamux, Multiaddr, PeerId, StreamProtocol,
+    tcp, yamux, Multiaddr, PeerId,
 };
 use tracing::{info,error};
 
@@ -18,6 +24,7 @@ use std::collections::hash_map::DefaultHasher;
 use std::collections::HashSet;
 use std::error::Error as StdError;
 use std::hash::{Hash, Hasher};
+use std::num::NonZeroU8;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::mpsc::Sender;
@@ -29,23 +36,47 @@ use tokio::{
 use super::{
     behaviour::{DirectMessageRequest, DirectMessageResponse, P2PBehaviourEvent},
     commands::DirectMessageType,
+    discovery::{Discovery, DISCOVERY_TICK, IDENTIFY_PROTOCOL, KADEMLIA_PROTOCOL},
     gossipsub_handler::handle_gossipsub_message,
+    handshake::MIN_PROTOCOL_VERSION,
+    peer_reputation::{PeerReputation, PeerScore, REPUTATION_TICK},
     request_response_handler::handle_request_response,
+    sync::{AuthorityPeer, SyncManager, SYNC_TICK},
+    wire::{Envelope, GOSSIP_VERSION, LEGACY_SYNC_PROTOCOL, SYNC_PROTOCOL},
     GossipMessageType, P2PBehaviour, P2PServerCommand,
 };
 
+// Addresses of a peer dialed in parallel
+const DIAL_CONCURRENCY: NonZeroU8 = match NonZeroU8::new(4) {
+    Some(factor) => factor,
+    None => unreachable!(),
+};
+
 pub struct P2PServer {
     pub behaviour: Swarm<P2PBehaviour>,
     pub topic: IdentTopic,
+    pub sync: SyncManager,
+    pub reputation: PeerReputation,
+    pub discovery: Discovery,
 }
 
 impl P2PServer {
+    // Starts with a new identity, so the peer id changes with every start
     pub fn new(
         topic_name: &str,
         listen_addrs: &[&str],
         peer_addrs: &[&str],
     ) -> Result<Self, Box<dyn StdError>> {
-        let mut swarm = Self::build_swarm(listen_addrs)?;
+        Self::with_node_key(Keypair::generate_ed25519(), topic_name, listen_addrs, peer_addrs)
+    }
+
+    pub fn with_node_key(
+        node_key: Keypair,
+        topic_name: &str,
+        listen_addrs: &[&str],
+        peer_addrs: &[&str],
+    ) -> Result<Self, Box<dyn StdError>> {
+        let mut swarm = Self::build_swarm(node_key, listen_addrs)?;
         let topic = Self::setup_gossipsub_topic(&mut swarm, topic_name)?;
 
         for peer in peer_addrs {
@@ -56,16 +87,19 @@ impl P2PServer {
         Ok(Self {
             behaviour: swarm,
             topic,
+            sync: SyncManager::new(),
+            reputation: PeerReputation::new(),
+            discovery: Discovery::new(),
         })
     }
 
     pub async fn gossip_message_command(
         command_tx_p2p: Sender<P2PServerCommand>,
         message_type: GossipMessageType,
-        message: &Vec<u8>,
+        message: &[u8],
     ) {
-        let mut message_with_type = vec![message_type.as_byte()];
-        message_with_type.extend(message);
+        let message_with_type =
+            Envelope::new(GOSSIP_VERSION, message_type.as_byte(), message.to_vec()).encode();
 
         let (response_tx, response_rx) = oneshot::channel();
         command_tx_p2p
@@ -85,17 +119,40 @@ impl P2PServer {
         }
     }
 
+    // Gossips the precommit Blockchain::precommit_block made for a block this node imported or
+    // authored. It is made while the blockchain is locked and sent after, since the P2P loop
+    // locks the blockchain too and would wait on the guard while the command waits on it.
+    pub async fn gossip_precommit_command(
+        command_tx_p2p: Sender<P2PServerCommand>,
+        block: &Block,
+        precommit: Result<Option<Precommit>, String>,
+    ) {
+        match precommit {
+            Ok(Some(precommit)) => {
+                Self::gossip_message_command(
+                    command_tx_p2p,
+                    GossipMessageType::Precommit,
+                    &encode(&precommit),
+                )
+                .await;
+            }
+            Ok(None) => {}
+            Err(e) => error!("Failed to precommit block {}: {}", block.index, e),
+        }
+    }
+
     #[allow(dead_code)]
     pub async fn send_direct_message_command(
         command_tx_p2p: Sender<P2PServerCommand>,
         peer_id: PeerId,
         message_type: DirectMessageType,
-        message: &Vec<u8>,
+        message: &[u8],
     ) -> Result<OutboundRequestId, Box<dyn StdError>> {
         let (response_tx, response_rx) = oneshot::channel();
 
-        let mut message_with_type = vec![message_type.as_byte()];
-        message_with_type.extend(message);
+        // Every peer reads the oldest supported version
+        let message_with_type =
+            Envelope::new(MIN_PROTOCOL_VERSION, message_type.as_byte(), message.to_vec()).encode();
 
         let direct_message = DirectMessageRequest {
             message: message_with_type,
@@ -141,6 +198,32 @@ impl P2PServer {
         Ok(peers)
     }
 
+    // Scores of connected and recently misbehaving peers
+    pub async fn get_peer_scores_command(
+        command_tx_p2p: Sender<P2PServerCommand>,
+    ) -> Result<Vec<PeerScore>, Box<dyn StdError>> {
+        let (response_tx, response_rx) = oneshot::channel();
+        command_tx_p2p
+            .send(P2PServerCommand::GetPeerScores { response_tx })
+            .await?;
+
+        let peer_scores = response_rx.await?;
+        Ok(peer_scores)
+    }
+
+    // Connected peers that proved to be run by a current authority
+    pub async fn get_authority_peers_command(
+        command_tx_p2p: Sender<P2PServerCommand>,
+    ) -> Result<Vec<AuthorityPeer>, Box<dyn StdError>> {
+        let (response_tx, response_rx) = oneshot::channel();
+        command_tx_p2p
+            .send(P2PServerCommand::GetAuthorityPeers { response_tx })
+            .await?;
+
+        let authority_peers = response_rx.await?;
+        Ok(authority_peers)
+    }
+
     pub async fn run(
         &mut self,
         blockchain: Arc<Mutex<Blockchain>>,
@@ -149,8 +232,11 @@ impl P2PServer {
         self.process_messages(blockchain, &mut command_rx).await
     }
 
-    fn build_swarm(listen_addrs: &[&str]) -> Result<Swarm<P2PBehaviour>, Box<dyn StdError>> {
-        let mut swarm = libp2p::SwarmBuilder::with_new_identity()
+    fn build_swarm(
+        node_key: Keypair,
+        listen_addrs: &[&str],
+    ) -> Result<Swarm<P2PBehaviour>, Box<dyn StdError>> {
+        let mut swarm = libp2p::SwarmBuilder::with_existing_identity(node_key)
             .with_tokio()
             .with_tcp(
                 tcp::Config::default(),
@@ -165,28 +251,50 @@ impl P2PServer {
                     gossipsub::MessageId::from(s.finish().to_string())
                 };
 
+                // Messages are relayed only after the node has checked them
                 let gossipsub_config = gossipsub::ConfigBuilder::default()
                     .heartbeat_interval(Duration::from_secs(10))
                     .validation_mode(gossipsub::ValidationMode::Strict)
+                    .validate_messages()
                     .message_id_fn(message_id_fn)
                     .build()
                     .map_err(|msg| io::Error::new(io::ErrorKind::Other, msg))?;
 
-                let gossipsub = gossipsub::Behaviour::new(
+                let mut gossipsub = gossipsub::Behaviour::new(
                     gossipsub::MessageAuthenticity::Signed(key.clone()),
                     gossipsub_config,
                 )?;
+                let (score_params, score_thresholds) = PeerReputation::gossipsub_score_params();
+                gossipsub
+                    .with_peer_score(score_params, score_thresholds)
+                    .map_err(io::Error::other)?;
 
                 let mdns = mdns::tokio::Behaviour::new(
                     mdns::Config::default(),
                     key.public().to_peer_id(),
                 )?;
 
+                // Server mode, so peers can query this node before it learned its external address
+                let local_peer_id = key.public().to_peer_id();
+                let mut kademlia = kad::Behaviour::with_config(
+                    local_peer_id,
+                    MemoryStore::new(local_peer_id),
+                    kad::Config::new(KADEMLIA_PROTOCOL),
+                );
+                kademlia.set_mode(Some(kad::Mode::Server));
+
+                let identify = identify::Behaviour::new(identify::Config::new(
+                    IDENTIFY_PROTOCOL.to_string(),
+                    key.public(),
+                ));
+
                 let rr_config = RequestResponseConfig::default();
-                let rr_protocol = StreamProtocol::new("/agent/message/1.0.0");
                 let rr_behavior =
                     RequestResponseBehavior::<DirectMessageRequest, DirectMessageResponse>::new(
-                        [(rr_protocol, RequestResponseProtocolSupport::Full)],
+                        [
+                            (SYNC_PROTOCOL, RequestResponseProtocolSupport::Full),
+                            (LEGACY_SYNC_PROTOCOL, RequestResponseProtocolSupport::Full),
+                        ],
                         rr_config,
                     );
 
@@ -194,9 +302,15 @@ impl P2PServer {
                     gossipsub,
                     mdns,
                     request_response: rr_behavior,
+                    kademlia,
+                    identify,
                 })
             })?
-            .with_swarm_config(|c| c.with_idle_connection_timeout(Duration::from_secs(60)))
+            // Known peers often have stale addresses, so several are tried at once
+            .with_swarm_config(|c| {
+                c.with_idle_connection_timeout(Duration::from_secs(60))
+                    .with_dial_concurrency_factor(DIAL_CONCURRENCY)
+            })
             .build();
 
         for addr in listen_addrs {
@@ -220,10 +334,23 @@ impl P2PServer {
         blockchain: Arc<Mutex<Blockchain>>,
         command_rx: &mut tokio::sync::mpsc::Receiver<P2PServerCommand>,
     ) -> Result<(), Box<dyn StdError>> {
+        let mut sync_tick = tokio::time::interval(SYNC_TICK);
+        let mut reputation_tick = tokio::time::interval(REPUTATION_TICK);
+        let mut discovery_tick = tokio::time::interval(DISCOVERY_TICK);
+        self.discovery.start(&mut self.behaviour);
         loop {
             select! {
                 event = self.behaviour.select_next_some().fuse() => {
-                    Self::handle_swarm_event(event, &mut self.behaviour, &blockchain).await;
+                    Self::handle_swarm_event(event, &mut self.behaviour, &self.topic, &blockchain, &mut self.sync, &mut self.reputation, &mut self.discovery).await;
+                },
+                _ = sync_tick.tick() => {
+                    self.sync.tick(&mut self.behaviour, &self.topic, &blockchain).await;
+                },
+                _ = reputation_tick.tick() => {
+                    self.reputation.tick(&mut self.behaviour);
+                },
+                _ = discovery_tick.tick() => {
+                    self.discovery.tick(&mut self.behaviour, &self.reputation);
                 },
                 command = command_rx.recv() => {
                     if let Some(command) = command {
@@ -244,6 +371,13 @@ impl P2PServer {
                                 let peer_id = self.get_local_peer_id();
                                 let _ = response_tx.send(peer_id);
                             },
+                            P2PServerCommand::GetPeerScores { response_tx } => {
+                                let peer_scores = self.reputation.peer_scores(self.behaviour.connected_peers());
+                                let _ = response_tx.send(peer_scores);
+                            },
+                            P2PServerCommand::GetAuthorityPeers { response_tx } => {
+                                let _ = response_tx.send(self.sync.authority_peers());
+                            },
                         }
                     }
                 },
@@ -283,11 +417,29 @@ impl P2PServer {
     async fn handle_swarm_event(
         event: SwarmEvent<P2PBehaviourEvent>,
         swarm: &mut Swarm<P2PBehaviour>,
+        topic: &IdentTopic,
         blockchain: &Arc<Mutex<Blockchain>>,
+        sync: &mut SyncManager,
+        reputation: &mut PeerReputation,
+        discovery: &mut Discovery,
     ) {
         match event {
             SwarmEvent::Behaviour(P2PBehaviourEvent::Mdns(MdnsEvent::Discovered(list))) => {
-                Self::handle_mdns_discovered(swarm, list);
+                Self::handle_mdns_discovered(swarm, list, reputation, discovery);
+            }
+            SwarmEvent::Behaviour(P2PBehaviourEvent::Identify(IdentifyEvent::Received {
+                peer_id,
+                info,
+                ..
+            })) => {
+                discovery.on_identify(swarm, &peer_id, info);
+            }
+            SwarmEvent::Behaviour(P2PBehaviourEvent::Kademlia(KademliaEvent::RoutingUpdated {
+                peer,
+                addresses,
+                ..
+            })) => {
+                discovery.on_routing_updated(&peer, addresses.into_vec());
             }
             SwarmEvent::Behaviour(P2PBehaviourEvent::Mdns(MdnsEvent::Expired(list))) => {
                 Self::handle_mdns_expired(swarm, list);
@@ -297,10 +449,49 @@ impl P2PServer {
                 message_id: id,
                 message,
             })) => {
-                handle_gossipsub_message(peer_id, id, message, blockchain).await;
+                let acceptance = if !reputation.allow_message(swarm, &peer_id) {
+                    MessageAcceptance::Ignore
+                } else {
+                    match handle_gossipsub_message(peer_id, id.clone(), message, swarm, topic, blockchain, sync).await {
+                        Ok(_) => MessageAcceptance::Accept,
+                        Err(misbehaviour) => {
+                            reputation.report(swarm, &peer_id, misbehaviour);
+                            MessageAcceptance::Reject
+                        }
+                    }
+                };
+                if let Err(e) = swarm
+                    .behaviour_mut()
+                    .gossipsub
+                    .report_message_validation_result(&id, &peer_id, acceptance)
+                {
+                    error!("Failed to report validation of message {}: {:?}", id, e);
+                }
             }
             SwarmEvent::Behaviour(P2PBehaviourEvent::RequestResponse(event)) => {
-                handle_request_response(event, swarm, blockchain).await;
+                if let Err((peer, misbehaviour)) =
+                    handle_request_response(event, swarm, topic, blockchain, sync, reputation).await
+                {
+                    reputation.report(swarm, &peer, misbehaviour);
+                }
+            }
+            SwarmEvent::ConnectionEstablished {
+                peer_id, endpoint, ..
+            } => {
+                // Banned peers are disconnected instead
+                if !reputation.on_connection_established(swarm, &peer_id) {
+                    return;
+                }
+                discovery.on_connection_established(&peer_id, &endpoint);
+                sync.add_peer(peer_id);
+            }
+            SwarmEvent::ConnectionClosed {
+                peer_id,
+                num_established: 0,
+                ..
+            } => {
+                discovery.on_connection_closed(&peer_id);
+                sync.remove_peer(&peer_id);
             }
             SwarmEvent::NewListenAddr { address, .. } => {
                 info!("Local node is listening on {address}");
@@ -309,9 +500,18 @@ impl P2PServer {
         }
     }
 
-    fn handle_mdns_discovered(swarm: &mut Swarm<P2PBehaviour>, list: Vec<(PeerId, Multiaddr)>) {
-        for (peer_id, _multiaddr) in list {
+    fn handle_mdns_discovered(
+        swarm: &mut Swarm<P2PBehaviour>,
+        list: Vec<(PeerId, Multiaddr)>,
+        reputation: &PeerReputation,
+        discovery: &mut Discovery,
+    ) {
+        for (peer_id, multiaddr) in list {
+            if reputation.is_banned(&peer_id) {
+                continue;
+            }
             info!("mDNS discovered a new peer: {peer_id}");
+            discovery.on_mdns_discovered(&peer_id, multiaddr);
             swarm.behaviour_mut().gossipsub.add_explicit_peer(&peer_id);
         }
     }
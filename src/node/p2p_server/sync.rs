@@ -0,0 +1,1027 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use libp2p::{gossipsub::IdentTopic, request_response::OutboundRequestId, swarm::Swarm, PeerId};
+use serde::Serialize;
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+use super::commands::DirectMessageType;
+use super::get_block_bodies::GetBlockBodies;
+use super::get_block_header::GetBlockHeaders;
+use super::gossipsub_handler::{handle_received_equivocation, publish};
+use super::handshake::{
+    Handshake, CAPABILITY_BLOCK_BODIES, CAPABILITY_BLOCK_HEADERS, CAPABILITY_POOLED_TRANSACTIONS,
+};
+use super::node_key::NodeIdentity;
+use super::peer_reputation::Misbehaviour;
+use super::pooled_transactions::{GetPooledTransactionHashes, GetPooledTransactions};
+use super::request_response_handler::{encode_handshake, encode_message, send_request};
+use super::wire::negotiate_version;
+use super::{GossipMessageType, P2PBehaviour};
+use crate::node::blockchain::Blockchain;
+use crate::node::blocks::block::Block;
+use crate::node::blocks::block_headers::BlockHeader;
+use crate::node::rlp_encoding::encode;
+use crate::node::transactions::transaction::Transaction;
+
+// How often the sync manager checks its peers and requests
+pub const SYNC_TICK: Duration = Duration::from_secs(1);
+// Blocks asked for per GetBlockHeaders and GetBlockBodies request
+pub const HEADERS_PER_REQUEST: usize = 64;
+pub const BODIES_PER_REQUEST: usize = 16;
+// Pooled transaction hashes asked from a peer when it connects, and transactions fetched per
+// GetPooledTransactions request
+pub const MAX_POOLED_TRANSACTION_HASHES: usize = 4096;
+pub const POOLED_TRANSACTIONS_PER_REQUEST: usize = 256;
+// Requests in flight to one peer, so ranges are spread over all peers
+const MAX_REQUESTS_PER_PEER: usize = 2;
+// A request without a response by then is dropped and its range asked from another peer
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// How often peers are asked for their best block again
+const HANDSHAKE_INTERVAL: Duration = Duration::from_secs(10);
+// Gossiped blocks kept while the blocks below them are fetched
+const MAX_GOSSIPED_BLOCKS: usize = 128;
+
+#[derive(Debug, Default)]
+struct PeerStatus {
+    // Unknown until the peer answered a handshake
+    best_index: Option<usize>,
+    last_handshake: Option<Instant>,
+    // Authority that signed the peer's node identity
+    authority: Option<String>,
+    // Announced in the peer's handshake
+    protocol_version: Option<u32>,
+    capabilities: Vec<String>,
+    // Handshakes in the layout from before chain ids
+    legacy: bool,
+    // Asked once per connection for the transactions in its pool
+    pool_requested: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AuthorityPeer {
+    pub authority: String,
+    pub peer_id: String,
+}
+
+#[derive(Debug)]
+enum SyncRequest {
+    Handshake,
+    Headers { start: usize, limit: usize },
+    Bodies { indexes: Vec<usize> },
+    PooledTransactionHashes,
+    PooledTransactions { hashes: Vec<String> },
+}
+
+#[derive(Debug)]
+struct PendingRequest {
+    peer: PeerId,
+    request: SyncRequest,
+    sent_at: Instant,
+}
+
+// Header-first sync: peers report their best block in handshakes, headers above the local
+// head are downloaded in ranges from several peers and verified as a chain, and bodies for
+// verified headers are fetched in parallel and imported in order. Requests that fail or time
+// out are asked again from another peer, and the manager keeps going until no peer is ahead.
+// Responses that fail verification are returned as misbehaviour of the peer that sent them.
+// The transactions in a peer's pool are fetched once it connects: it lists their hashes and
+// the ones missing locally are requested in batches.
+pub struct SyncManager {
+    enabled: bool,
+    // Sent along with this node's handshakes
+    node_identity: Option<NodeIdentity>,
+    peers: HashMap<PeerId, PeerStatus>,
+    pending: HashMap<OutboundRequestId, PendingRequest>,
+    // Header ranges by start index, each a verified chain, not yet linked to the local chain
+    unlinked_headers: BTreeMap<usize, Vec<BlockHeader>>,
+    // Headers linked to the local chain whose blocks are not imported yet
+    headers: BTreeMap<usize, BlockHeader>,
+    // Downloaded blocks matching their header, waiting for their parent to be imported
+    blocks: BTreeMap<usize, Block>,
+    // Blocks gossiped ahead of the local head, applied once the gap below them is synced
+    gossiped_blocks: BTreeMap<usize, Block>,
+    // Transaction hashes peers announced from their pools that are missing locally, by peer
+    announced_transactions: HashMap<PeerId, Vec<String>>,
+}
+
+impl Default for SyncManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SyncManager {
+    pub fn new() -> Self {
+        SyncManager {
+            enabled: true,
+            node_identity: None,
+            peers: HashMap::new(),
+            pending: HashMap::new(),
+            unlinked_headers: BTreeMap::new(),
+            headers: BTreeMap::new(),
+            blocks: BTreeMap::new(),
+            gossiped_blocks: BTreeMap::new(),
+            announced_transactions: HashMap::new(),
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn set_node_identity(&mut self, node_identity: NodeIdentity) {
+        self.node_identity = Some(node_identity);
+    }
+
+    // The handshake this node sends and answers with
+    pub fn local_handshake(&self, blockchain: &Blockchain) -> Result<Handshake, String> {
+        let mut handshake = blockchain.handshake()?;
+        handshake.node_identity = self.node_identity.clone();
+        Ok(handshake)
+    }
+
+    // Connected peers whose node identity is signed by a current authority
+    pub fn authority_peers(&self) -> Vec<AuthorityPeer> {
+        let mut authority_peers: Vec<AuthorityPeer> = self
+            .peers
+            .iter()
+            .filter_map(|(peer, status)| {
+                status.authority.as_ref().map(|authority| AuthorityPeer {
+                    authority: authority.clone(),
+                    peer_id: peer.to_string(),
+                })
+            })
+            .collect();
+        authority_peers.sort_by(|a, b| a.authority.cmp(&b.authority));
+        authority_peers
+    }
+
+    // Highest block any peer reported
+    pub fn target_index(&self) -> Option<usize> {
+        self.peers
+            .values()
+            .filter_map(|status| status.best_index)
+            .max()
+    }
+
+    pub fn add_peer(&mut self, peer: PeerId) {
+        self.peers.entry(peer).or_default();
+    }
+
+    // Requests to the peer are dropped, so their ranges are asked from other peers
+    pub fn remove_peer(&mut self, peer: &PeerId) {
+        self.peers.remove(peer);
+        self.pending.retain(|_, pending| pending.peer != *peer);
+        self.announced_transactions.remove(peer);
+    }
+
+    // Keeps a gossiped block that is ahead of the local head and marks the peer it came from as
+    // having the blocks below it, so the gap is requested from it. Returns false when sync is
+    // disabled or the block is not signed by its author.
+    pub fn buffer_gossiped_block(&mut self, peer: &PeerId, block: &Block) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        if let Err(e) = block.to_block_header().verify_signature() {
+            warn!("Not buffering gossiped block {}: {}", block.index, e);
+            return false;
+        }
+
+        self.gossiped_blocks.insert(block.index, block.clone());
+        while self.gossiped_blocks.len() > MAX_GOSSIPED_BLOCKS {
+            self.gossiped_blocks.pop_last();
+        }
+
+        let status = self.peers.entry(*peer).or_default();
+        let gap_end = block.index - 1;
+        if status.best_index.is_none_or(|best| best < gap_end) {
+            status.best_index = Some(gap_end);
+        }
+        true
+    }
+
+    pub fn on_request_failed(&mut self, request_id: &OutboundRequestId) {
+        if let Some(pending) = self.pending.remove(request_id) {
+            debug!(
+                "Sync request {:?} to {} failed",
+                pending.request, pending.peer
+            );
+        }
+    }
+
+    // Handshakes are exchanged both ways, so a handshake request from a peer also reports its
+    // best block and capabilities. Peers of another chain or protocol version and node
+    // identities not signed for the peer are returned as misbehaviour, which disconnects them.
+    pub async fn on_handshake(
+        &mut self,
+        peer: &PeerId,
+        handshake: &Handshake,
+        blockchain: &Arc<Mutex<Blockchain>>,
+    ) -> Result<(), Misbehaviour> {
+        let (local_handshake, authorities) = {
+            let blockchain = blockchain.lock().await;
+            (
+                self.local_handshake(&blockchain),
+                blockchain.get_authorities().unwrap_or_default(),
+            )
+        };
+        let local_handshake = match local_handshake {
+            Ok(local_handshake) => local_handshake,
+            Err(e) => {
+                warn!("Failed to build handshake: {}", e);
+                return Ok(());
+            }
+        };
+        if let Err(e) = handshake.check_compatible(&local_handshake) {
+            warn!("Peer {} is incompatible: {}", peer, e);
+            self.remove_peer(peer);
+            return Err(Misbehaviour::IncompatibleHandshake);
+        }
+
+        let mut authority = None;
+        if let Some(node_identity) = &handshake.node_identity {
+            if let Err(e) = node_identity.verify(peer) {
+                warn!("{}", e);
+                return Err(Misbehaviour::InvalidNodeIdentity);
+            }
+            if authorities.contains(&node_identity.authority) {
+                authority = Some(node_identity.authority.clone());
+            } else {
+                debug!(
+                    "Peer {} is bound to {}, which is not an authority",
+                    peer, node_identity.authority
+                );
+            }
+        }
+
+        let status = self.peers.entry(*peer).or_default();
+        status.best_index = Some(handshake.latest_block_index);
+        status.protocol_version = Some(handshake.protocol_version);
+        status.capabilities = handshake.capabilities.clone();
+        status.legacy = handshake.legacy;
+        if status.authority != authority {
+            if let Some(authority) = &authority {
+                info!("Peer {} is authority {}", peer, authority);
+            }
+            status.authority = authority;
+        }
+        Ok(())
+    }
+
+    pub fn on_handshake_response(&mut self, request_id: &OutboundRequestId) {
+        self.pending.remove(request_id);
+    }
+
+    pub async fn on_block_headers(
+        &mut self,
+        request_id: &OutboundRequestId,
+        peer: &PeerId,
+        block_headers: Vec<BlockHeader>,
+        blockchain: &Arc<Mutex<Blockchain>>,
+    ) -> Result<(), Misbehaviour> {
+        let (start, limit) = match self.pending.remove(request_id) {
+            Some(PendingRequest {
+                request: SyncRequest::Headers { start, limit },
+                ..
+            }) => (start, limit),
+            _ => {
+                debug!("Ignoring unrequested block headers from {}", peer);
+                return Ok(());
+            }
+        };
+
+        if block_headers.is_empty() {
+            // The peer does not have the range (anymore), ask it again after the next handshake
+            if let Some(status) = self.peers.get_mut(peer) {
+                status.best_index = Some(start.saturating_sub(1));
+            }
+            return Ok(());
+        }
+        // Nothing is downloaded for headers that fail, and the range is asked from another peer
+        let verified = match Self::verify_header_range(start, limit, &block_headers) {
+            Ok(_) => blockchain
+                .lock()
+                .await
+                .verify_header_authors(&block_headers),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = verified {
+            warn!("Peer {} sent invalid block headers: {}", peer, e);
+            return Err(Misbehaviour::InvalidSyncResponse);
+        }
+
+        self.unlinked_headers.insert(start, block_headers);
+        Ok(())
+    }
+
+    pub fn on_block_bodies(
+        &mut self,
+        request_id: &OutboundRequestId,
+        peer: &PeerId,
+        blocks: Vec<Block>,
+    ) -> Result<(), Misbehaviour> {
+        let indexes = match self.pending.remove(request_id) {
+            Some(PendingRequest {
+                request: SyncRequest::Bodies { indexes },
+                ..
+            }) => indexes,
+            _ => {
+                debug!("Ignoring unrequested block bodies from {}", peer);
+                return Ok(());
+            }
+        };
+
+        let mut result = Ok(());
+        for block in blocks {
+            if !indexes.contains(&block.index) {
+                warn!("Peer {} sent unrequested block {}", peer, block.index);
+                continue;
+            }
+            match self.headers.get(&block.index) {
+                Some(header) if Self::block_matches_header(&block, header) => {
+                    self.blocks.insert(block.index, block);
+                }
+                Some(_) => {
+                    warn!(
+                        "Peer {} sent block {} that does not match its header",
+                        peer, block.index
+                    );
+                    result = Err(Misbehaviour::InvalidSyncResponse);
+                }
+                None => {}
+            }
+        }
+        result
+    }
+
+    // Announced hashes already in the local pool are not fetched
+    pub async fn on_pooled_transaction_hashes(
+        &mut self,
+        request_id: &OutboundRequestId,
+        peer: &PeerId,
+        hashes: Vec<String>,
+        blockchain: &Arc<Mutex<Blockchain>>,
+    ) -> Result<(), Misbehaviour> {
+        match self.pending.remove(request_id) {
+            Some(PendingRequest {
+                request: SyncRequest::PooledTransactionHashes,
+                ..
+            }) => {}
+            _ => {
+                debug!(
+                    "Ignoring unrequested pooled transaction hashes from {}",
+                    peer
+                );
+                return Ok(());
+            }
+        }
+
+        if hashes.len() > MAX_POOLED_TRANSACTION_HASHES {
+            warn!(
+                "Peer {} sent {} pooled transaction hashes for a request of {}",
+                peer,
+                hashes.len(),
+                MAX_POOLED_TRANSACTION_HASHES
+            );
+            return Err(Misbehaviour::InvalidSyncResponse);
+        }
+
+        let missing: Vec<String> = {
+            let blockchain = blockchain.lock().await;
+            hashes
+                .into_iter()
+                .filter(|hash| !blockchain.is_transaction_pooled(hash))
+                .collect()
+        };
+        if !missing.is_empty() {
+            debug!(
+                "Peer {} announced {} missing pooled transactions",
+                peer,
+                missing.len()
+            );
+            self.announced_transactions.insert(*peer, missing);
+        }
+        Ok(())
+    }
+
+    // Transactions are checked like gossiped ones and added to the pool in nonce order.
+    // Transactions the pool rejects, e.g. because they were included in a block meanwhile,
+    // are skipped.
+    pub async fn on_pooled_transactions(
+        &mut self,
+        request_id: &OutboundRequestId,
+        peer: &PeerId,
+        mut transactions: Vec<Transaction>,
+        blockchain: &Arc<Mutex<Blockchain>>,
+    ) -> Result<(), Misbehaviour> {
+        let hashes = match self.pending.remove(request_id) {
+            Some(PendingRequest {
+                request: SyncRequest::PooledTransactions { hashes },
+                ..
+            }) => hashes,
+            _ => {
+                debug!("Ignoring unrequested pooled transactions from {}", peer);
+                return Ok(());
+            }
+        };
+
+        let blockchain = blockchain.lock().await;
+        for transaction in &transactions {
+            if !hashes.contains(&transaction.hash) {
+                warn!(
+                    "Peer {} sent unrequested transaction {}",
+                    peer, transaction.hash
+                );
+                return Err(Misbehaviour::InvalidSyncResponse);
+            }
+            if let Err(e) = transaction.verify_signature(blockchain.chain_id()) {
+                warn!(
+                    "Peer {} sent invalid pooled transaction {}: {}",
+                    peer, transaction.hash, e
+                );
+                return Err(Misbehaviour::InvalidTransaction);
+            }
+        }
+
+        transactions.sort_by(|a, b| a.from.cmp(&b.from).then(a.nonce.cmp(&b.nonce)));
+        let mut added = 0;
+        for transaction in &transactions {
+            match blockchain.add_transaction_to_pool(transaction) {
+                Ok(_) => added += 1,
+                Err(e) => debug!(
+                    "Pooled transaction {} from {} not added: {}",
+                    transaction.hash, peer, e
+                ),
+            }
+        }
+        if added > 0 {
+            info!("Added {} pooled transactions from {}", added, peer);
+        }
+        Ok(())
+    }
+
+    // Headers of a response start at the requested index, are consecutive, signed by their
+    // authors and each links to the previous one
+    fn verify_header_range(
+        start: usize,
+        limit: usize,
+        block_headers: &[BlockHeader],
+    ) -> Result<(), String> {
+        if block_headers.len() > limit {
+            return Err(format!(
+                "{} headers for a request of {}",
+                block_headers.len(),
+                limit
+            ));
+        }
+        for (offset, header) in block_headers.iter().enumerate() {
+            if header.index != start + offset {
+                return Err(format!(
+                    "Expected header {}, got header {}",
+                    start + offset,
+                    header.index
+                ));
+            }
+            header.verify_signature()?;
+            if offset > 0 && header.previous_hash != block_headers[offset - 1].hash {
+                return Err(format!(
+                    "Header {} does not link to header {}",
+                    header.index,
+                    header.index - 1
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn block_matches_header(block: &Block, header: &BlockHeader) -> bool {
+        let transaction_hashes: Vec<String> = block
+            .transactions
+            .iter()
+            .map(|tx| tx.hash.clone())
+            .collect();
+        block.to_block_header() == *header
+            && Block::hash_of(
+                block.index,
+                &block.previous_hash,
+                &transaction_hashes,
+                block.timestamp,
+                block.signature_version,
+            ) == block.hash
+    }
+
+    // Drives the sync: called every SYNC_TICK
+    pub async fn tick(
+        &mut self,
+        swarm: &mut Swarm<P2PBehaviour>,
+        topic: &IdentTopic,
+        blockchain: &Arc<Mutex<Blockchain>>,
+    ) {
+        if !self.enabled {
+            return;
+        }
+
+        self.expire_requests();
+        self.send_handshakes(swarm, blockchain).await;
+        self.progress(swarm, topic, blockchain).await;
+    }
+
+    // Imports what can be imported and requests what is missing, after every tick and every
+    // sync response
+    pub async fn progress(
+        &mut self,
+        swarm: &mut Swarm<P2PBehaviour>,
+        topic: &IdentTopic,
+        blockchain: &Arc<Mutex<Blockchain>>,
+    ) {
+        if !self.enabled {
+            return;
+        }
+
+        self.import_blocks(swarm, topic, blockchain).await;
+
+        let latest_block = match blockchain.lock().await.get_latest_block() {
+            Some(block) => block,
+            None => return,
+        };
+        self.link_headers(&latest_block);
+
+        match self.target_index() {
+            Some(target) if target > latest_block.index => {
+                self.request_headers(swarm, target, latest_block.index);
+                self.request_bodies(swarm);
+            }
+            _ => {}
+        }
+
+        // Blocks first, pooled transactions use the requests left to each peer
+        self.request_pooled_transaction_hashes(swarm);
+        self.request_pooled_transactions(swarm);
+    }
+
+    fn expire_requests(&mut self) {
+        let now = Instant::now();
+        self.pending.retain(|request_id, pending| {
+            let expired = now.duration_since(pending.sent_at) > REQUEST_TIMEOUT;
+            if expired {
+                warn!(
+                    "Sync request {:?} {:?} to {} timed out",
+                    request_id, pending.request, pending.peer
+                );
+            }
+            !expired
+        });
+    }
+
+    async fn send_handshakes(
+        &mut self,
+        swarm: &mut Swarm<P2PBehaviour>,
+        blockchain: &Arc<Mutex<Blockchain>>,
+    ) {
+        let now = Instant::now();
+        let due_peers: Vec<PeerId> = self
+            .peers
+            .iter()
+            .filter(|(_, status)| {
+                status
+                    .last_handshake
+                    .is_none_or(|sent_at| now.duration_since(sent_at) >= HANDSHAKE_INTERVAL)
+            })
+            .map(|(peer, _)| *peer)
+            .collect();
+        if due_peers.is_empty() {
+            return;
+        }
+
+        let handshake = match self.local_handshake(&*blockchain.lock().await) {
+            Ok(handshake) => handshake,
+            Err(e) => {
+                warn!("Failed to build handshake: {}", e);
+                return;
+            }
+        };
+        for peer in due_peers {
+            let legacy = self.peers.get(&peer).is_some_and(|status| status.legacy);
+            let message = encode_handshake(self.version_for(&peer), &handshake, legacy);
+            self.send(swarm, peer, SyncRequest::Handshake, message);
+            if let Some(status) = self.peers.get_mut(&peer) {
+                status.last_handshake = Some(now);
+            }
+        }
+    }
+
+    // Moves header ranges that continue the local chain, or the headers linked so far, into
+    // `headers`. A range that does not link is dropped and downloaded again.
+    fn link_headers(&mut self, latest_block: &Block) {
+        // Blocks imported meanwhile, e.g. from gossip
+        self.headers.retain(|index, _| *index > latest_block.index);
+        self.blocks.retain(|index, _| *index > latest_block.index);
+        self.gossiped_blocks
+            .retain(|index, _| *index > latest_block.index);
+        if let Some(first) = self.headers.values().next() {
+            if first.index != latest_block.index + 1 || first.previous_hash != latest_block.hash {
+                warn!(
+                    "Downloaded headers no longer link to block {}, restarting sync",
+                    latest_block.index
+                );
+                self.headers.clear();
+                self.blocks.clear();
+            }
+        }
+
+        let (mut last_index, mut last_hash) = match self.headers.values().next_back() {
+            Some(header) => (header.index, header.hash.clone()),
+            None => (latest_block.index, latest_block.hash.clone()),
+        };
+        self.unlinked_headers
+            .retain(|start, range| start + range.len() > last_index + 1);
+
+        loop {
+            let next = last_index + 1;
+            let start = match self
+                .unlinked_headers
+                .range(..=next)
+                .map(|(start, _)| *start)
+                .next_back()
+            {
+                Some(start) => start,
+                None => break,
+            };
+            let range = self.unlinked_headers.remove(&start).unwrap();
+            if start + range.len() <= next {
+                continue;
+            }
+
+            let suffix = &range[next - start..];
+            if suffix[0].previous_hash != last_hash {
+                warn!(
+                    "Header {} does not link to header {}, downloading it again",
+                    next, last_index
+                );
+                break;
+            }
+            for header in suffix {
+                self.headers.insert(header.index, header.clone());
+            }
+            let last = suffix.last().unwrap();
+            last_index = last.index;
+            last_hash = last.hash.clone();
+        }
+    }
+
+    // Ranges of HEADERS_PER_REQUEST above the linked headers that are neither downloaded nor
+    // requested yet, each from the least busy peer that has it
+    fn request_headers(
+        &mut self,
+        swarm: &mut Swarm<P2PBehaviour>,
+        target: usize,
+        latest_index: usize,
+    ) {
+        let mut covered: Vec<(usize, usize)> = self
+            .pending
+            .values()
+            .filter_map(|pending| match pending.request {
+                SyncRequest::Headers { start, limit } => Some((start, start + limit)),
+                _ => None,
+            })
+            .chain(
+                self.unlinked_headers
+                    .iter()
+                    .map(|(start, range)| (*start, start + range.len())),
+            )
+            .collect();
+        covered.sort();
+
+        let mut start = self
+            .headers
+            .keys()
+            .next_back()
+            .copied()
+            .unwrap_or(latest_index)
+            + 1;
+        while start <= target {
+            if let Some((_, end)) = covered
+                .iter()
+                .find(|(range_start, range_end)| *range_start <= start && start < *range_end)
+            {
+                start = *end;
+                continue;
+            }
+
+            let limit = HEADERS_PER_REQUEST.min(target + 1 - start);
+            let peer = match self.select_peer(start + limit - 1, CAPABILITY_BLOCK_HEADERS) {
+                Some(peer) => peer,
+                None => break,
+            };
+            let get_block_headers = GetBlockHeaders {
+                start_block_index: start,
+                skip: 0,
+                limit,
+            };
+            let message = encode_message(
+                self.version_for(&peer),
+                DirectMessageType::GetBlockHeaders,
+                &get_block_headers,
+            );
+            self.send(swarm, peer, SyncRequest::Headers { start, limit }, message);
+            start += limit;
+        }
+    }
+
+    // Bodies of linked headers in batches of BODIES_PER_REQUEST, spread over the peers
+    fn request_bodies(&mut self, swarm: &mut Swarm<P2PBehaviour>) {
+        let requested: Vec<usize> = self
+            .pending
+            .values()
+            .filter_map(|pending| match &pending.request {
+                SyncRequest::Bodies { indexes } => Some(indexes.clone()),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        let missing: Vec<usize> = self
+            .headers
+            .keys()
+            .filter(|index| !self.blocks.contains_key(index) && !requested.contains(index))
+            .copied()
+            .collect();
+
+        for indexes in missing.chunks(BODIES_PER_REQUEST) {
+            let peer = match self.select_peer(*indexes.last().unwrap(), CAPABILITY_BLOCK_BODIES) {
+                Some(peer) => peer,
+                None => break,
+            };
+            let get_block_bodies = GetBlockBodies {
+                block_indexes: indexes.to_vec(),
+            };
+            let message = encode_message(
+                self.version_for(&peer),
+                DirectMessageType::GetBlockBodies,
+                &get_block_bodies,
+            );
+            self.send(
+                swarm,
+                peer,
+                SyncRequest::Bodies {
+                    indexes: indexes.to_vec(),
+                },
+                message,
+            );
+        }
+    }
+
+    // Peers that answer pooled transaction requests are asked once, after their handshake, for
+    // the hashes in their pool. Transactions gossiped before this node connected reach its
+    // pool this way.
+    fn request_pooled_transaction_hashes(&mut self, swarm: &mut Swarm<P2PBehaviour>) {
+        let peers: Vec<PeerId> = self
+            .peers
+            .iter()
+            .filter(|(_, status)| !status.pool_requested)
+            .filter(|(_, status)| {
+                status
+                    .capabilities
+                    .iter()
+                    .any(|known| known == CAPABILITY_POOLED_TRANSACTIONS)
+            })
+            .map(|(peer, _)| *peer)
+            .collect();
+
+        for peer in peers {
+            let get_pooled_transaction_hashes = GetPooledTransactionHashes {
+                limit: MAX_POOLED_TRANSACTION_HASHES,
+            };
+            let message = encode_message(
+                self.version_for(&peer),
+                DirectMessageType::GetPooledTransactionHashes,
+                &get_pooled_transaction_hashes,
+            );
+            self.send(swarm, peer, SyncRequest::PooledTransactionHashes, message);
+            if let Some(status) = self.peers.get_mut(&peer) {
+                status.pool_requested = true;
+            }
+        }
+    }
+
+    // Announced transactions in batches of POOLED_TRANSACTIONS_PER_REQUEST from the peer that
+    // announced them. A hash announced by several peers is asked from one of them.
+    fn request_pooled_transactions(&mut self, swarm: &mut Swarm<P2PBehaviour>) {
+        let mut requested: HashSet<String> = self
+            .pending
+            .values()
+            .filter_map(|pending| match &pending.request {
+                SyncRequest::PooledTransactions { hashes } => Some(hashes.clone()),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+
+        let peers: Vec<PeerId> = self.announced_transactions.keys().copied().collect();
+        for peer in peers {
+            while self.in_flight(&peer) < MAX_REQUESTS_PER_PEER {
+                let announced = match self.announced_transactions.get_mut(&peer) {
+                    Some(announced) => announced,
+                    None => break,
+                };
+                announced.retain(|hash| !requested.contains(hash));
+                if announced.is_empty() {
+                    break;
+                }
+                let count = announced.len().min(POOLED_TRANSACTIONS_PER_REQUEST);
+                let hashes: Vec<String> = announced.drain(..count).collect();
+                requested.extend(hashes.iter().cloned());
+
+                let get_pooled_transactions = GetPooledTransactions {
+                    hashes: hashes.clone(),
+                };
+                let message = encode_message(
+                    self.version_for(&peer),
+                    DirectMessageType::GetPooledTransactions,
+                    &get_pooled_transactions,
+                );
+                self.send(
+                    swarm,
+                    peer,
+                    SyncRequest::PooledTransactions { hashes },
+                    message,
+                );
+            }
+        }
+        self.announced_transactions
+            .retain(|_, announced| !announced.is_empty());
+    }
+
+    // Peer with the fewest requests in flight among those that have the block at the index and
+    // answer the request. Peers that keep sending invalid responses are banned and removed.
+    fn select_peer(&self, index: usize, capability: &str) -> Option<PeerId> {
+        self.peers
+            .iter()
+            .filter(|(_, status)| status.best_index.is_some_and(|best| best >= index))
+            .filter(|(_, status)| status.capabilities.iter().any(|known| known == capability))
+            .map(|(peer, _)| (self.in_flight(peer), *peer))
+            .filter(|(in_flight, _)| *in_flight < MAX_REQUESTS_PER_PEER)
+            .min()
+            .map(|(_, peer)| peer)
+    }
+
+    fn in_flight(&self, peer: &PeerId) -> usize {
+        self.pending
+            .values()
+            .filter(|pending| pending.peer == *peer)
+            .count()
+    }
+
+    fn version_for(&self, peer: &PeerId) -> u32 {
+        negotiate_version(
+            self.peers
+                .get(peer)
+                .and_then(|status| status.protocol_version),
+        )
+    }
+
+    fn send(
+        &mut self,
+        swarm: &mut Swarm<P2PBehaviour>,
+        peer: PeerId,
+        request: SyncRequest,
+        message: Vec<u8>,
+    ) {
+        debug!("Sending sync request {:?} to {}", request, peer);
+        let request_id = send_request(&peer, message, swarm);
+        self.pending.insert(
+            request_id,
+            PendingRequest {
+                peer,
+                request,
+                sent_at: Instant::now(),
+            },
+        );
+    }
+
+    // Imports downloaded and gossiped blocks that continue the local chain. A downloaded block
+    // that fails to import drops everything downloaded above the local head.
+    async fn import_blocks(
+        &mut self,
+        swarm: &mut Swarm<P2PBehaviour>,
+        topic: &IdentTopic,
+        blockchain: &Arc<Mutex<Blockchain>>,
+    ) {
+        let mut proofs = Vec::new();
+        let mut precommits = Vec::new();
+        {
+            let blockchain = blockchain.lock().await;
+            let mut latest_index = match blockchain.get_latest_block() {
+                Some(block) => block.index,
+                None => return,
+            };
+            let mut imported = 0;
+
+            loop {
+                let next = latest_index + 1;
+                let (block, gossiped) = match self.blocks.remove(&next) {
+                    Some(block) => (block, false),
+                    None => match self.gossiped_blocks.remove(&next) {
+                        Some(block) => (block, true),
+                        None => break,
+                    },
+                };
+
+                if let Some(proof) = blockchain.observe_block(&block) {
+                    proofs.push(proof);
+                }
+                match blockchain.import_block(&block) {
+                    Ok(_) => {
+                        debug!("Successfully imported block with index: {}", block.index);
+                        self.headers.remove(&block.index);
+                        latest_index = block.index;
+                        imported += 1;
+                        // Gossiped blocks are at the head of the chain, so they are precommitted
+                        // like blocks imported from gossip directly
+                        if gossiped {
+                            match blockchain.precommit_block(&block) {
+                                Ok(Some(precommit)) => precommits.push(precommit),
+                                Ok(None) => {}
+                                Err(e) => warn!("Failed to precommit block {}: {}", block.index, e),
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        warn!("Failed to import block with index {}: {:?}", block.index, e);
+                        if !gossiped {
+                            self.headers.clear();
+                            self.blocks.clear();
+                        }
+                        break;
+                    }
+                }
+            }
+
+            if imported > 0 {
+                info!(
+                    "Synced {} blocks, at block {} of {}",
+                    imported,
+                    latest_index,
+                    self.target_index().unwrap_or(latest_index)
+                );
+            }
+        }
+
+        for precommit in precommits {
+            publish(
+                swarm,
+                topic,
+                GossipMessageType::Precommit,
+                encode(&precommit),
+            );
+        }
+        for proof in proofs {
+            publish(
+                swarm,
+                topic,
+                GossipMessageType::Equivocation,
+                encode(&proof),
+            );
+            handle_received_equivocation(&proof, swarm, topic, blockchain).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AUTHOR_PUBLIC_KEY: &str = "0x9b6e8afff8329743cac73dbef83ca3cbf9a74c20";
+    const AUTHOR_SECRET_KEY: &str =
+        "0883ddd3d07303b87c954b0c9383f7b78f45e002520fc03a8adc80595dbf6509";
+
+    fn signed_headers(count: usize) -> Vec<BlockHeader> {
+        let mut previous_hash = "0".repeat(64);
+        (1..=count)
+            .map(|index| {
+                let mut block = Block::new_block(index, previous_hash.clone(), vec![]);
+                block.sign(AUTHOR_PUBLIC_KEY, AUTHOR_SECRET_KEY);
+                previous_hash = block.hash.clone();
+                block.to_block_header()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_verify_header_range() {
+        let headers = signed_headers(3);
+        assert!(SyncManager::verify_header_range(1, 3, &headers).is_ok());
+        assert!(SyncManager::verify_header_range(1, 2, &headers).is_err());
+        assert!(SyncManager::verify_header_range(2, 3, &headers).is_err());
+
+        let mut unlinked = headers.clone();
+        unlinked[2].previous_hash = "0".repeat(64);
+        assert!(SyncManager::verify_header_range(1, 3, &unlinked).is_err());
+
+        let mut forged = headers.clone();
+        forged[1].author = "0xdeb4cfb63db134698e1879ea24904df074726cc0".to_string();
+        assert!(SyncManager::verify_header_range(1, 3, &forged).is_err());
+    }
+}
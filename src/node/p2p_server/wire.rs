@@ -0,0 +1,126 @@
+use libp2p::StreamProtocol;
+use rlp::{Rlp, RlpStream};
+
+use super::handshake::{MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+
+// Request-response protocols, preferred first. Nodes that only know the legacy name are
+// still answered over it.
+pub const SYNC_PROTOCOL: StreamProtocol = StreamProtocol::new("/clutch/sync/2");
+pub const LEGACY_SYNC_PROTOCOL: StreamProtocol = StreamProtocol::new("/agent/message/1.0.0");
+
+// Gossip reaches every peer at once, so it is written in the oldest version still supported
+pub const GOSSIP_VERSION: u32 = MIN_PROTOCOL_VERSION;
+
+// RLP lists start at 0xc0, message type bytes are below it
+const RLP_LIST_PREFIX: u8 = 0xc0;
+
+// Framing of gossip and direct messages. Version 1 is the message type byte followed by the
+// RLP payload. From version 2 a message is the RLP list [version, message_type, payload], so
+// payload layouts can change with the version while older peers are served in theirs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Envelope {
+    pub version: u32,
+    pub message_type: u8,
+    pub payload: Vec<u8>,
+}
+
+impl Envelope {
+    pub fn new(version: u32, message_type: u8, payload: Vec<u8>) -> Envelope {
+        Envelope {
+            version,
+            message_type,
+            payload,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        if self.version < 2 {
+            let mut message = Vec::with_capacity(1 + self.payload.len());
+            message.push(self.message_type);
+            message.extend_from_slice(&self.payload);
+            return message;
+        }
+
+        let mut stream = RlpStream::new_list(3);
+        stream.append(&self.version);
+        stream.append(&self.message_type);
+        stream.append(&self.payload);
+        stream.out().to_vec()
+    }
+
+    pub fn decode(message: &[u8]) -> Result<Envelope, String> {
+        let first_byte = *message.first().ok_or("Empty message")?;
+        if first_byte < RLP_LIST_PREFIX {
+            return Ok(Envelope::new(1, first_byte, message[1..].to_vec()));
+        }
+
+        let rlp = Rlp::new(message);
+        if rlp.item_count().map_err(|e| e.to_string())? != 3 {
+            return Err("Message envelope is not a list of 3 items".to_string());
+        }
+        let version: u32 = rlp.val_at(0).map_err(|e| e.to_string())?;
+        if !(2..=PROTOCOL_VERSION).contains(&version) {
+            return Err(format!("Unsupported message version {}", version));
+        }
+        Ok(Envelope::new(
+            version,
+            rlp.val_at(1).map_err(|e| e.to_string())?,
+            rlp.val_at(2).map_err(|e| e.to_string())?,
+        ))
+    }
+}
+
+// Version of direct messages to a peer: the highest both speak, or the oldest supported
+// until the peer's handshake told its version
+pub fn negotiate_version(peer_version: Option<u32>) -> u32 {
+    peer_version
+        .map(|version| version.clamp(MIN_PROTOCOL_VERSION, PROTOCOL_VERSION))
+        .unwrap_or(MIN_PROTOCOL_VERSION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::p2p_server::commands::DirectMessageType;
+    use crate::node::p2p_server::get_block_header::GetBlockHeaders;
+    use crate::node::rlp_encoding::{decode, encode};
+
+    #[test]
+    fn test_decode_both_versions() {
+        let get_block_headers = GetBlockHeaders {
+            start_block_index: 5,
+            skip: 0,
+            limit: 64,
+        };
+        let message_type = DirectMessageType::GetBlockHeaders.as_byte();
+
+        for version in [1, 2] {
+            let message = Envelope::new(version, message_type, encode(&get_block_headers)).encode();
+            let envelope = Envelope::decode(&message).unwrap();
+            assert_eq!(envelope.version, version);
+            assert_eq!(envelope.message_type, message_type);
+            let decoded = decode::<GetBlockHeaders>(&envelope.payload).unwrap();
+            assert_eq!(decoded.start_block_index, 5);
+            assert_eq!(decoded.limit, 64);
+        }
+
+        // Version 1 is the type byte and payload, as written before envelopes existed
+        let mut legacy_message = vec![message_type];
+        legacy_message.extend(encode(&get_block_headers));
+        assert_eq!(Envelope::decode(&legacy_message).unwrap().version, 1);
+    }
+
+    #[test]
+    fn test_unsupported_versions_rejected() {
+        let message = Envelope::new(PROTOCOL_VERSION + 1, 0x01, vec![0x80]).encode();
+        assert!(Envelope::decode(&message).is_err());
+        assert!(Envelope::decode(&[]).is_err());
+    }
+
+    #[test]
+    fn test_negotiate_version() {
+        assert_eq!(negotiate_version(None), MIN_PROTOCOL_VERSION);
+        assert_eq!(negotiate_version(Some(1)), 1);
+        assert_eq!(negotiate_version(Some(PROTOCOL_VERSION + 1)), PROTOCOL_VERSION);
+    }
+}
@@ -8,18 +8,30 @@ use hex;
 use super::blocks::block::Block;
 use super::blocks::block_bodies::BlockBodies;
 use super::blocks::block_headers::{BlockHeader, BlockHeaders};
+use super::equivocation::{EquivocationProof, SignedHeader};
+use super::finality::Precommit;
 use super::p2p_server::get_block_bodies::GetBlockBodies;
 use super::p2p_server::get_block_header::GetBlockHeaders;
-use super::p2p_server::handshake::Handshake;
+use super::p2p_server::handshake::{Handshake, CAPABILITY_BLOCK_BODIES, CAPABILITY_BLOCK_HEADERS};
+use super::p2p_server::node_key::NodeIdentity;
+use super::p2p_server::pooled_transactions::{
+    GetPooledTransactionHashes, GetPooledTransactions, PooledTransactionHashes, PooledTransactions,
+};
+use super::signature_keys::SignatureVersion;
 use super::transactions::complain_arrival::ComplainArrival;
 use super::transactions::confirm_arrival::ConfirmArrival;
 use super::transactions::function_call::FunctionCall;
+use super::transactions::raw_transaction::RawTransaction;
+use super::transactions::propose_validator::ProposeValidator;
+use super::transactions::remove_validator::RemoveValidator;
+use super::transactions::report_equivocation::ReportEquivocation;
 use super::transactions::ride_acceptance::RideAcceptance;
 use super::transactions::ride_cancel::RideCancel;
 use super::transactions::ride_offer::RideOffer;
 use super::transactions::ride_pay::RidePay;
 use super::transactions::ride_request::RideRequest;
 use super::transactions::transfer::Transfer;
+use super::transactions::vote_validator::VoteValidator;
 
 impl Encodable for FunctionCall {
     fn rlp_append(&self, stream: &mut RlpStream) {
@@ -64,6 +76,26 @@ impl Encodable for FunctionCall {
                 stream.append(&7u8); // Tag for ComplainArrival
                 stream.append(args);
             }
+            FunctionCall::ProposeValidator(args) => {
+                stream.begin_list(2);
+                stream.append(&8u8); // Tag for ProposeValidator
+                stream.append(args);
+            }
+            FunctionCall::VoteValidator(args) => {
+                stream.begin_list(2);
+                stream.append(&9u8); // Tag for VoteValidator
+                stream.append(args);
+            }
+            FunctionCall::RemoveValidator(args) => {
+                stream.begin_list(2);
+                stream.append(&10u8); // Tag for RemoveValidator
+                stream.append(args);
+            }
+            FunctionCall::ReportEquivocation(args) => {
+                stream.begin_list(2);
+                stream.append(&11u8); // Tag for ReportEquivocation
+                stream.append(args);
+            }
         }
     }
 }
@@ -109,14 +141,98 @@ impl Decodable for FunctionCall {
                 let args: ComplainArrival = rlp.val_at(1)?;
                 Ok(FunctionCall::ComplainArrival(args))
             }
+            8 => {
+                let args: ProposeValidator = rlp.val_at(1)?;
+                Ok(FunctionCall::ProposeValidator(args))
+            }
+            9 => {
+                let args: VoteValidator = rlp.val_at(1)?;
+                Ok(FunctionCall::VoteValidator(args))
+            }
+            10 => {
+                let args: RemoveValidator = rlp.val_at(1)?;
+                Ok(FunctionCall::RemoveValidator(args))
+            }
+            11 => {
+                let args: ReportEquivocation = rlp.val_at(1)?;
+                Ok(FunctionCall::ReportEquivocation(args))
+            }
             _ => Err(DecoderError::Custom("Unknown FunctionCall variant")),
         }
     }
 }
 
+// Appended as a single value: `append` would count it twice and close the enclosing list
+// one item early, pushing the fee of a transaction out of its list
+impl Encodable for SignatureVersion {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.append_internal(&self.as_byte());
+    }
+}
+
+impl Decodable for SignatureVersion {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        let byte: u8 = rlp.as_val()?;
+        SignatureVersion::from_byte(byte).ok_or(DecoderError::Custom("Unknown SignatureVersion"))
+    }
+}
+
+// The signature version is only appended when it is not legacy, keeping legacy encodings unchanged
+fn append_signature_version(stream: &mut RlpStream, signature_version: &SignatureVersion) {
+    if *signature_version != SignatureVersion::Legacy {
+        stream.append(signature_version);
+    }
+}
+
+fn signature_list_len(item_count: usize, signature_version: &SignatureVersion) -> usize {
+    if *signature_version == SignatureVersion::Legacy {
+        item_count
+    } else {
+        item_count + 1
+    }
+}
+
+fn decode_signature_version(
+    rlp: &Rlp,
+    item_count: usize,
+) -> Result<SignatureVersion, DecoderError> {
+    if rlp.item_count()? > item_count {
+        rlp.val_at(item_count)
+    } else {
+        Ok(SignatureVersion::Legacy)
+    }
+}
+
+// Transactions carry the fee after the signature version; both are only appended when set,
+// and a fee forces an explicit (possibly legacy) signature version in front of it
+fn append_transaction_tail(stream: &mut RlpStream, signature_version: &SignatureVersion, fee: u64) {
+    if fee > 0 {
+        stream.append(signature_version);
+        stream.append(&fee);
+    } else {
+        append_signature_version(stream, signature_version);
+    }
+}
+
+fn transaction_list_len(item_count: usize, signature_version: &SignatureVersion, fee: u64) -> usize {
+    if fee > 0 {
+        item_count + 2
+    } else {
+        signature_list_len(item_count, signature_version)
+    }
+}
+
+fn decode_fee(rlp: &Rlp, item_count: usize) -> Result<u64, DecoderError> {
+    if rlp.item_count()? > item_count + 1 {
+        rlp.val_at(item_count + 1)
+    } else {
+        Ok(0)
+    }
+}
+
 impl Encodable for Transaction {
     fn rlp_append(&self, stream: &mut RlpStream) {
-        stream.begin_list(7);
+        stream.begin_list(transaction_list_len(7, &self.signature_version, self.fee));
 
         stream.append(&self.from);
         stream.append(&self.nonce);
@@ -126,12 +242,13 @@ impl Encodable for Transaction {
         stream.append(&signature_v_as_u64);
         stream.append(&self.hash);
         stream.append(&self.data);
+        append_transaction_tail(stream, &self.signature_version, self.fee);
     }
 }
 
 impl Decodable for Transaction {
     fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
-        if !rlp.is_list() || rlp.item_count()? != 7 {
+        if !rlp.is_list() || !(7..=9).contains(&rlp.item_count()?) {
             return Err(DecoderError::RlpIncorrectListLen);
         }            
         
@@ -159,18 +276,54 @@ impl Decodable for Transaction {
         Ok(Transaction {
             from,
             nonce: rlp.val_at(1)?,
+            fee: decode_fee(rlp, 7)?,
             signature_r: rlp.val_at(2)?,
             signature_s: rlp.val_at(3)?,
             signature_v: rlp.val_at::<u64>(4)? as i32,
+            signature_version: decode_signature_version(rlp, 7)?,
             hash: rlp.val_at(5)?,
             data: rlp.val_at(6)?,
         })
     }
 }
 
+impl Encodable for RawTransaction {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(transaction_list_len(6, &self.signature_version, self.fee));
+
+        stream.append(&self.nonce);
+        stream.append(&self.signature_r);
+        stream.append(&self.signature_s);
+        let signature_v_as_u64 = self.signature_v as u64;
+        stream.append(&signature_v_as_u64);
+        stream.append(&self.hash);
+        stream.append(&self.data);
+        append_transaction_tail(stream, &self.signature_version, self.fee);
+    }
+}
+
+impl Decodable for RawTransaction {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if !rlp.is_list() || !(6..=8).contains(&rlp.item_count()?) {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        Ok(RawTransaction {
+            nonce: rlp.val_at(0)?,
+            fee: decode_fee(rlp, 6)?,
+            signature_r: rlp.val_at(1)?,
+            signature_s: rlp.val_at(2)?,
+            signature_v: rlp.val_at::<u64>(3)? as i32,
+            signature_version: decode_signature_version(rlp, 6)?,
+            hash: rlp.val_at(4)?,
+            data: rlp.val_at(5)?,
+        })
+    }
+}
+
 impl Encodable for Block {
     fn rlp_append(&self, stream: &mut RlpStream) {
-        stream.begin_list(9);
+        stream.begin_list(signature_list_len(9, &self.signature_version));
 
         stream.append(&self.index);
         stream.append(&self.timestamp);
@@ -182,12 +335,13 @@ impl Encodable for Block {
         stream.append(&signature_v_as_u64);
         stream.append(&self.hash);
         stream.append_list(&self.transactions);
+        append_signature_version(stream, &self.signature_version);
     }
 }
 
 impl Decodable for Block {
     fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
-        if !rlp.is_list() || rlp.item_count()? != 9 {
+        if !rlp.is_list() || !(9..=10).contains(&rlp.item_count()?) {
             return Err(DecoderError::RlpIncorrectListLen);
         }
         
@@ -199,6 +353,7 @@ impl Decodable for Block {
             signature_r: rlp.val_at(4)?,
             signature_s: rlp.val_at(5)?,
             signature_v: rlp.val_at::<u64>(6)? as i32,
+            signature_version: decode_signature_version(rlp, 9)?,
             hash: rlp.val_at(7)?,
             transactions: rlp.list_at(8)?,
         })
@@ -207,23 +362,132 @@ impl Decodable for Block {
 
 impl Encodable for Handshake {
     fn rlp_append(&self, stream: &mut RlpStream) {
-        stream.begin_list(3);
+        stream.begin_list(7);
+        stream.append(&self.chain_id);
+        stream.append(&self.protocol_version);
+        stream.append_list::<String, String>(&self.capabilities);
         stream.append(&self.genesis_block_hash);
         stream.append(&self.latest_block_hash);
         stream.append(&self.latest_block_index);
+        // An empty list for nodes without an identity
+        match &self.node_identity {
+            Some(node_identity) => stream.append(node_identity),
+            None => stream.begin_list(0),
+        };
     }
 }
 
 impl Decodable for Handshake {
     fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
-        if !rlp.is_list() || rlp.item_count()? != 3 {
+        if !rlp.is_list() || rlp.item_count()? != 7 {
             return Err(DecoderError::RlpIncorrectListLen);
         }
-        
+
+        // Only the legacy layout has no chain id
+        let chain_id: u64 = rlp.val_at(0)?;
+        if chain_id == 0 {
+            return Err(DecoderError::Custom("Handshake without a chain id"));
+        }
+
+        let node_identity = rlp.at(6)?;
         Ok(Handshake {
-            genesis_block_hash: rlp.val_at(0)?,
-            latest_block_hash: rlp.val_at(1)?,
-            latest_block_index: rlp.val_at(2)?,
+            chain_id,
+            protocol_version: rlp.val_at(1)?,
+            capabilities: rlp.list_at(2)?,
+            genesis_block_hash: rlp.val_at(3)?,
+            latest_block_hash: rlp.val_at(4)?,
+            latest_block_index: rlp.val_at(5)?,
+            node_identity: if node_identity.item_count()? == 0 {
+                None
+            } else {
+                Some(node_identity.as_val()?)
+            },
+            legacy: false,
+        })
+    }
+}
+
+// Nodes from before chain ids handshake with [genesis_block_hash, latest_block_hash,
+// latest_block_index] in version 1 messages and answer block headers and bodies
+pub fn decode_handshake(payload: &[u8], version: u32) -> Result<Handshake, DecoderError> {
+    let rlp = Rlp::new(payload);
+    if version > 1 || !rlp.is_list() || rlp.item_count()? != 3 {
+        return Handshake::decode(&rlp);
+    }
+
+    Ok(Handshake {
+        chain_id: 0,
+        protocol_version: 1,
+        capabilities: vec![
+            CAPABILITY_BLOCK_HEADERS.to_string(),
+            CAPABILITY_BLOCK_BODIES.to_string(),
+        ],
+        genesis_block_hash: rlp.val_at(0)?,
+        latest_block_hash: rlp.val_at(1)?,
+        latest_block_index: rlp.val_at(2)?,
+        node_identity: None,
+        legacy: true,
+    })
+}
+
+pub fn encode_legacy_handshake(handshake: &Handshake) -> Vec<u8> {
+    let mut stream = RlpStream::new_list(3);
+    stream.append(&handshake.genesis_block_hash);
+    stream.append(&handshake.latest_block_hash);
+    stream.append(&handshake.latest_block_index);
+    stream.out().to_vec()
+}
+
+impl Encodable for NodeIdentity {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(4);
+        stream.append(&self.authority);
+        stream.append(&self.signature_r);
+        stream.append(&self.signature_s);
+        stream.append(&(self.signature_v as u64));
+    }
+}
+
+impl Decodable for NodeIdentity {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if !rlp.is_list() || rlp.item_count()? != 4 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        Ok(NodeIdentity {
+            authority: rlp.val_at(0)?,
+            signature_r: rlp.val_at(1)?,
+            signature_s: rlp.val_at(2)?,
+            signature_v: rlp.val_at::<u64>(3)? as i32,
+        })
+    }
+}
+
+impl Encodable for Precommit {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(6);
+        stream.append(&self.block_index);
+        stream.append(&self.block_hash);
+        stream.append(&self.voter);
+        stream.append(&self.signature_r);
+        stream.append(&self.signature_s);
+        stream.append(&(self.signature_v as u64));
+    }
+}
+
+impl Decodable for Precommit {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if !rlp.is_list() || rlp.item_count()? != 6 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        Ok(Precommit {
+            block_index: rlp.val_at(0)?,
+            block_hash: rlp.val_at(1)?,
+            voter: rlp.val_at(2)?,
+            signature_r: rlp.val_at(3)?,
+            signature_s: rlp.val_at(4)?,
+            signature_v: rlp.val_at::<u64>(5)? as i32,
         })
     }
 }
@@ -251,9 +515,15 @@ impl Decodable for GetBlockHeaders {
     }
 }
 
+// A header without a timestamp keeps the layout it had before the timestamp was added, so
+// equivocation proofs in existing report transactions encode to the same hash
 impl Encodable for BlockHeader {
     fn rlp_append(&self, stream: &mut RlpStream) {
-        stream.begin_list(7);
+        if self.timestamp == 0 {
+            stream.begin_list(signature_list_len(7, &self.signature_version));
+        } else {
+            stream.begin_list(9);
+        }
         stream.append(&self.index);
         stream.append(&self.previous_hash);
         stream.append(&self.author);
@@ -262,12 +532,19 @@ impl Encodable for BlockHeader {
         let signature_v_as_u64 = self.signature_v as u64;
         stream.append(&signature_v_as_u64);
         stream.append(&self.hash);
+        if self.timestamp == 0 {
+            append_signature_version(stream, &self.signature_version);
+        } else {
+            stream.append(&self.signature_version);
+            stream.append(&self.timestamp);
+        }
     }
 }
 
 impl Decodable for BlockHeader {
     fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
-        if !rlp.is_list() || rlp.item_count()? != 7 {
+        let item_count = rlp.item_count()?;
+        if !rlp.is_list() || !(7..=9).contains(&item_count) {
             return Err(DecoderError::RlpIncorrectListLen);
         }
         
@@ -278,7 +555,51 @@ impl Decodable for BlockHeader {
             signature_r: rlp.val_at(3)?,
             signature_s: rlp.val_at(4)?,
             signature_v: rlp.val_at::<u64>(5)? as i32,
+            signature_version: decode_signature_version(rlp, 7)?,
             hash: rlp.val_at(6)?,
+            timestamp: if item_count == 9 { rlp.val_at(8)? } else { 0 },
+        })
+    }
+}
+
+impl Encodable for SignedHeader {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(2);
+        stream.append(&self.header);
+        stream.append_list::<String, String>(&self.transaction_hashes);
+    }
+}
+
+impl Decodable for SignedHeader {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if !rlp.is_list() || rlp.item_count()? != 2 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        Ok(SignedHeader {
+            header: rlp.val_at(0)?,
+            transaction_hashes: rlp.list_at(1)?,
+        })
+    }
+}
+
+impl Encodable for EquivocationProof {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(2);
+        stream.append(&self.first);
+        stream.append(&self.second);
+    }
+}
+
+impl Decodable for EquivocationProof {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if !rlp.is_list() || rlp.item_count()? != 2 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        Ok(EquivocationProof {
+            first: rlp.val_at(0)?,
+            second: rlp.val_at(1)?,
         })
     }
 }
@@ -340,6 +661,82 @@ impl Decodable for BlockBodies {
     }
 }
 
+impl Encodable for GetPooledTransactionHashes {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(1);
+        stream.append(&self.limit);
+    }
+}
+
+impl Decodable for GetPooledTransactionHashes {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if !rlp.is_list() || rlp.item_count()? != 1 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        Ok(GetPooledTransactionHashes {
+            limit: rlp.val_at(0)?,
+        })
+    }
+}
+
+impl Encodable for PooledTransactionHashes {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(1);
+        stream.append_list::<String, String>(&self.hashes);
+    }
+}
+
+impl Decodable for PooledTransactionHashes {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if !rlp.is_list() || rlp.item_count()? != 1 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        Ok(PooledTransactionHashes {
+            hashes: rlp.list_at(0)?,
+        })
+    }
+}
+
+impl Encodable for GetPooledTransactions {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(1);
+        stream.append_list::<String, String>(&self.hashes);
+    }
+}
+
+impl Decodable for GetPooledTransactions {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if !rlp.is_list() || rlp.item_count()? != 1 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        Ok(GetPooledTransactions {
+            hashes: rlp.list_at(0)?,
+        })
+    }
+}
+
+impl Encodable for PooledTransactions {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(1);
+        stream.append_list(&self.transactions);
+    }
+}
+
+impl Decodable for PooledTransactions {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if !rlp.is_list() || rlp.item_count()? != 1 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        Ok(PooledTransactions {
+            transactions: rlp.list_at(0)?,
+        })
+    }
+}
+
 pub fn encode<T: Encodable>(data: &T) -> Vec<u8> {
     let mut stream = RlpStream::new();
     data.rlp_append(&mut stream);
@@ -356,6 +753,7 @@ mod tests {
 
     use tracing::{error, info};
 
+    use crate::node::p2p_server::wire::Envelope;
     use crate::node::time_utils::get_current_timespan;
 
     use super::*;
@@ -371,11 +769,13 @@ mod tests {
             from: "0xdeb4cfb63db134698e1879ea24904df074726cc0".to_string(),
             data: function_call,
             nonce: 1,
+            fee: 0,
             signature_r: "3b0cb46ae73d852bb75653ed1f1710676b0b736cd33aefc0c96e6e11417a4c32"
                 .to_string(),
             signature_s: "296086bdc703286c0727c59e07b727cadfc2fe7b9c061149e4a86e726ed23908"
                 .to_string(),
             signature_v: 27,
+            signature_version: SignatureVersion::Legacy,
             hash: "0086095648e3160d0dfa5d40bdf4693d8a00d77ed3fb3b607156465b3e0de2db".to_string(),
         };
 
@@ -389,6 +789,28 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_encode_decode_transaction_with_fee() {
+        let mut tx = Transaction::new_transaction_with_fee(
+            "0xdeb4cfb63db134698e1879ea24904df074726cc0".to_string(),
+            1,
+            3,
+            FunctionCall::Transfer(Transfer {
+                to: "0x8f19077627cde4848b090c53c83b12956837d5e9".to_string(),
+                value: 10,
+            }),
+        );
+        tx.signature_version = SignatureVersion::Legacy;
+
+        let decoded = decode::<Transaction>(&encode(&tx)).unwrap();
+        assert_eq!(decoded.fee, 3);
+        assert_eq!(decoded.signature_version, SignatureVersion::Legacy);
+        assert_eq!(decoded.hash, tx.hash);
+
+        let raw = decode::<RawTransaction>(&encode(&tx.to_raw_transaction())).unwrap();
+        assert_eq!(raw.fee, 3);
+    }
+
     #[test]
     fn test_encode_decode_block() {
         let tx1 = Transaction {
@@ -398,11 +820,13 @@ mod tests {
                 value: 10,
             }),
             nonce: 1,
+            fee: 0,
             signature_r: "3b0cb46ae73d852bb75653ed1f1710676b0b736cd33aefc0c96e6e11417a4c32"
                 .to_string(),
             signature_s: "296086bdc703286c0727c59e07b727cadfc2fe7b9c061149e4a86e726ed23908"
                 .to_string(),
             signature_v: 27,
+            signature_version: SignatureVersion::Legacy,
             hash: "0086095648e3160d0dfa5d40bdf4693d8a00d77ed3fb3b607156465b3e0de2db".to_string(),
         };
 
@@ -413,11 +837,13 @@ mod tests {
                 value: 5,
             }),
             nonce: 2,
+            fee: 0,
             signature_r: "2b0cb46ae73d852bb75653ed1f1710676b0b736cd33aefc0c96e6e11417a4c33"
                 .to_string(),
             signature_s: "396086bdc703286c0727c59e07b727cadfc2fe7b9c061149e4a86e726ed23909"
                 .to_string(),
             signature_v: 28,
+            signature_version: SignatureVersion::Legacy,
             hash: "1086095648e3160d0dfa5d40bdf4693d8a00d77ed3fb3b607156465b3e0de2db".to_string(),
         };
 
@@ -432,6 +858,7 @@ mod tests {
             signature_s: "496086bdc703286c0727c59e07b727cadfc2fe7b9c061149e4a86e726ed23910"
                 .to_string(),
             signature_v: 27,
+            signature_version: SignatureVersion::Legacy,
             hash: "2086095648e3160d0dfa5d40bdf4693d8a00d77ed3fb3b607156465b3e0de2dc".to_string(),
             transactions: vec![tx1, tx2],
         };
@@ -446,6 +873,62 @@ mod tests {
         }
     }
 
+    fn fee_paying_transactions() -> Vec<Transaction> {
+        [
+            (1, 3, SignatureVersion::Legacy),
+            (2, 5, SignatureVersion::Digest),
+        ]
+        .into_iter()
+        .map(|(nonce, fee, signature_version)| {
+            let mut tx = Transaction::new_transaction_with_fee(
+                "0xdeb4cfb63db134698e1879ea24904df074726cc0".to_string(),
+                nonce,
+                fee,
+                FunctionCall::Transfer(Transfer {
+                    to: "0x8f19077627cde4848b090c53c83b12956837d5e9".to_string(),
+                    value: 10,
+                }),
+            );
+            tx.signature_version = signature_version;
+            tx
+        })
+        .collect()
+    }
+
+    fn block_with_transactions(index: usize, transactions: Vec<Transaction>) -> Block {
+        let mut block = Block::new_block(index, "0".repeat(64), transactions);
+        block.sign(
+            "0x9b6e8afff8329743cac73dbef83ca3cbf9a74c20",
+            "0883ddd3d07303b87c954b0c9383f7b78f45e002520fc03a8adc80595dbf6509",
+        );
+        block
+    }
+
+    #[test]
+    fn test_encode_decode_block_with_fee_paying_transactions() {
+        let transactions = fee_paying_transactions();
+        let block = block_with_transactions(1, transactions.clone());
+
+        let decoded = decode::<Block>(&encode(&block)).unwrap();
+        assert_eq!(decoded.hash, block.hash);
+        assert_eq!(decoded.transactions.len(), 2);
+        for (decoded_tx, tx) in decoded.transactions.iter().zip(&transactions) {
+            assert_eq!(decoded_tx.hash, tx.hash);
+            assert_eq!(decoded_tx.fee, tx.fee);
+            assert_eq!(decoded_tx.signature_version, tx.signature_version);
+        }
+
+        let block_bodies = BlockBodies {
+            blocks: vec![block, block_with_transactions(2, transactions)],
+        };
+        let decoded = decode::<BlockBodies>(&encode(&block_bodies)).unwrap();
+        assert_eq!(decoded.blocks.len(), 2);
+        assert!(decoded
+            .blocks
+            .iter()
+            .all(|block| block.transactions.iter().map(|tx| tx.fee).eq([3, 5])));
+    }
+
     #[test]
     fn test_encode_decode_get_block_headers() {
         let get_block_headers = GetBlockHeaders {
@@ -476,7 +959,9 @@ mod tests {
             signature_s: "496086bdc703286c0727c59e07b727cadfc2fe7b9c061149e4a86e726ed23910"
                 .to_string(),
             signature_v: 27,
+            signature_version: SignatureVersion::Legacy,
             hash: "2086095648e3160d0dfa5d40bdf4693d8a00d77ed3fb3b607156465b3e0de2dc".to_string(),
+            timestamp: 0,
         };
 
         let block_header_2 = BlockHeader {
@@ -489,7 +974,9 @@ mod tests {
             signature_s: "496086bdc703286c0727c59e07b727cadfc2fe7b9c061149e4a86e726ed23910"
                 .to_string(),
             signature_v: 27,
+            signature_version: SignatureVersion::Legacy,
             hash: "2086095648e3160d0dfa5d40bdf4693d8a00d77ed3fb3b607156465b3e0de2dc".to_string(),
+            timestamp: 0,
         };
 
         let block_headers = BlockHeaders {
@@ -506,6 +993,37 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_encode_decode_block_header_timestamp() {
+        let mut block_header = BlockHeader {
+            index: 1,
+            previous_hash: "0".repeat(64),
+            author: "0x1234cfb63db134698e1879ea24904df074726cc0".to_string(),
+            signature_r: "4b0cb46ae73d852bb75653ed1f1710676b0b736cd33aefc0c96e6e11417a4c34"
+                .to_string(),
+            signature_s: "496086bdc703286c0727c59e07b727cadfc2fe7b9c061149e4a86e726ed23910"
+                .to_string(),
+            signature_v: 27,
+            signature_version: SignatureVersion::Legacy,
+            hash: "2086095648e3160d0dfa5d40bdf4693d8a00d77ed3fb3b607156465b3e0de2dc".to_string(),
+            timestamp: 0,
+        };
+
+        // Without a timestamp the header keeps its legacy layout
+        assert_eq!(Rlp::new(&encode(&block_header)).item_count().unwrap(), 7);
+
+        block_header.timestamp = 1_700_000_000;
+        let decoded = decode::<BlockHeader>(&encode(&block_header)).unwrap();
+        assert_eq!(decoded, block_header);
+        // Nested in a list, where a miscounted item shows up
+        block_header.signature_version = SignatureVersion::Digest;
+        let block_headers = BlockHeaders {
+            block_headers: vec![block_header.clone()],
+        };
+        let decoded = decode::<BlockHeaders>(&encode(&block_headers)).unwrap();
+        assert_eq!(decoded.block_headers[0], block_header);
+    }
+
     #[test]
     fn test_encode_decode_block_bodies() {
         let block = Block {
@@ -519,6 +1037,7 @@ mod tests {
             signature_s: "496086bdc703286c0727c59e07b727cadfc2fe7b9c061149e4a86e726ed23910"
                 .to_string(),
             signature_v: 27,
+            signature_version: SignatureVersion::Legacy,
             hash: "2086095648e3160d0dfa5d40bdf4693d8a00d77ed3fb3b607156465b3e0de2dc".to_string(),
             transactions: vec![],
         };
@@ -536,4 +1055,103 @@ mod tests {
             Err(e) => error!("Failed to decode BlockBodies: {:?}", e),
         }
     }
+
+    #[test]
+    fn test_encode_decode_handshake_with_node_identity() {
+        let mut handshake = Handshake {
+            chain_id: 1,
+            protocol_version: 1,
+            capabilities: vec!["block_headers".to_string(), "block_bodies".to_string()],
+            genesis_block_hash: "0086095648e3160d0dfa5d40bdf4693d8a00d77ed3fb3b607156465b3e0de2db"
+                .to_string(),
+            latest_block_hash: "2086095648e3160d0dfa5d40bdf4693d8a00d77ed3fb3b607156465b3e0de2dc"
+                .to_string(),
+            latest_block_index: 7,
+            node_identity: None,
+            legacy: false,
+        };
+        let decoded = decode::<Handshake>(&encode(&handshake)).unwrap();
+        assert_eq!(decoded.chain_id, 1);
+        assert_eq!(decoded.capabilities, handshake.capabilities);
+        assert_eq!(decoded.latest_block_index, 7);
+        assert!(decoded.node_identity.is_none());
+
+        let node_identity = NodeIdentity {
+            authority: "0x9b6e8afff8329743cac73dbef83ca3cbf9a74c20".to_string(),
+            signature_r: "4b0cb46ae73d852bb75653ed1f1710676b0b736cd33aefc0c96e6e11417a4c34"
+                .to_string(),
+            signature_s: "496086bdc703286c0727c59e07b727cadfc2fe7b9c061149e4a86e726ed23910"
+                .to_string(),
+            signature_v: 28,
+        };
+        handshake.node_identity = Some(node_identity.clone());
+        let decoded = decode::<Handshake>(&encode(&handshake)).unwrap();
+        assert_eq!(decoded.node_identity, Some(node_identity));
+    }
+
+    #[test]
+    fn test_decode_legacy_handshake() {
+        // Handshake request as sent by a node from before chain ids, type byte then RLP payload
+        let message = hex::decode(concat!(
+            "01f885b84030303836303935363438653331363064306466613564343062646634363933643861303064",
+            "373765643366623362363037313536343635623365306465326462b84032303836303935363438653331",
+            "363064306466613564343062646634363933643861303064373765643366623362363037313536343635",
+            "62336530646532646307",
+        ))
+        .unwrap();
+        let envelope = Envelope::decode(&message).unwrap();
+        assert_eq!(envelope.version, 1);
+
+        let handshake = decode_handshake(&envelope.payload, envelope.version).unwrap();
+        assert!(handshake.legacy);
+        assert_eq!(
+            handshake.genesis_block_hash,
+            "0086095648e3160d0dfa5d40bdf4693d8a00d77ed3fb3b607156465b3e0de2db"
+        );
+        assert_eq!(
+            handshake.latest_block_hash,
+            "2086095648e3160d0dfa5d40bdf4693d8a00d77ed3fb3b607156465b3e0de2dc"
+        );
+        assert_eq!(handshake.latest_block_index, 7);
+        assert_eq!(encode_legacy_handshake(&handshake), envelope.payload);
+
+        // The legacy layout only exists in version 1 messages
+        assert!(decode_handshake(&envelope.payload, 2).is_err());
+
+        // A full handshake cannot pass as legacy by leaving out the chain id
+        let mut full_handshake = decode_handshake(&envelope.payload, 1).unwrap();
+        full_handshake.chain_id = 0;
+        assert!(decode_handshake(&encode(&full_handshake), 1).is_err());
+        assert!(decode_handshake(&encode(&full_handshake), 2).is_err());
+    }
+
+    #[test]
+    fn test_encode_decode_pooled_transactions() {
+        let transactions = fee_paying_transactions();
+        let hashes: Vec<String> = transactions.iter().map(|tx| tx.hash.clone()).collect();
+
+        let get_hashes = decode::<GetPooledTransactionHashes>(&encode(&GetPooledTransactionHashes {
+            limit: 4096,
+        }))
+        .unwrap();
+        assert_eq!(get_hashes.limit, 4096);
+
+        let decoded = decode::<PooledTransactionHashes>(&encode(&PooledTransactionHashes {
+            hashes: hashes.clone(),
+        }))
+        .unwrap();
+        assert_eq!(decoded.hashes, hashes);
+
+        let decoded = decode::<GetPooledTransactions>(&encode(&GetPooledTransactions {
+            hashes: hashes.clone(),
+        }))
+        .unwrap();
+        assert_eq!(decoded.hashes, hashes);
+
+        let decoded = decode::<PooledTransactions>(&encode(&PooledTransactions { transactions }))
+            .unwrap();
+        let decoded_hashes: Vec<String> = decoded.transactions.iter().map(|tx| tx.hash.clone()).collect();
+        assert_eq!(decoded_hashes, hashes);
+        assert!(decoded.transactions.iter().map(|tx| tx.fee).eq([3, 5]));
+    }
 }
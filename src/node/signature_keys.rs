@@ -3,10 +3,49 @@ use rand::rngs::OsRng;
 use secp256k1::{
     ecdsa::RecoverableSignature, ecdsa::RecoveryId, Message, PublicKey, Secp256k1, SecretKey,
 };
+use serde::{Deserialize, Serialize};
 use sha3::{Digest, Keccak256};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SignatureVersion {
+    // Keccak-256 of the ASCII hex hash string
+    #[default]
+    Legacy,
+    // The 32-byte hash digest itself
+    Digest,
+    // EIP-712 typed data (EIP-191 version 0x01)
+    TypedData,
+    // Blocks only: the 32-byte hash digest, where the hash also commits to the block timestamp
+    TimestampedDigest,
+}
+
+impl SignatureVersion {
+    pub fn as_byte(&self) -> u8 {
+        match self {
+            SignatureVersion::Legacy => 0x00,
+            SignatureVersion::Digest => 0x01,
+            SignatureVersion::TypedData => 0x02,
+            SignatureVersion::TimestampedDigest => 0x03,
+        }
+    }
+
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0x00 => Some(SignatureVersion::Legacy),
+            0x01 => Some(SignatureVersion::Digest),
+            0x02 => Some(SignatureVersion::TypedData),
+            0x03 => Some(SignatureVersion::TimestampedDigest),
+            _ => None,
+        }
+    }
+
+    // Legacy signatures are only valid below the digest signature activation height
+    pub fn is_allowed_at(&self, block_index: usize, digest_signature_activation_height: usize) -> bool {
+        *self != SignatureVersion::Legacy || block_index < digest_signature_activation_height
+    }
+}
+
 #[derive(Debug)]
-#[allow(dead_code)]
 pub struct SignatureKeys {
     pub secret_key: String,
     pub public_key: String,
@@ -15,7 +54,6 @@ pub struct SignatureKeys {
 
 impl SignatureKeys {
 
-    #[allow(dead_code)]
     pub fn generate_new_keypair() -> Self {
         let secp = Secp256k1::new();
         let mut rng = OsRng::default();
@@ -29,6 +67,24 @@ impl SignatureKeys {
         }
     }
 
+    pub fn address_from_secret_key(secret_key: &str) -> Result<String, String> {
+        let public_key = Self::parse_public_key(secret_key)?;
+        Ok(Self::derive_address(&public_key))
+    }
+
+    pub fn public_key_from_secret_key(secret_key: &str) -> Result<String, String> {
+        let public_key = Self::parse_public_key(secret_key)?;
+        Ok(hex::encode(public_key.serialize_uncompressed()))
+    }
+
+    fn parse_public_key(secret_key: &str) -> Result<PublicKey, String> {
+        let secret_key_bytes = hex::decode(secret_key.trim_start_matches("0x"))
+            .map_err(|_| "Invalid hex in secret key".to_string())?;
+        let secret_key = SecretKey::from_slice(&secret_key_bytes)
+            .map_err(|_| "Invalid secp256k1 secret key".to_string())?;
+        Ok(PublicKey::from_secret_key(&Secp256k1::new(), &secret_key))
+    }
+
     fn derive_address(public_key: &PublicKey) -> String {
         let serialized_pubkey = public_key.serialize_uncompressed();
         let mut hasher = Keccak256::new();
@@ -39,20 +95,36 @@ impl SignatureKeys {
         address_key
     }
 
+    pub fn keccak256(data: &[u8]) -> [u8; 32] {
+        let mut hasher = Keccak256::new();
+        hasher.update(data);
+        hasher.finalize().into()
+    }
+
+    // Decode a hex hash string (with or without 0x prefix) into a 32-byte digest
+    pub fn digest_from_hex(hash: &str) -> Result<[u8; 32], String> {
+        let bytes = hex::decode(hash.trim_start_matches("0x"))
+            .map_err(|_| format!("Invalid hex in hash '{}'", hash))?;
+        bytes
+            .try_into()
+            .map_err(|_| format!("Hash '{}' is not 32 bytes long", hash))
+    }
+
+    #[allow(dead_code)]
     pub fn sign(secret_key: &str, data: &[u8]) -> (String, String, i32) {
+        // Create a message hash (Keccak-256 of the data)
+        let message_hash = Self::keccak256(data);
+        Self::sign_digest(secret_key, &message_hash)
+    }
+
+    pub fn sign_digest(secret_key: &str, digest: &[u8; 32]) -> (String, String, i32) {
         let secp = Secp256k1::new();
 
         let secret_key_bytes = hex::decode(secret_key).unwrap();
         let secret_key = SecretKey::from_slice(&secret_key_bytes).unwrap();
 
-        // Create a message hash (Keccak-256 of the data)
-        let mut hasher = Keccak256::new();
-        hasher.update(data);
-        let message_hash = hasher.finalize();
-
         // Create a message object for secp256k1
-        let message = Message::from_digest_slice(&message_hash)
-            .expect("Message could not be created from hash");
+        let message = Message::from_digest(*digest);
 
         // Sign the message
         let recoverable_sig = secp.sign_ecdsa_recoverable(&message, &secret_key);
@@ -68,6 +140,7 @@ impl SignatureKeys {
         (r, s, v)
     }
 
+    #[allow(dead_code)]
     pub fn verify(
         derive_address: &str,
         data: &[u8],
@@ -75,12 +148,35 @@ impl SignatureKeys {
         s: &str,
         v: i32,
     ) -> Result<bool, String> {
+        let recovered_address = Self::recover_address(data, r, s, v)?;
+        Ok(recovered_address == derive_address)
+    }
+
+    pub fn verify_digest(
+        derive_address: &str,
+        digest: &[u8; 32],
+        r: &str,
+        s: &str,
+        v: i32,
+    ) -> Result<bool, String> {
+        let recovered_address = Self::recover_address_from_digest(digest, r, s, v)?;
+        Ok(recovered_address == derive_address)
+    }
+
+    #[allow(dead_code)]
+    pub fn recover_address(data: &[u8], r: &str, s: &str, v: i32) -> Result<String, String> {
+        let message_hash = Self::keccak256(data);
+        Self::recover_address_from_digest(&message_hash, r, s, v)
+    }
+
+    pub fn recover_address_from_digest(
+        digest: &[u8; 32],
+        r: &str,
+        s: &str,
+        v: i32,
+    ) -> Result<String, String> {
         let secp = Secp256k1::new();
-        let mut hasher = Keccak256::new();
-        hasher.update(data);
-        let message_hash = hasher.finalize();
-        let message = Message::from_digest_slice(&message_hash)
-            .map_err(|_| "Message could not be created from hash".to_string())?;
+        let message = Message::from_digest(*digest);
 
         let sig_r = Vec::from_hex(r).map_err(|_| "Invalid hex in r".to_string())?;
         let sig_s = Vec::from_hex(s).map_err(|_| "Invalid hex in s".to_string())?;
@@ -91,10 +187,7 @@ impl SignatureKeys {
             .map_err(|_| "Valid signature could not be created".to_string())?;
 
         match secp.recover_ecdsa(&message, &recoverable_sig) {
-            Ok(recovered_public_key) => {
-                let derived_address = Self::derive_address(&recovered_public_key);
-                Ok(derived_address == derive_address)
-            }
+            Ok(recovered_public_key) => Ok(Self::derive_address(&recovered_public_key)),
             Err(_) => Err("Public key could not be recovered".to_string()),
         }
     }
@@ -173,4 +266,48 @@ mod tests {
             ),
         }
     }
+
+    #[test]
+    fn test_keys_from_secret_key() {
+        let keys = SignatureKeys::generate_new_keypair();
+
+        assert_eq!(
+            SignatureKeys::address_from_secret_key(&keys.secret_key).unwrap(),
+            keys.address_key
+        );
+        assert_eq!(
+            SignatureKeys::public_key_from_secret_key(&keys.secret_key).unwrap(),
+            keys.public_key
+        );
+        assert!(SignatureKeys::address_from_secret_key("not-a-key").is_err());
+    }
+
+    #[test]
+    fn test_recover_address() {
+        let keys = SignatureKeys::generate_new_keypair();
+        let data = b"Blockchain technology";
+
+        let (r, s, v) = SignatureKeys::sign(&keys.secret_key, data);
+        let recovered_address = SignatureKeys::recover_address(data, &r, &s, v)
+            .expect("Failed to recover address from signature");
+
+        assert_eq!(recovered_address, keys.address_key);
+    }
+
+    #[test]
+    fn test_sign_digest_and_recover_address() {
+        let keys = SignatureKeys::generate_new_keypair();
+        let digest = SignatureKeys::keccak256(b"Blockchain technology");
+
+        let (r, s, v) = SignatureKeys::sign_digest(&keys.secret_key, &digest);
+        let recovered_address = SignatureKeys::recover_address_from_digest(&digest, &r, &s, v)
+            .expect("Failed to recover address from signature");
+        assert_eq!(recovered_address, keys.address_key);
+
+        // Signing the digest is not the same as signing the hex string of the digest
+        let hex_digest = hex::encode(digest);
+        let legacy_address = SignatureKeys::recover_address(hex_digest.as_bytes(), &r, &s, v)
+            .expect("Failed to recover address from signature");
+        assert_ne!(legacy_address, keys.address_key);
+    }
 }
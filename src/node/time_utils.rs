@@ -1,8 +1,11 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub fn get_current_timespan() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap()
         .as_secs()
-}
\ No newline at end of file
+}
+pub fn get_current_duration() -> Duration {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap()
+}
@@ -1,7 +1,13 @@
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
-use super::{complain_arrival::ComplainArrival, confirm_arrival::ConfirmArrival, ride_acceptance::RideAcceptance, ride_cancel::RideCancel, ride_offer::RideOffer, ride_pay::RidePay, ride_request::RideRequest, transfer::Transfer};
+use super::{
+    complain_arrival::ComplainArrival, confirm_arrival::ConfirmArrival,
+    propose_validator::ProposeValidator, remove_validator::RemoveValidator,
+    report_equivocation::ReportEquivocation, ride_acceptance::RideAcceptance,
+    ride_cancel::RideCancel, ride_offer::RideOffer, ride_pay::RidePay, ride_request::RideRequest,
+    transfer::Transfer, vote_validator::VoteValidator,
+};
 
 
 
@@ -16,6 +22,10 @@ pub enum FunctionCall {
     RideCancel(RideCancel),
     ConfirmArrival(ConfirmArrival),
     ComplainArrival(ComplainArrival),
+    ProposeValidator(ProposeValidator),
+    VoteValidator(VoteValidator),
+    RemoveValidator(RemoveValidator),
+    ReportEquivocation(ReportEquivocation),
 }
 
 impl fmt::Display for FunctionCall {
@@ -29,6 +39,10 @@ impl fmt::Display for FunctionCall {
             FunctionCall::RideCancel(args) => write!(f, "RideCancel: {:?}", args),
             FunctionCall::ConfirmArrival(args) => write!(f, "ConfirmArrival: {:?}", args),
             FunctionCall::ComplainArrival(args) => write!(f, "ComplainArrival: {:?}", args),
+            FunctionCall::ProposeValidator(args) => write!(f, "ProposeValidator: {:?}", args),
+            FunctionCall::VoteValidator(args) => write!(f, "VoteValidator: {:?}", args),
+            FunctionCall::RemoveValidator(args) => write!(f, "RemoveValidator: {:?}", args),
+            FunctionCall::ReportEquivocation(args) => write!(f, "ReportEquivocation: {:?}", args),
         }
     }
 }
\ No newline at end of file
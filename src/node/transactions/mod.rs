@@ -1,6 +1,10 @@
 pub mod complain_arrival;
 pub mod confirm_arrival;
 pub mod function_call;
+pub mod propose_validator;
+pub mod raw_transaction;
+pub mod remove_validator;
+pub mod report_equivocation;
 pub mod ride_acceptance;
 pub mod ride_cancel;
 pub mod ride_offer;
@@ -9,3 +13,4 @@ pub mod ride_request;
 pub mod transaction;
 pub mod transaction_pool;
 pub mod transfer;
+pub mod vote_validator;
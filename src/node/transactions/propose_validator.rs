@@ -0,0 +1,59 @@
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+use serde::{Deserialize, Serialize};
+
+use crate::node::database::Database;
+use crate::node::validator_set::{ValidatorChange, ValidatorProposal, ValidatorSets};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProposeValidator {
+    pub validator: String,
+}
+
+impl ProposeValidator {
+    pub fn verify_state(&self, from: &str, db: &Database) -> Result<(), String> {
+        let authorities = ValidatorSets::next_block_authorities(db)?;
+        if !authorities.iter().any(|authority| authority == from) {
+            return Err(format!(
+                "Only authorities can propose validators, '{}' is not one.",
+                from
+            ));
+        }
+        if authorities.contains(&self.validator) {
+            return Err(format!("'{}' is already an authority.", self.validator));
+        }
+        Ok(())
+    }
+
+    pub fn state_transaction(
+        &self,
+        from: &str,
+        tx_hash: &str,
+    ) -> Vec<Option<(Vec<u8>, Vec<u8>)>> {
+        ValidatorProposal {
+            change: ValidatorChange::Add,
+            validator: self.validator.clone(),
+            proposer: from.to_string(),
+            enacted: false,
+        }
+        .state_transaction(tx_hash)
+    }
+}
+
+impl Encodable for ProposeValidator {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(1);
+        stream.append(&self.validator);
+    }
+}
+
+impl Decodable for ProposeValidator {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if !rlp.is_list() || rlp.item_count()? != 1 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        Ok(ProposeValidator {
+            validator: rlp.val_at(0)?,
+        })
+    }
+}
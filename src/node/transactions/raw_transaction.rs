@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+
+use crate::node::signature_keys::SignatureVersion;
+
+use super::function_call::FunctionCall;
+
+/// Signed transaction without an explicit `from`; the sender is recovered from the signature.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RawTransaction {
+    pub nonce: u64,
+    #[serde(default)]
+    pub fee: u64,
+    pub data: FunctionCall,
+    pub signature_r: String,
+    pub signature_s: String,
+    pub signature_v: i32,
+    #[serde(default)]
+    pub signature_version: SignatureVersion,
+    pub hash: String,
+}
@@ -0,0 +1,62 @@
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+use serde::{Deserialize, Serialize};
+
+use crate::node::database::Database;
+use crate::node::equivocation::EquivocationProof;
+use crate::node::validator_set::ValidatorSets;
+
+// Anyone can report an equivocation. The offender is removed from the validator set from
+// the next epoch on (see ValidatorSets::remove_offenders).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReportEquivocation {
+    pub proof: Box<EquivocationProof>,
+}
+
+impl ReportEquivocation {
+    pub fn verify_state(&self, db: &Database) -> Result<(), String> {
+        self.proof.verify(&ValidatorSets::get(db)?)?;
+
+        let authorities = ValidatorSets::authorities_at(db, self.proof.block_index())?;
+        if !authorities.contains(self.proof.offender()) {
+            return Err(format!(
+                "'{}' was not an authority at block {}.",
+                self.proof.offender(),
+                self.proof.block_index()
+            ));
+        }
+        if self.proof.is_reported(db) {
+            return Err(format!(
+                "The equivocation of '{}' at block {} is already reported.",
+                self.proof.offender(),
+                self.proof.block_index()
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn state_transaction(&self, tx_hash: &str) -> Vec<Option<(Vec<u8>, Vec<u8>)>> {
+        vec![Some((
+            EquivocationProof::construct_report_key(self.proof.offender(), self.proof.block_index()),
+            tx_hash.as_bytes().to_vec(),
+        ))]
+    }
+}
+
+impl Encodable for ReportEquivocation {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(1);
+        stream.append(self.proof.as_ref());
+    }
+}
+
+impl Decodable for ReportEquivocation {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if !rlp.is_list() || rlp.item_count()? != 1 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        Ok(ReportEquivocation {
+            proof: Box::new(rlp.val_at(0)?),
+        })
+    }
+}
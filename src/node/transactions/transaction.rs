@@ -1,16 +1,22 @@
 use crate::node::{
     account_state::AccountState,
-    database::Database,
-    signature_keys::{self, SignatureKeys},
+    chain_spec::DEFAULT_CHAIN_ID,
+    database::{Database, StateUpdate},
+    rlp_encoding::{decode, encode},
+    signature_keys::{SignatureKeys, SignatureVersion},
+    typed_data::transaction_typed_data_hash,
 };
 
-use rlp::RlpStream;
+use rlp::{Rlp, RlpStream};
 use serde::{Deserialize, Serialize};
 use sha2::Digest;
 use sha3::Sha3_256;
 use std::vec;
 
-use super::{function_call::FunctionCall, transfer::Transfer};
+use super::{
+    function_call::FunctionCall, raw_transaction::RawTransaction, ride_offer::RideOffer,
+    transfer::Transfer,
+};
 
 const FROM_GENESIS: &str = "0xGENESIS";
 
@@ -19,28 +25,67 @@ pub struct Transaction {
     pub from: String,
     pub data: FunctionCall,
     pub nonce: u64,
+    #[serde(default)]
+    pub fee: u64,
     pub signature_r: String,
     pub signature_s: String,
     pub signature_v: i32,
+    #[serde(default)]
+    pub signature_version: SignatureVersion,
     pub hash: String,
 }
 
 impl Transaction {
     pub fn new_transaction(from: String, nonce: u64, function_call: FunctionCall) -> Transaction {
+        Self::new_transaction_with_fee(from, nonce, 0, function_call)
+    }
+
+    pub fn new_transaction_with_fee(
+        from: String,
+        nonce: u64,
+        fee: u64,
+        function_call: FunctionCall,
+    ) -> Transaction {
         let mut transaction = Transaction {
             hash: String::new(),
             signature_r: String::new(),
             signature_s: String::new(),
             signature_v: 0,
+            signature_version: SignatureVersion::default(),
             from: from,
             nonce: nonce,
+            fee,
             data: function_call,
         };
         transaction.hash = transaction.calculate_hash();
         transaction
     }
 
-    pub fn new_genesis_transactions() -> Vec<Transaction> {
+    // A zero value transfer to the sender itself, used to take the place of a pending
+    // transaction with the same nonce
+    pub fn new_cancel_transaction(from: String, nonce: u64, fee: u64) -> Transaction {
+        let to = from.clone();
+        Self::new_transaction_with_fee(
+            from,
+            nonce,
+            fee,
+            FunctionCall::Transfer(Transfer { to, value: 0 }),
+        )
+    }
+
+    pub fn encoded_size(&self) -> usize {
+        encode(self).len()
+    }
+
+    pub fn is_cancel(&self) -> bool {
+        match &self.data {
+            FunctionCall::Transfer(transfer) => transfer.value == 0 && transfer.to == self.from,
+            _ => false,
+        }
+    }
+
+    // The built-in genesis balances followed by the chain spec's genesis allocations
+    pub fn new_genesis_transactions(allocations: &[(String, u64)]) -> Vec<Transaction> {
         let tx1 = Self::new_transaction(
             FROM_GENESIS.to_string(),
             0,
@@ -82,16 +127,31 @@ impl Transaction {
             }),
         );
 
-        vec![tx1, tx2, tx3, tx4, tx5]
+        let mut transactions = vec![tx1, tx2, tx3, tx4, tx5];
+        transactions.extend(allocations.iter().map(|(to, value)| {
+            Self::new_transaction(
+                FROM_GENESIS.to_string(),
+                0,
+                FunctionCall::Transfer(Transfer {
+                    to: to.clone(),
+                    value: *value,
+                }),
+            )
+        }));
+        transactions
     }
 
     fn calculate_hash(&self) -> String {
-        // Serialize only the unsigned transaction (from, nonce, data) using RLP
+        // Serialize only the unsigned transaction (from, nonce, data, fee) using RLP;
+        // a zero fee is left out so fee-less transactions keep their original hash
         let mut stream = RlpStream::new();
-        stream.begin_list(3);
+        stream.begin_list(if self.fee > 0 { 4 } else { 3 });
         stream.append(&self.from);
         stream.append(&self.nonce);
         stream.append(&self.data);
+        if self.fee > 0 {
+            stream.append(&self.fee);
+        }
         let rlp_bytes = stream.out();
 
         // Initialize the SHA3-256 hasher
@@ -105,43 +165,165 @@ impl Transaction {
 
     #[allow(dead_code)]
     pub fn sign(&mut self, secret_key: &str) {
-        let hash_bytes = self.hash.as_bytes();
-        let (r, s, v) = signature_keys::SignatureKeys::sign(secret_key, hash_bytes);
+        // Digest signatures do not depend on the chain id
+        self.sign_with_version(secret_key, SignatureVersion::Digest, DEFAULT_CHAIN_ID)
+            .expect("Failed to compute transaction signing digest");
+    }
+
+    pub fn sign_with_version(
+        &mut self,
+        secret_key: &str,
+        signature_version: SignatureVersion,
+        chain_id: u64,
+    ) -> Result<(), String> {
+        let digest = self.signing_digest(signature_version, chain_id)?;
+        let (r, s, v) = SignatureKeys::sign_digest(secret_key, &digest);
 
         self.signature_r = r;
         self.signature_s = s;
         self.signature_v = v;
+        self.signature_version = signature_version;
+        Ok(())
+    }
+
+    // Typed data signatures are bound to the chain id, the other versions only to the hash
+    fn signing_digest(&self, signature_version: SignatureVersion, chain_id: u64) -> Result<[u8; 32], String> {
+        match signature_version {
+            SignatureVersion::Legacy => Ok(SignatureKeys::keccak256(self.hash.as_bytes())),
+            SignatureVersion::Digest => SignatureKeys::digest_from_hex(&self.hash),
+            SignatureVersion::TypedData => {
+                transaction_typed_data_hash(chain_id, self.nonce, self.fee, &self.data)
+            }
+            SignatureVersion::TimestampedDigest => {
+                Err("Timestamped digest signatures are only supported for blocks".to_string())
+            }
+        }
     }
 
-    fn verify_signature(&self) -> Result<bool, String> {
-        let from_public_key = &self.from;
-        let data = self.hash.as_bytes();
+    pub fn recover_sender(&self, chain_id: u64) -> Result<String, String> {
+        let digest = self.signing_digest(self.signature_version, chain_id)?;
         let r = &self.signature_r;
         let s = &self.signature_s;
         let v = self.signature_v;
 
-        SignatureKeys::verify(from_public_key, data, r, s, v)
+        SignatureKeys::recover_address_from_digest(&digest, r, s, v)
     }
 
-    pub fn validate_transactions(
-        db: &Database,
-        transactions: &Vec<Transaction>,
-    ) -> Result<(), String> {
-        if transactions.is_empty() {
-            return Err("No transactions to validate.".to_string());
+    pub fn verify_signature(&self, chain_id: u64) -> Result<(), String> {
+        if self.hash != self.calculate_hash() {
+            return Err(format!(
+                "Verification failed: Hash does not match the content of transaction '{}'.",
+                self.hash
+            ));
+        }
+
+        let sender = self.recover_sender(chain_id)?;
+        if sender != self.from {
+            return Err(format!(
+                "Verification failed: Signature does not match for transaction from '{}', recovered sender: '{}'.",
+                self.from, sender
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn to_raw_transaction(&self) -> RawTransaction {
+        RawTransaction {
+            nonce: self.nonce,
+            fee: self.fee,
+            data: self.data.clone(),
+            signature_r: self.signature_r.clone(),
+            signature_s: self.signature_s.clone(),
+            signature_v: self.signature_v,
+            signature_version: self.signature_version,
+            hash: self.hash.clone(),
+        }
+    }
+
+    pub fn from_raw_transaction(raw_transaction: RawTransaction, chain_id: u64) -> Result<Transaction, String> {
+        let mut transaction = Transaction {
+            from: String::new(),
+            data: raw_transaction.data,
+            nonce: raw_transaction.nonce,
+            fee: raw_transaction.fee,
+            signature_r: raw_transaction.signature_r,
+            signature_s: raw_transaction.signature_s,
+            signature_v: raw_transaction.signature_v,
+            signature_version: raw_transaction.signature_version,
+            hash: raw_transaction.hash,
+        };
+        transaction.from = transaction.recover_sender(chain_id)?;
+
+        if transaction.hash != transaction.calculate_hash() {
+            return Err(format!(
+                "Raw transaction hash '{}' does not match its content signed by '{}'.",
+                transaction.hash, transaction.from
+            ));
         }
 
-        for tx in transactions.iter() {
-            tx.validate_transaction(&db)?;
+        Ok(transaction)
+    }
+
+    pub fn verify_signature_version(
+        &self,
+        block_index: usize,
+        digest_signature_activation_height: usize,
+    ) -> Result<(), String> {
+        if !self
+            .signature_version
+            .is_allowed_at(block_index, digest_signature_activation_height)
+        {
+            return Err(format!(
+                "Verification failed: Legacy signatures are not accepted from block {}, transaction '{}' was signed with {:?}.",
+                digest_signature_activation_height, self.hash, self.signature_version
+            ));
         }
 
         Ok(())
     }
 
-    pub fn validate_transaction(&self, db: &Database) -> Result<(), String> {
-        self.verify_signature()?;
+    // Accepts the raw format (sender recovered from the signature) and the legacy format with an explicit `from`
+    pub fn from_raw_bytes(bytes: &[u8], chain_id: u64) -> Result<Transaction, String> {
+        // With `from` the call data is the 7th item, without it the 6th; the signature version and fee are optional
+        let rlp = Rlp::new(bytes);
+        if let Err(e) = rlp.item_count() {
+            return Err(format!("Failed to decode RLP transaction: {:?}", e));
+        }
+        let has_from = rlp.at(6).map(|item| item.is_list()).unwrap_or(false);
+
+        if has_from {
+            decode::<Transaction>(bytes)
+                .map_err(|e| format!("Failed to decode RLP transaction: {:?}", e))
+        } else {
+            let raw_transaction = decode::<RawTransaction>(bytes)
+                .map_err(|e| format!("Failed to decode RLP raw transaction: {:?}", e))?;
+            Self::from_raw_transaction(raw_transaction, chain_id)
+        }
+    }
+
+    // Each transaction is validated against the state left by the ones before it in the block,
+    // so a sender can have consecutive nonces in the same block
+    pub fn validate_transactions(
+        db: &Database,
+        transactions: &Vec<Transaction>,
+        chain_id: u64,
+    ) -> Result<(), String> {
+        db.begin_staging();
+        let result = transactions.iter().try_for_each(|tx| {
+            tx.validate_transaction(db, chain_id)?;
+            tx.stage_state(db)
+        });
+        db.end_staging();
+
+        result
+    }
+
+    pub fn validate_transaction(&self, db: &Database, chain_id: u64) -> Result<(), String> {
+        self.verify_signature(chain_id)?;
         self.verify_nonce(db)?;
         self.verify_state(db)?;
+        self.verify_fee(db)?;
 
         Ok(())
     }
@@ -165,6 +347,33 @@ impl Transaction {
         }
     }
 
+    // The fee is burned, so the sender must afford it on top of what the call itself spends
+    fn verify_fee(&self, db: &Database) -> Result<(), String> {
+        if self.fee == 0 {
+            return Ok(());
+        }
+
+        let spent = match &self.data {
+            FunctionCall::Transfer(transfer) => transfer.value,
+            FunctionCall::RideAcceptance(ride_acceptance) => {
+                RideOffer::get_ride_offer(&ride_acceptance.ride_offer_transaction_hash, db)?
+                    .map(|ride_offer| ride_offer.fare)
+                    .unwrap_or(0)
+            }
+            _ => 0,
+        };
+
+        let balance = AccountState::get_current_state(&self.from, db).balance;
+        if balance < spent.saturating_add(self.fee) {
+            return Err(format!(
+                "Verification failed: Insufficient balance for fee. From: {} Required: {} + fee {}, Available: {}",
+                self.from, spent, self.fee, balance
+            ));
+        }
+
+        Ok(())
+    }
+
     fn verify_state(&self, db: &Database) -> Result<(), String> {
         match &self.data {
             FunctionCall::Transfer(transfer) => transfer.verify_state(&self.from, db),
@@ -177,10 +386,25 @@ impl Transaction {
             FunctionCall::RideCancel(ride_cancel) => ride_cancel.verify_state(&self.from, db),
             FunctionCall::ConfirmArrival(confirm_arrival) => confirm_arrival.verify_state(db),
             FunctionCall::ComplainArrival(complain_arrival) => complain_arrival.verify_state(db),
+            FunctionCall::ProposeValidator(propose_validator) => {
+                propose_validator.verify_state(&self.from, db)
+            }
+            FunctionCall::VoteValidator(vote_validator) => vote_validator.verify_state(&self.from, db),
+            FunctionCall::RemoveValidator(remove_validator) => {
+                remove_validator.verify_state(&self.from, db)
+            }
+            FunctionCall::ReportEquivocation(report_equivocation) => report_equivocation.verify_state(db),
         }
     }
 
-    pub fn state_transaction(&self, db: &Database) -> Vec<Option<(Vec<u8>, Vec<u8>)>> {
+    pub fn stage_state(&self, db: &Database) -> Result<(), String> {
+        for (key, value) in self.state_transaction(db)?.into_iter().flatten() {
+            db.stage("state", &key, &value);
+        }
+        Ok(())
+    }
+
+    pub fn state_transaction(&self, db: &Database) -> Result<Vec<Option<StateUpdate>>, String> {
         let mut states = match &self.data {
             FunctionCall::Transfer(transfer) => transfer.state_transaction(&self.from, db),
             FunctionCall::RideRequest(ride_request) => {
@@ -198,8 +422,24 @@ impl Transaction {
             FunctionCall::ComplainArrival(complain_arrival) => {
                 complain_arrival.state_transaction(db)
             }
+            FunctionCall::ProposeValidator(propose_validator) => {
+                propose_validator.state_transaction(&self.from, &self.hash)
+            }
+            FunctionCall::VoteValidator(vote_validator) => {
+                vote_validator.state_transaction(&self.from)
+            }
+            FunctionCall::RemoveValidator(remove_validator) => {
+                remove_validator.state_transaction(&self.from, &self.hash)
+            }
+            FunctionCall::ReportEquivocation(report_equivocation) => {
+                report_equivocation.state_transaction(&self.hash)
+            }
         };
 
+        if self.fee > 0 {
+            AccountState::apply_balance_change(&mut states, &self.from, -(self.fee as i64), db)?;
+        }
+
         match AccountState::increase_account_nonce_key(&self.from, db) {
             Ok((nonce_key, nonce_serialized)) => {
                 states.push(Some((nonce_key, nonce_serialized)));
@@ -209,6 +449,6 @@ impl Transaction {
             }
         }
 
-        states
+        Ok(states)
     }
 }
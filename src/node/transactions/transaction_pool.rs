@@ -1,49 +1,405 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+
+use tracing::{info, warn};
+
+use crate::node::account_state::AccountState;
+use crate::node::blocks::block::BlockLimits;
 use crate::node::database::Database;
+use crate::node::metric;
 use crate::node::transactions::transaction::Transaction;
 
-pub struct TransactionPool {}
+#[derive(Debug, Clone, Copy)]
+pub struct TransactionPoolConfig {
+    pub max_transactions: usize,
+    pub max_transactions_per_account: usize,
+}
+
+impl Default for TransactionPoolConfig {
+    fn default() -> Self {
+        TransactionPoolConfig {
+            max_transactions: 4096,
+            max_transactions_per_account: 16,
+        }
+    }
+}
+
+struct PooledTransaction {
+    transaction: Transaction,
+    // Arrival order, used to break fee ties
+    sequence: u64,
+}
+
+// In-memory pool with one nonce-ordered queue per sender. Transactions whose nonce is
+// ahead of the sender's next nonce are held until the gap is filled.
+pub struct TransactionPool {
+    config: TransactionPoolConfig,
+    // Typed data signatures are checked against it
+    chain_id: u64,
+    accounts: HashMap<String, BTreeMap<u64, PooledTransaction>>,
+    hashes: HashMap<String, (String, u64)>,
+    // Next nonce of each sender in the committed state, refreshed on add and after each block
+    next_nonces: HashMap<String, u64>,
+    // Transactions executable in the next block, kept up to date for the metrics
+    pending: usize,
+    next_sequence: u64,
+}
 
 impl TransactionPool {
-    pub fn add_transaction(db: &Database, transaction: &Transaction) -> Result<(), String> {
-        transaction.validate_transaction(&db)?;
-        let key = Self::construct_tx_pool_key(&transaction.hash);
-        let value = serde_json::to_string(&transaction).unwrap().into_bytes();
+    pub fn new(config: TransactionPoolConfig, chain_id: u64) -> Self {
+        TransactionPool {
+            config,
+            chain_id,
+            accounts: HashMap::new(),
+            hashes: HashMap::new(),
+            next_nonces: HashMap::new(),
+            pending: 0,
+            next_sequence: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    pub fn add_transaction(
+        &mut self,
+        db: &Database,
+        transaction: &Transaction,
+    ) -> Result<(), String> {
+        if self.hashes.contains_key(&transaction.hash) {
+            return Err(format!(
+                "Transaction '{}' is already in the transaction pool.",
+                transaction.hash
+            ));
+        }
 
-        db.put("tx_pool", &key, &value)
+        let account_nonce = AccountState::get_current_nonce(&transaction.from, db)?;
+        if transaction.nonce <= account_nonce {
+            return Err(format!(
+                "Transaction nonce {} from '{}' is already used. Expected at least: {}.",
+                transaction.nonce,
+                transaction.from,
+                account_nonce + 1
+            ));
+        }
+
+        // Only the next nonce can be checked against the current state, later ones are
+        // validated once the transactions before them are in a block
+        if transaction.nonce == account_nonce + 1 {
+            transaction.validate_transaction(db, self.chain_id)?;
+        } else {
+            transaction.verify_signature(self.chain_id)?;
+        }
+
+        if let Some(queue) = self.accounts.get(&transaction.from) {
+            if let Some(pending) = queue.get(&transaction.nonce) {
+                let replaced = pending.transaction.clone();
+                return self.replace(&replaced, transaction);
+            }
+            if queue.len() >= self.config.max_transactions_per_account {
+                return Err(format!(
+                    "Account '{}' already has {} pending transactions.",
+                    transaction.from,
+                    queue.len()
+                ));
+            }
+        }
+
+        if self.len() >= self.config.max_transactions {
+            self.evict_for(transaction)?;
+        }
+
+        self.insert(transaction.clone());
+        self.set_next_nonce(&transaction.from, account_nonce + 1);
+        Ok(())
+    }
+
+    pub fn remove_transaction(&mut self, tx_hash: &str) -> Option<Transaction> {
+        let (from, nonce) = self.hashes.remove(tx_hash)?;
+        let executable = self.executable(&from).len();
+        let queue = self.accounts.get_mut(&from)?;
+        let pooled = queue.remove(&nonce);
+        if queue.is_empty() {
+            self.accounts.remove(&from);
+        }
+        self.update_pending(&from, executable);
+        pooled.map(|pooled| pooled.transaction)
+    }
+
+    // Executable transactions ordered by fee, each sender's in nonce order. Block transactions
+    // are executed in order, so a sender's consecutive nonces can share a block.
+    pub fn pending_transactions(&self) -> Vec<Transaction> {
+        let mut runs: Vec<Vec<&PooledTransaction>> = self
+            .accounts
+            .keys()
+            .map(|from| self.executable(from).into_iter().rev().collect())
+            .collect();
+
+        // The best next transaction of each sender, highest fee and then earliest arrival first
+        let mut heads: BinaryHeap<(u64, Reverse<u64>, usize)> = runs
+            .iter()
+            .enumerate()
+            .filter_map(|(index, run)| {
+                run.last()
+                    .map(|pooled| (pooled.transaction.fee, Reverse(pooled.sequence), index))
+            })
+            .collect();
+
+        let mut pending = Vec::new();
+        while let Some((_, _, index)) = heads.pop() {
+            let Some(pooled) = runs[index].pop() else {
+                continue;
+            };
+            pending.push(pooled.transaction.clone());
+            if let Some(next) = runs[index].last() {
+                heads.push((next.transaction.fee, Reverse(next.sequence), index));
+            }
+        }
+        pending
     }
 
-    #[allow(dead_code)]
-    pub fn remove_transaction(db: &Database, tx_hash: &str) -> Result<(), String> {
-        let key = Self::construct_tx_pool_key(tx_hash);
-        match db.delete("tx_pool", &key) {
-            Ok(_) => Ok(()),
-            Err(e) => Err(format!("Failed to remove transaction: {}", e)),
+    // Fills a block from the pending transactions within the limits. Transactions that fail
+    // validation are evicted instead of failing the block, ones that do not fit wait for the next.
+    pub fn block_transactions(&mut self, db: &Database, limits: &BlockLimits) -> Vec<Transaction> {
+        let mut transactions = Vec::new();
+        let mut size = 0;
+        let mut invalid = Vec::new();
+        // Senders whose later nonces cannot go in without the one left out
+        let mut skipped = HashSet::new();
+
+        db.begin_staging();
+        for transaction in self.pending_transactions() {
+            if transactions.len() >= limits.max_transactions {
+                break;
+            }
+            if skipped.contains(&transaction.from) {
+                continue;
+            }
+            if let Err(e) = transaction.validate_transaction(db, self.chain_id) {
+                skipped.insert(transaction.from.clone());
+                invalid.push((transaction.hash, e));
+                continue;
+            }
+            let transaction_size = transaction.encoded_size();
+            if size + transaction_size > limits.max_bytes {
+                skipped.insert(transaction.from.clone());
+                continue;
+            }
+            if let Err(e) = transaction.stage_state(db) {
+                skipped.insert(transaction.from.clone());
+                invalid.push((transaction.hash, e));
+                continue;
+            }
+            size += transaction_size;
+            transactions.push(transaction);
+        }
+        db.end_staging();
+
+        for (tx_hash, reason) in invalid {
+            self.remove_transaction(&tx_hash);
+            info!(
+                "Dropped transaction '{}' from the pool: {}",
+                tx_hash, reason
+            );
         }
+        transactions
     }
 
-    pub fn construct_tx_pool_key(tx_hash: &str) -> Vec<u8> {
-        format!("tx_pool_{}", tx_hash).into_bytes()
+    pub fn get_transactions(&self) -> Vec<Transaction> {
+        let mut senders: Vec<&String> = self.accounts.keys().collect();
+        senders.sort();
+        senders
+            .into_iter()
+            .flat_map(|from| self.accounts[from].values())
+            .map(|pooled| pooled.transaction.clone())
+            .collect()
     }
 
-    pub fn get_transactions(db: &Database) -> Result<Vec<Transaction>, String> {
-        match db.get_keys_values_by_cf_name("tx_pool") {
-            Ok(entries) => {
-                let mut transactions = Vec::new();
+    // Hashes in the order of get_transactions, so a peer fetching them adds each sender's
+    // transactions in nonce order
+    pub fn get_transaction_hashes(&self, limit: usize) -> Vec<String> {
+        let mut senders: Vec<&String> = self.accounts.keys().collect();
+        senders.sort();
+        senders
+            .into_iter()
+            .flat_map(|from| self.accounts[from].values())
+            .take(limit)
+            .map(|pooled| pooled.transaction.hash.clone())
+            .collect()
+    }
 
-                for (_key, value) in entries {
-                    match serde_json::from_slice::<Transaction>(&value) {
-                        Ok(transaction) => {
-                            transactions.push(transaction);
-                        }
-                        Err(e) => {
-                            return Err(format!("Failed to deserialize transaction: {}", e));
-                        }
-                    }
+    pub fn get_transaction(&self, tx_hash: &str) -> Option<Transaction> {
+        let (from, nonce) = self.hashes.get(tx_hash)?;
+        self.accounts
+            .get(from)?
+            .get(nonce)
+            .map(|pooled| pooled.transaction.clone())
+    }
+
+    pub fn contains(&self, tx_hash: &str) -> bool {
+        self.hashes.contains_key(tx_hash)
+    }
+
+    // Post-commit maintenance: drops every transaction whose nonce has been used, which
+    // includes the ones just committed, and revalidates each sender's next transaction against
+    // the new state. Transactions behind a dropped one stay queued until its nonce is sent again.
+    pub fn prune(&mut self, db: &Database) {
+        // Senders whose transactions all left the pool
+        self.next_nonces
+            .retain(|from, _| self.accounts.contains_key(from));
+        if self.is_empty() {
+            return;
+        }
+
+        let mut dropped = Vec::new();
+        let mut next_nonces = Vec::new();
+        for (from, queue) in self.accounts.iter() {
+            let account_nonce = match AccountState::get_current_nonce(from, db) {
+                Ok(nonce) => nonce,
+                Err(e) => {
+                    warn!(
+                        "Failed to get the nonce of '{}' during pool maintenance: {}",
+                        from, e
+                    );
+                    continue;
                 }
+            };
+            next_nonces.push((from.clone(), account_nonce + 1));
+            for (nonce, pooled) in queue.range(..=account_nonce) {
+                dropped.push((
+                    pooled.transaction.hash.clone(),
+                    format!("nonce {} from '{}' is used", nonce, from),
+                ));
+            }
+            if let Some(pooled) = queue.get(&(account_nonce + 1)) {
+                if let Err(e) = pooled.transaction.validate_transaction(db, self.chain_id) {
+                    dropped.push((pooled.transaction.hash.clone(), e));
+                }
+            }
+        }
 
-                Ok(transactions)
+        for (from, next_nonce) in next_nonces {
+            self.set_next_nonce(&from, next_nonce);
+        }
+        for (tx_hash, reason) in dropped {
+            if self.remove_transaction(&tx_hash).is_some() {
+                info!(
+                    "Dropped transaction '{}' from the pool: {}",
+                    tx_hash, reason
+                );
             }
-            Err(e) => Err(format!("Failed to retrieve transactions: {}", e)),
         }
     }
+
+    // A pending transaction can be replaced by one paying a higher fee, or cancelled by a
+    // cancel transaction paying at least the same fee
+    fn replace(&mut self, replaced: &Transaction, transaction: &Transaction) -> Result<(), String> {
+        let allowed = transaction.fee > replaced.fee
+            || (transaction.is_cancel() && transaction.fee >= replaced.fee);
+        if !allowed {
+            return Err(format!(
+                "A transaction from '{}' with nonce {} is already pending. A replacement must pay a fee higher than {}.",
+                transaction.from, transaction.nonce, replaced.fee
+            ));
+        }
+
+        self.remove_transaction(&replaced.hash);
+        self.insert(transaction.clone());
+        metric::TX_POOL_REPLACED.inc();
+        info!(
+            "Replaced transaction '{}' with '{}' for nonce {} from '{}'.",
+            replaced.hash, transaction.hash, transaction.nonce, transaction.from
+        );
+        Ok(())
+    }
+
+    fn insert(&mut self, transaction: Transaction) {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+
+        let from = transaction.from.clone();
+        let executable = self.executable(&from).len();
+        self.hashes.insert(
+            transaction.hash.clone(),
+            (transaction.from.clone(), transaction.nonce),
+        );
+        self.accounts
+            .entry(transaction.from.clone())
+            .or_default()
+            .insert(
+                transaction.nonce,
+                PooledTransaction {
+                    transaction,
+                    sequence,
+                },
+            );
+        self.update_pending(&from, executable);
+    }
+
+    fn set_next_nonce(&mut self, from: &str, next_nonce: u64) {
+        let executable = self.executable(from).len();
+        self.next_nonces.insert(from.to_string(), next_nonce);
+        self.update_pending(from, executable);
+    }
+
+    // The sender's transactions with consecutive nonces from its next nonce
+    fn executable(&self, from: &str) -> Vec<&PooledTransaction> {
+        let (Some(queue), Some(&next_nonce)) =
+            (self.accounts.get(from), self.next_nonces.get(from))
+        else {
+            return Vec::new();
+        };
+        queue
+            .range(next_nonce..)
+            .zip(next_nonce..)
+            .take_while(|((nonce, _), expected)| **nonce == *expected)
+            .map(|((_, pooled), _)| pooled)
+            .collect()
+    }
+
+    // Makes room by evicting the cheapest tail transaction of any sender; evicting tails never
+    // leaves a nonce gap behind. The newcomer must pay a strictly higher fee.
+    fn evict_for(&mut self, transaction: &Transaction) -> Result<(), String> {
+        let candidate = self
+            .accounts
+            .values()
+            .filter_map(|queue| queue.values().next_back())
+            .min_by(|a, b| {
+                a.transaction
+                    .fee
+                    .cmp(&b.transaction.fee)
+                    .then(b.sequence.cmp(&a.sequence))
+            })
+            .map(|pooled| (pooled.transaction.hash.clone(), pooled.transaction.fee));
+
+        match candidate {
+            Some((tx_hash, fee)) if fee < transaction.fee => {
+                self.remove_transaction(&tx_hash);
+                metric::TX_POOL_EVICTED.inc();
+                info!(
+                    "Evicted transaction '{}' with fee {} from the full pool for '{}' with fee {}.",
+                    tx_hash, fee, transaction.hash, transaction.fee
+                );
+                Ok(())
+            }
+            _ => Err(format!(
+                "Transaction pool is full ({} transactions) and fee {} is too low to evict any.",
+                self.len(),
+                transaction.fee
+            )),
+        }
+    }
+
+    // Applies the change in one sender's executable transactions, the only ones an update
+    // can move between pending and queued
+    fn update_pending(&mut self, from: &str, previous: usize) {
+        self.pending = self.pending + self.executable(from).len() - previous;
+        metric::TX_POOL_PENDING.set(self.pending as i64);
+        metric::TX_POOL_QUEUED.set((self.len() - self.pending) as i64);
+    }
 }
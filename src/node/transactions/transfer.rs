@@ -29,6 +29,11 @@ impl Transfer {
         from: &String,
         db: &Database,
     ) -> Vec<Option<(Vec<u8>, Vec<u8>)>> {
+        // Both updates are computed from the stored state, so the second would overwrite the first
+        if self.to == *from {
+            return vec![];
+        }
+
         let transfer_value: i64 = self.value as i64;
 
         // Update sender's account state by deducting the transfer value
@@ -0,0 +1,66 @@
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+use serde::{Deserialize, Serialize};
+
+use crate::node::database::Database;
+use crate::node::validator_set::{ValidatorProposal, ValidatorSets};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VoteValidator {
+    pub proposal_transaction_hash: String,
+}
+
+impl VoteValidator {
+    pub fn verify_state(&self, from: &str, db: &Database) -> Result<(), String> {
+        let authorities = ValidatorSets::next_block_authorities(db)?;
+        if !authorities.iter().any(|authority| authority == from) {
+            return Err(format!("Only authorities can vote, '{}' is not one.", from));
+        }
+
+        let proposal = ValidatorProposal::get(&self.proposal_transaction_hash, db)?
+            .ok_or_else(|| {
+                format!(
+                    "Validator proposal '{}' does not exist.",
+                    self.proposal_transaction_hash
+                )
+            })?;
+        if proposal.enacted {
+            return Err(format!(
+                "Validator proposal '{}' has already been enacted.",
+                self.proposal_transaction_hash
+            ));
+        }
+        if ValidatorProposal::has_voted(&self.proposal_transaction_hash, from, db) {
+            return Err(format!(
+                "'{}' has already voted on validator proposal '{}'.",
+                from, self.proposal_transaction_hash
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn state_transaction(&self, from: &str) -> Vec<Option<(Vec<u8>, Vec<u8>)>> {
+        vec![Some((
+            ValidatorProposal::construct_vote_key(&self.proposal_transaction_hash, from),
+            vec![1],
+        ))]
+    }
+}
+
+impl Encodable for VoteValidator {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(1);
+        stream.append(&self.proposal_transaction_hash);
+    }
+}
+
+impl Decodable for VoteValidator {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if !rlp.is_list() || rlp.item_count()? != 1 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        Ok(VoteValidator {
+            proposal_transaction_hash: rlp.val_at(0)?,
+        })
+    }
+}
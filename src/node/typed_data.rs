@@ -0,0 +1,224 @@
+use super::coordinate::Coordinates;
+use super::signature_keys::SignatureKeys;
+use super::transactions::function_call::FunctionCall;
+
+const EIP712_DOMAIN_TYPE: &str = "EIP712Domain(string name,string version,uint256 chainId)";
+const DOMAIN_NAME: &str = "Clutch";
+const DOMAIN_VERSION: &str = "1";
+const COORDINATES_TYPE: &str = "Coordinates(string latitude,string longitude)";
+
+// EIP-712 signing hash: keccak256(0x19 0x01 || domainSeparator || hashStruct(message)). The
+// domain carries the chain id, so a signature is only valid on the chain it was made for.
+pub fn transaction_typed_data_hash(
+    chain_id: u64,
+    nonce: u64,
+    fee: u64,
+    function_call: &FunctionCall,
+) -> Result<[u8; 32], String> {
+    let mut encoded = vec![0x19, 0x01];
+    encoded.extend_from_slice(&domain_separator(chain_id));
+    encoded.extend_from_slice(&function_call_struct_hash(nonce, fee, function_call)?);
+    Ok(SignatureKeys::keccak256(&encoded))
+}
+
+fn domain_separator(chain_id: u64) -> [u8; 32] {
+    hash_struct(
+        EIP712_DOMAIN_TYPE,
+        &[
+            encode_string(DOMAIN_NAME),
+            encode_string(DOMAIN_VERSION),
+            encode_uint64(chain_id),
+        ],
+    )
+}
+
+fn function_call_struct_hash(
+    nonce: u64,
+    fee: u64,
+    function_call: &FunctionCall,
+) -> Result<[u8; 32], String> {
+    let nonce = encode_uint64(nonce);
+    let fee = encode_uint64(fee);
+
+    let (type_string, fields) = match function_call {
+        FunctionCall::Transfer(transfer) => (
+            "Transfer(uint64 nonce,uint64 fee,address to,uint64 value)".to_string(),
+            vec![
+                nonce,
+                fee,
+                encode_address(&transfer.to)?,
+                encode_uint64(transfer.value),
+            ],
+        ),
+        FunctionCall::RideRequest(ride_request) => (
+            format!(
+                "RideRequest(uint64 nonce,uint64 fee,Coordinates pickup_location,Coordinates dropoff_location,uint64 fare){}",
+                COORDINATES_TYPE
+            ),
+            vec![
+                nonce,
+                fee,
+                coordinates_struct_hash(&ride_request.pickup_location),
+                coordinates_struct_hash(&ride_request.dropoff_location),
+                encode_uint64(ride_request.fare),
+            ],
+        ),
+        FunctionCall::RideOffer(ride_offer) => (
+            "RideOffer(uint64 nonce,uint64 fee,string ride_request_transaction_hash,uint64 fare)"
+                .to_string(),
+            vec![
+                nonce,
+                fee,
+                encode_string(&ride_offer.ride_request_transaction_hash),
+                encode_uint64(ride_offer.fare),
+            ],
+        ),
+        FunctionCall::RideAcceptance(ride_acceptance) => (
+            "RideAcceptance(uint64 nonce,uint64 fee,string ride_offer_transaction_hash)".to_string(),
+            vec![
+                nonce,
+                fee,
+                encode_string(&ride_acceptance.ride_offer_transaction_hash),
+            ],
+        ),
+        FunctionCall::RidePay(ride_pay) => (
+            "RidePay(uint64 nonce,uint64 fee,string ride_acceptance_transaction_hash,uint64 fare)"
+                .to_string(),
+            vec![
+                nonce,
+                fee,
+                encode_string(&ride_pay.ride_acceptance_transaction_hash),
+                encode_uint64(ride_pay.fare),
+            ],
+        ),
+        FunctionCall::RideCancel(ride_cancel) => (
+            "RideCancel(uint64 nonce,uint64 fee,string ride_acceptance_transaction_hash)".to_string(),
+            vec![
+                nonce,
+                fee,
+                encode_string(&ride_cancel.ride_acceptance_transaction_hash),
+            ],
+        ),
+        FunctionCall::ConfirmArrival(confirm_arrival) => (
+            "ConfirmArrival(uint64 nonce,uint64 fee,string ride_acceptance_transaction_hash)".to_string(),
+            vec![
+                nonce,
+                fee,
+                encode_string(&confirm_arrival.ride_acceptance_transaction_hash),
+            ],
+        ),
+        FunctionCall::ComplainArrival(complain_arrival) => (
+            "ComplainArrival(uint64 nonce,uint64 fee,string ride_acceptance_transaction_hash)".to_string(),
+            vec![
+                nonce,
+                fee,
+                encode_string(&complain_arrival.ride_acceptance_transaction_hash),
+            ],
+        ),
+        FunctionCall::ProposeValidator(propose_validator) => (
+            "ProposeValidator(uint64 nonce,uint64 fee,address validator)".to_string(),
+            vec![nonce, fee, encode_address(&propose_validator.validator)?],
+        ),
+        FunctionCall::VoteValidator(vote_validator) => (
+            "VoteValidator(uint64 nonce,uint64 fee,string proposal_transaction_hash)".to_string(),
+            vec![
+                nonce,
+                fee,
+                encode_string(&vote_validator.proposal_transaction_hash),
+            ],
+        ),
+        FunctionCall::RemoveValidator(remove_validator) => (
+            "RemoveValidator(uint64 nonce,uint64 fee,address validator)".to_string(),
+            vec![nonce, fee, encode_address(&remove_validator.validator)?],
+        ),
+        // The block hashes identify the proof, the rest of it is checked against them
+        FunctionCall::ReportEquivocation(report_equivocation) => (
+            "ReportEquivocation(uint64 nonce,uint64 fee,address offender,uint64 block_index,string first_block_hash,string second_block_hash)"
+                .to_string(),
+            vec![
+                nonce,
+                fee,
+                encode_address(report_equivocation.proof.offender())?,
+                encode_uint64(report_equivocation.proof.block_index() as u64),
+                encode_string(&report_equivocation.proof.first.header.hash),
+                encode_string(&report_equivocation.proof.second.header.hash),
+            ],
+        ),
+    };
+
+    Ok(hash_struct(&type_string, &fields))
+}
+
+// Coordinates are encoded as decimal strings so wallets can display them
+fn coordinates_struct_hash(coordinates: &Coordinates) -> [u8; 32] {
+    hash_struct(
+        COORDINATES_TYPE,
+        &[
+            encode_string(&coordinates.latitude.to_string()),
+            encode_string(&coordinates.longitude.to_string()),
+        ],
+    )
+}
+
+fn hash_struct(type_string: &str, fields: &[[u8; 32]]) -> [u8; 32] {
+    let mut encoded = SignatureKeys::keccak256(type_string.as_bytes()).to_vec();
+    for field in fields {
+        encoded.extend_from_slice(field);
+    }
+    SignatureKeys::keccak256(&encoded)
+}
+
+fn encode_string(value: &str) -> [u8; 32] {
+    SignatureKeys::keccak256(value.as_bytes())
+}
+
+fn encode_uint64(value: u64) -> [u8; 32] {
+    let mut encoded = [0u8; 32];
+    encoded[24..].copy_from_slice(&value.to_be_bytes());
+    encoded
+}
+
+fn encode_address(address: &str) -> Result<[u8; 32], String> {
+    let bytes = hex::decode(address.trim_start_matches("0x"))
+        .map_err(|_| format!("Invalid hex in address '{}'", address))?;
+    if bytes.len() != 20 {
+        return Err(format!("Address '{}' is not 20 bytes long", address));
+    }
+
+    let mut encoded = [0u8; 32];
+    encoded[12..].copy_from_slice(&bytes);
+    Ok(encoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::transactions::transfer::Transfer;
+
+    #[test]
+    fn test_typed_data_hash_binds_nonce_fee_and_arguments() {
+        let transfer = |value| {
+            FunctionCall::Transfer(Transfer {
+                to: "0x8f19077627cde4848b090c53c83b12956837d5e9".to_string(),
+                value,
+            })
+        };
+
+        let hash = transaction_typed_data_hash(1, 1, 0, &transfer(10)).unwrap();
+        assert_eq!(hash, transaction_typed_data_hash(1, 1, 0, &transfer(10)).unwrap());
+        assert_ne!(hash, transaction_typed_data_hash(1, 2, 0, &transfer(10)).unwrap());
+        assert_ne!(hash, transaction_typed_data_hash(1, 1, 0, &transfer(11)).unwrap());
+        assert_ne!(hash, transaction_typed_data_hash(1, 1, 1, &transfer(10)).unwrap());
+        assert_ne!(hash, transaction_typed_data_hash(2, 1, 0, &transfer(10)).unwrap());
+    }
+
+    #[test]
+    fn test_typed_data_hash_rejects_invalid_address() {
+        let function_call = FunctionCall::Transfer(Transfer {
+            to: "not-an-address".to_string(),
+            value: 10,
+        });
+
+        assert!(transaction_typed_data_hash(1, 1, 0, &function_call).is_err());
+    }
+}
@@ -0,0 +1,358 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tracing::info;
+
+use crate::node::blocks::block::Block;
+use crate::node::blocks::block_headers::BlockHeader;
+use crate::node::database::{Database, StateUpdate};
+use crate::node::signature_keys::SignatureVersion;
+use crate::node::transactions::function_call::FunctionCall;
+
+const VALIDATOR_SETS_KEY: &[u8] = b"validator_sets";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ValidatorSet {
+    // First epoch the set is active in
+    pub epoch: u64,
+    pub authorities: Vec<String>,
+}
+
+// Every validator set change so far, stored once at genesis and appended to by governance
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ValidatorSets {
+    pub epoch_length: u64,
+    // See ConsensusEngine::slot_duration. Zero in chains created before it was stored.
+    #[serde(default)]
+    pub slot_duration: u64,
+    pub sets: Vec<ValidatorSet>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ValidatorChange {
+    Add,
+    Remove,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ValidatorProposal {
+    pub change: ValidatorChange,
+    pub validator: String,
+    pub proposer: String,
+    pub enacted: bool,
+}
+
+impl ValidatorSets {
+    // Writes the genesis validator set, unless the chain already has one
+    pub fn initialize(
+        db: &Database,
+        authorities: &[String],
+        epoch_length: u64,
+        slot_duration: u64,
+    ) -> Result<(), String> {
+        if db.get("state", VALIDATOR_SETS_KEY)?.is_some() {
+            return Ok(());
+        }
+
+        let validator_sets = ValidatorSets {
+            epoch_length,
+            slot_duration,
+            sets: vec![ValidatorSet {
+                epoch: 0,
+                authorities: authorities.to_vec(),
+            }],
+        };
+        db.write(vec![(
+            "state",
+            VALIDATOR_SETS_KEY,
+            Some(&validator_sets.to_bytes()),
+        )])
+    }
+
+    pub fn get(db: &Database) -> Result<ValidatorSets, String> {
+        match db.get("state", VALIDATOR_SETS_KEY)? {
+            Some(value) => serde_json::from_slice(&value)
+                .map_err(|e| format!("Failed to deserialize validator sets: {}", e)),
+            None => Err("Validator sets are not initialized".to_string()),
+        }
+    }
+
+    pub fn authorities_at(db: &Database, block_index: usize) -> Result<Vec<String>, String> {
+        let validator_sets = Self::get(db)?;
+        Ok(validator_sets
+            .authorities_at_epoch(validator_sets.epoch_of(block_index))
+            .to_vec())
+    }
+
+    // Authorities for the block after the latest one, which new transactions will be part of
+    pub fn next_block_authorities(db: &Database) -> Result<Vec<String>, String> {
+        let next_block_index = Block::get_latest_block(db)
+            .map(|block| block.index + 1)
+            .unwrap_or(0);
+        Self::authorities_at(db, next_block_index)
+    }
+
+    pub fn epoch_of(&self, block_index: usize) -> u64 {
+        block_index as u64 / self.epoch_length
+    }
+
+    pub fn authorities_at_epoch(&self, epoch: u64) -> &[String] {
+        self.sets
+            .iter()
+            .rev()
+            .find(|set| set.epoch <= epoch)
+            .map(|set| set.authorities.as_slice())
+            .unwrap_or(&[])
+    }
+
+    // The slot a header was signed for, its height when authorities take turns by height. None
+    // when the slot comes from a timestamp the signature does not cover.
+    pub fn signed_slot_of(&self, header: &BlockHeader) -> Option<u64> {
+        if self.slot_duration == 0 {
+            return Some(header.index as u64);
+        }
+        (header.signature_version == SignatureVersion::TimestampedDigest)
+            .then(|| header.timestamp / self.slot_duration)
+    }
+
+    // More than two thirds of the active authorities
+    pub fn is_supermajority(votes: usize, authorities: usize) -> bool {
+        votes * 3 > authorities * 2
+    }
+
+    // Runs with the block transactions staged: every proposal touched by the block that now has
+    // a supermajority of votes changes the validator set from the next epoch on. Returns the
+    // state updates, written in the block's batch.
+    pub fn enact_proposals(db: &Database, block: &Block) -> Result<Vec<StateUpdate>, String> {
+        let mut proposal_hashes = Vec::new();
+        let mut seen = HashSet::new();
+        for transaction in block.transactions.iter() {
+            let proposal_hash = match &transaction.data {
+                FunctionCall::ProposeValidator(_) | FunctionCall::RemoveValidator(_) => {
+                    &transaction.hash
+                }
+                FunctionCall::VoteValidator(vote) => &vote.proposal_transaction_hash,
+                _ => continue,
+            };
+            if seen.insert(proposal_hash.clone()) {
+                proposal_hashes.push(proposal_hash.clone());
+            }
+        }
+        if proposal_hashes.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut validator_sets = Self::get(db)?;
+        let active = validator_sets
+            .authorities_at_epoch(validator_sets.epoch_of(block.index))
+            .to_vec();
+        let next_epoch = validator_sets.epoch_of(block.index) + 1;
+
+        let mut operations: Vec<StateUpdate> = Vec::new();
+        for proposal_hash in proposal_hashes {
+            let mut proposal = match ValidatorProposal::get(&proposal_hash, db)? {
+                Some(proposal) if !proposal.enacted => proposal,
+                _ => continue,
+            };
+
+            let votes = active
+                .iter()
+                .filter(|authority| ValidatorProposal::has_voted(&proposal_hash, authority, db))
+                .count();
+            if !Self::is_supermajority(votes, active.len()) {
+                continue;
+            }
+
+            let mut authorities = validator_sets.authorities_at_epoch(next_epoch).to_vec();
+            match proposal.change {
+                ValidatorChange::Add if !authorities.contains(&proposal.validator) => {
+                    authorities.push(proposal.validator.clone());
+                }
+                ValidatorChange::Remove if authorities.len() > 1 => {
+                    authorities.retain(|authority| *authority != proposal.validator);
+                }
+                _ => {}
+            }
+            validator_sets.set_epoch(next_epoch, authorities);
+
+            proposal.enacted = true;
+            operations.push((
+                ValidatorProposal::construct_proposal_key(&proposal_hash),
+                serde_json::to_vec(&proposal).unwrap(),
+            ));
+            info!(
+                "Validator proposal '{}' passed with {} of {} votes: {:?} {} from epoch {}.",
+                proposal_hash,
+                votes,
+                active.len(),
+                proposal.change,
+                proposal.validator,
+                next_epoch
+            );
+        }
+
+        if operations.is_empty() {
+            return Ok(operations);
+        }
+        operations.push((VALIDATOR_SETS_KEY.to_vec(), validator_sets.to_bytes()));
+        Ok(operations)
+    }
+
+    // Runs after enact_proposals with its updates staged: authorities reported for equivocation
+    // in the block are removed from the next epoch on, unless they are the last one
+    pub fn remove_offenders(db: &Database, block: &Block) -> Result<Vec<StateUpdate>, String> {
+        let offenders: Vec<&String> = block
+            .transactions
+            .iter()
+            .filter_map(|transaction| match &transaction.data {
+                FunctionCall::ReportEquivocation(report) => Some(report.proof.offender()),
+                _ => None,
+            })
+            .collect();
+        if offenders.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut validator_sets = Self::get(db)?;
+        let next_epoch = validator_sets.epoch_of(block.index) + 1;
+        let mut authorities = validator_sets.authorities_at_epoch(next_epoch).to_vec();
+        for offender in offenders {
+            if authorities.len() > 1 && authorities.contains(offender) {
+                authorities.retain(|authority| authority != offender);
+                info!(
+                    "Authority {} removed for equivocation from epoch {}.",
+                    offender, next_epoch
+                );
+            }
+        }
+        validator_sets.set_epoch(next_epoch, authorities);
+
+        Ok(vec![(
+            VALIDATOR_SETS_KEY.to_vec(),
+            validator_sets.to_bytes(),
+        )])
+    }
+
+    fn set_epoch(&mut self, epoch: u64, authorities: Vec<String>) {
+        match self.sets.iter_mut().find(|set| set.epoch == epoch) {
+            Some(set) => set.authorities = authorities,
+            None => self.sets.push(ValidatorSet { epoch, authorities }),
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap()
+    }
+}
+
+impl ValidatorProposal {
+    pub fn get(proposal_hash: &str, db: &Database) -> Result<Option<ValidatorProposal>, String> {
+        match db.get("state", &Self::construct_proposal_key(proposal_hash))? {
+            Some(value) => serde_json::from_slice(&value)
+                .map(Some)
+                .map_err(|e| format!("Failed to deserialize validator proposal: {}", e)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn has_voted(proposal_hash: &str, voter: &str, db: &Database) -> bool {
+        matches!(
+            db.get("state", &Self::construct_vote_key(proposal_hash, voter)),
+            Ok(Some(_))
+        )
+    }
+
+    // New proposals count the proposer's vote
+    pub fn state_transaction(&self, tx_hash: &str) -> Vec<Option<(Vec<u8>, Vec<u8>)>> {
+        vec![
+            Some((
+                Self::construct_proposal_key(tx_hash),
+                serde_json::to_vec(self).unwrap(),
+            )),
+            Some((Self::construct_vote_key(tx_hash, &self.proposer), vec![1])),
+        ]
+    }
+
+    pub fn construct_proposal_key(proposal_hash: &str) -> Vec<u8> {
+        format!("validator_proposal_{}", proposal_hash).into_bytes()
+    }
+
+    pub fn construct_vote_key(proposal_hash: &str, voter: &str) -> Vec<u8> {
+        format!("validator_vote_{}_{}", proposal_hash, voter).into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_authorities_at_epoch() {
+        let validator_sets = ValidatorSets {
+            epoch_length: 10,
+            slot_duration: 0,
+            sets: vec![
+                ValidatorSet {
+                    epoch: 0,
+                    authorities: vec!["a".to_string()],
+                },
+                ValidatorSet {
+                    epoch: 2,
+                    authorities: vec!["a".to_string(), "b".to_string()],
+                },
+            ],
+        };
+
+        assert_eq!(validator_sets.epoch_of(19), 1);
+        assert_eq!(validator_sets.authorities_at_epoch(1), ["a".to_string()]);
+        assert_eq!(
+            validator_sets
+                .authorities_at_epoch(validator_sets.epoch_of(20))
+                .len(),
+            2
+        );
+        assert_eq!(validator_sets.authorities_at_epoch(7).len(), 2);
+    }
+
+    #[test]
+    fn test_signed_slot_of() {
+        let mut block = Block::new_block(3, String::new(), vec![]);
+        block.timestamp = 1007;
+        block.sign(
+            "node_1",
+            "0883ddd3d07303b87c954b0c9383f7b78f45e002520fc03a8adc80595dbf6509",
+        );
+        let mut validator_sets = ValidatorSets {
+            epoch_length: 10,
+            slot_duration: 0,
+            sets: vec![],
+        };
+
+        // Turns by height
+        assert_eq!(
+            validator_sets.signed_slot_of(&block.to_block_header()),
+            Some(3)
+        );
+
+        validator_sets.slot_duration = 5;
+        assert_eq!(
+            validator_sets.signed_slot_of(&block.to_block_header()),
+            Some(201)
+        );
+
+        // The timestamp of a digest signed header is not signed
+        block.signature_version = SignatureVersion::Digest;
+        assert_eq!(
+            validator_sets.signed_slot_of(&block.to_block_header()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_is_supermajority() {
+        assert!(ValidatorSets::is_supermajority(1, 1));
+        assert!(!ValidatorSets::is_supermajority(2, 3));
+        assert!(ValidatorSets::is_supermajority(3, 4));
+        assert!(!ValidatorSets::is_supermajority(4, 6));
+        assert!(ValidatorSets::is_supermajority(5, 6));
+    }
+}
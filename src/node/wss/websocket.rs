@@ -6,13 +6,22 @@ use crate::node::rlp_encoding::encode;
 use futures::{stream::StreamExt, SinkExt};
 use tracing::{error, info, warn};
 use std::error::Error;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::Mutex;
+use tokio::sync::{broadcast, Mutex};
 use tokio_tungstenite::accept_async;
 use tokio_tungstenite::tungstenite::protocol::Message;
 use hex;
 
+static NEXT_SUBSCRIPTION_ID: AtomicU64 = AtomicU64::new(1);
+
+// Finalized block notifications of a connection
+struct Subscription {
+    id: u64,
+    finalized_blocks: broadcast::Receiver<Block>,
+}
+
 pub struct WebSocket;
 
 impl WebSocket {
@@ -44,21 +53,28 @@ impl WebSocket {
     ) -> Result<(), Box<dyn Error>> {
         let ws_stream = accept_async(stream).await?;
         let (mut ws_sender, mut ws_receiver) = ws_stream.split();
+        let mut subscription: Option<Subscription> = None;
 
-        while let Some(message) = ws_receiver.next().await {
-            match message {
-                Ok(Message::Text(text)) => {
-                    info!("Received from websocket: {}", text);
-                    if let Some(response) = Self::handle_json_rpc_request(&text, &blockchain, command_tx_p2p.clone()).await {
-                        if let Err(e) = ws_sender.send(Message::Text(response)).await {
-                            error!("Error sending message: {}", e);
-                            return Err(Box::new(e));
-                        }
+        loop {
+            let response = tokio::select! {
+                message = ws_receiver.next() => match message {
+                    Some(Ok(Message::Text(text))) => {
+                        info!("Received from websocket: {}", text);
+                        Self::handle_json_rpc_request(&text, &blockchain, command_tx_p2p.clone(), &mut subscription).await
                     }
-                }
-                Ok(_) => { /* Handle other message types if necessary */ }
-                Err(e) => {
-                    error!("Error receiving message: {}", e);
+                    Some(Ok(_)) => None, /* Handle other message types if necessary */
+                    Some(Err(e)) => {
+                        error!("Error receiving message: {}", e);
+                        return Err(Box::new(e));
+                    }
+                    None => break,
+                },
+                finalized_block = Self::next_finalized_block(&mut subscription) => finalized_block,
+            };
+
+            if let Some(response) = response {
+                if let Err(e) = ws_sender.send(Message::Text(response)).await {
+                    error!("Error sending message: {}", e);
                     return Err(Box::new(e));
                 }
             }
@@ -67,10 +83,36 @@ impl WebSocket {
         Ok(())
     }
 
+    // Waits for the next finalized block notification, forever when not subscribed
+    async fn next_finalized_block(subscription: &mut Option<Subscription>) -> Option<String> {
+        let subscription = match subscription {
+            Some(subscription) => subscription,
+            None => return std::future::pending().await,
+        };
+
+        match subscription.finalized_blocks.recv().await {
+            Ok(block) => Some(
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "method": "finalized_block",
+                    "params": { "subscription": subscription.id, "result": block }
+                })
+                .to_string(),
+            ),
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                // Later notifications cover the skipped blocks, finality implies the ancestors
+                warn!("Finalized block subscription {} skipped {} blocks", subscription.id, skipped);
+                None
+            }
+            Err(broadcast::error::RecvError::Closed) => std::future::pending().await,
+        }
+    }
+
     async fn handle_json_rpc_request(
         request_str: &str,
         blockchain: &Arc<Mutex<Blockchain>>,
         command_tx_p2p: tokio::sync::mpsc::Sender<P2PServerCommand>,
+        subscription: &mut Option<Subscription>,
     ) -> Option<String> {
         let request_value: serde_json::Value = match serde_json::from_str(request_str) {
             Ok(val) => val,
@@ -108,6 +150,25 @@ impl WebSocket {
             "get_next_nonce" => {
                 Self::handle_get_next_nonce(params, id, blockchain).await
             }
+            "get_authorities" => {
+                Self::handle_get_authorities(id, blockchain).await
+            }
+            "get_finalized_block" => {
+                Self::handle_get_finalized_block(id, blockchain).await
+            }
+            "get_peer_scores" => {
+                Self::handle_get_peer_scores(id, command_tx_p2p).await
+            }
+            "get_authority_peers" => {
+                Self::handle_get_authority_peers(id, command_tx_p2p).await
+            }
+            "subscribe_finalized_blocks" => {
+                Self::handle_subscribe_finalized_blocks(id, blockchain, subscription).await
+            }
+            "unsubscribe_finalized_blocks" => {
+                let unsubscribed = subscription.take().is_some();
+                Some(json_rpc_success_response(serde_json::json!(unsubscribed), id))
+            }
             _ => {
                 warn!("Unknown method '{}' in request: {}", method, request_str);
                 Some(json_rpc_error_response(-32601, "Method not found", id))
@@ -130,8 +191,7 @@ impl WebSocket {
             }
         };
 
-        let blockchain = blockchain.lock().await;
-        if let Err(e) = blockchain.add_transaction_to_pool(&transaction) {
+        if let Err(e) = blockchain.lock().await.add_transaction_to_pool(&transaction) {
             let error_msg = format!("Failed to add transaction: {}", e);
             error!("{}", error_msg);
             return Some(json_rpc_error_response(-32000, &error_msg, id));
@@ -169,21 +229,23 @@ impl WebSocket {
                 return Some(json_rpc_error_response(-32602, &error_msg, id));
             }
         };
-        // Decode RLP to Transaction
-        let transaction: Transaction = match crate::node::rlp_encoding::decode(&tx_bytes) {
-            Ok(tx) => tx,
-            Err(e) => {
-                let error_msg = format!("Failed to decode RLP transaction: {}", e);
-                warn!("{}", error_msg);
-                return Some(json_rpc_error_response(-32602, &error_msg, id));
+        // Decode RLP to Transaction, recovering the sender from the signature
+        let transaction = {
+            let blockchain = blockchain.lock().await;
+            let transaction = match Transaction::from_raw_bytes(&tx_bytes, blockchain.chain_id()) {
+                Ok(tx) => tx,
+                Err(e) => {
+                    warn!("{}", e);
+                    return Some(json_rpc_error_response(-32602, &e, id));
+                }
+            };
+            if let Err(e) = blockchain.add_transaction_to_pool(&transaction) {
+                let error_msg = format!("Failed to add transaction: {}", e);
+                error!("{}", error_msg);
+                return Some(json_rpc_error_response(-32000, &error_msg, id));
             }
+            transaction
         };
-        let blockchain = blockchain.lock().await;
-        if let Err(e) = blockchain.add_transaction_to_pool(&transaction) {
-            let error_msg = format!("Failed to add transaction: {}", e);
-            error!("{}", error_msg);
-            return Some(json_rpc_error_response(-32000, &error_msg, id));
-        }
         info!("Transaction added to pool from WebSocket.");
         // Gossip transaction
         let encoded_tx = encode(&transaction);
@@ -205,17 +267,21 @@ impl WebSocket {
             }
         };
 
-        let blockchain = blockchain.lock().await;
-        if let Err(e) = blockchain.import_block(&block) {
-            error!("Failed to import block: {}", e);
-            return Some(json_rpc_error_response(-32000, &format!("Failed to import block: {}", e), id));
-        }
+        let precommit = {
+            let blockchain = blockchain.lock().await;
+            if let Err(e) = blockchain.import_block(&block) {
+                error!("Failed to import block: {}", e);
+                return Some(json_rpc_error_response(-32000, &format!("Failed to import block: {}", e), id));
+            }
+            blockchain.precommit_block(&block)
+        };
 
         info!("Block imported to blockchain from WebSocket.");
 
         // Gossip block
         let encoded_block = encode(&block);
-        P2PServer::gossip_message_command(command_tx_p2p, GossipMessageType::Block, &encoded_block).await;
+        P2PServer::gossip_message_command(command_tx_p2p.clone(), GossipMessageType::Block, &encoded_block).await;
+        P2PServer::gossip_precommit_command(command_tx_p2p, &block, precommit).await;
 
         Some(json_rpc_success_response(serde_json::json!("Block imported"), id))
     }
@@ -225,12 +291,17 @@ impl WebSocket {
         blockchain: &Arc<Mutex<Blockchain>>,
         command_tx_p2p: tokio::sync::mpsc::Sender<P2PServerCommand>,
     ) -> Option<String> {
-        let blockchain = blockchain.lock().await;
-        let new_block = match blockchain.author_new_block() {
-            Ok(block) => block,
-            Err(e) => {
-                error!("Failed to author new block: {}", e);
-                return Some(json_rpc_error_response(-32000, &format!("Failed to author new block: {}", e), id));
+        let (new_block, precommit) = {
+            let blockchain = blockchain.lock().await;
+            match blockchain.author_new_block() {
+                Ok(block) => {
+                    let precommit = blockchain.precommit_block(&block);
+                    (block, precommit)
+                }
+                Err(e) => {
+                    error!("Failed to author new block: {}", e);
+                    return Some(json_rpc_error_response(-32000, &format!("Failed to author new block: {}", e), id));
+                }
             }
         };
 
@@ -238,7 +309,8 @@ impl WebSocket {
 
         // Gossip new block
         let encoded_block = encode(&new_block);
-        P2PServer::gossip_message_command(command_tx_p2p, GossipMessageType::Block, &encoded_block).await;
+        P2PServer::gossip_message_command(command_tx_p2p.clone(), GossipMessageType::Block, &encoded_block).await;
+        P2PServer::gossip_precommit_command(command_tx_p2p, &new_block, precommit).await;
 
         Some(json_rpc_success_response(serde_json::json!("New block authored"), id))
     }
@@ -278,6 +350,82 @@ impl WebSocket {
             }
         }
     }
+
+    // Authorities of the next block, including validator set changes made by governance
+    async fn handle_get_authorities(
+        id: serde_json::Value,
+        blockchain: &Arc<Mutex<Blockchain>>,
+    ) -> Option<String> {
+        let blockchain = blockchain.lock().await;
+
+        match blockchain.get_authorities() {
+            Ok(authorities) => Some(json_rpc_success_response(serde_json::json!(authorities), id)),
+            Err(e) => {
+                let error_msg = format!("Failed to get authorities: {}", e);
+                error!("{}", error_msg);
+                Some(json_rpc_error_response(-32000, &error_msg, id))
+            }
+        }
+    }
+
+    // Latest block finalized by authority precommits; transactions in it and its ancestors are settled
+    async fn handle_get_finalized_block(
+        id: serde_json::Value,
+        blockchain: &Arc<Mutex<Blockchain>>,
+    ) -> Option<String> {
+        let blockchain = blockchain.lock().await;
+
+        match blockchain.get_finalized_block() {
+            Some(block) => Some(json_rpc_success_response(serde_json::json!(block), id)),
+            None => Some(json_rpc_error_response(-32000, "Failed to get the finalized block", id)),
+        }
+    }
+
+    // Reputation of connected peers and of peers that misbehaved or are banned
+    async fn handle_get_peer_scores(
+        id: serde_json::Value,
+        command_tx_p2p: tokio::sync::mpsc::Sender<P2PServerCommand>,
+    ) -> Option<String> {
+        match P2PServer::get_peer_scores_command(command_tx_p2p).await {
+            Ok(peer_scores) => Some(json_rpc_success_response(serde_json::json!(peer_scores), id)),
+            Err(e) => {
+                let error_msg = format!("Failed to get peer scores: {}", e);
+                error!("{}", error_msg);
+                Some(json_rpc_error_response(-32000, &error_msg, id))
+            }
+        }
+    }
+
+    async fn handle_get_authority_peers(
+        id: serde_json::Value,
+        command_tx_p2p: tokio::sync::mpsc::Sender<P2PServerCommand>,
+    ) -> Option<String> {
+        match P2PServer::get_authority_peers_command(command_tx_p2p).await {
+            Ok(authority_peers) => {
+                Some(json_rpc_success_response(serde_json::json!(authority_peers), id))
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to get authority peers: {}", e);
+                error!("{}", error_msg);
+                Some(json_rpc_error_response(-32000, &error_msg, id))
+            }
+        }
+    }
+
+    async fn handle_subscribe_finalized_blocks(
+        id: serde_json::Value,
+        blockchain: &Arc<Mutex<Blockchain>>,
+        subscription: &mut Option<Subscription>,
+    ) -> Option<String> {
+        let blockchain = blockchain.lock().await;
+        let subscription_id = NEXT_SUBSCRIPTION_ID.fetch_add(1, Ordering::Relaxed);
+        *subscription = Some(Subscription {
+            id: subscription_id,
+            finalized_blocks: blockchain.subscribe_finalized_blocks(),
+        });
+
+        Some(json_rpc_success_response(serde_json::json!(subscription_id), id))
+    }
 }
 
 fn json_rpc_error_response(code: i32, message: &str, id: serde_json::Value) -> String {
@@ -1,24 +1,31 @@
+mod common;
+
 use std::vec;
 
-use clutch_node::node::{blockchain::Blockchain, transactions::{function_call::FunctionCall, transaction::Transaction, transfer::Transfer}};
+use clutch_node::node::{blocks::block::{Block, BlockLimits}, chain_spec::{ChainSpec, DEFAULT_CHAIN_ID}, consensus::ConsensusEngine, equivocation::SignedHeader, signature_keys::SignatureVersion, transactions::{function_call::FunctionCall, transaction::Transaction, transaction_pool::TransactionPoolConfig, transfer::Transfer, propose_validator::ProposeValidator}};
+use common::{new_blockchain, AUTHOR_PUBLIC_KEY, AUTHOR_SECRET_KEY};
 use ::tracing::info;
 
 const BLOCKCHAIN_NAME: &str = "clutch-node-transfer-test";
+const LEGACY_SIGNATURE_BLOCKCHAIN_NAME: &str = "clutch-node-legacy-signature-test";
+const BLOCK_LIMITS_BLOCKCHAIN_NAME: &str = "clutch-node-block-limits-test";
+const EMPTY_BLOCKS_BLOCKCHAIN_NAME: &str = "clutch-node-empty-blocks-test";
+const INSTANT_SEAL_BLOCKCHAIN_NAME: &str = "clutch-node-instant-seal-test";
+const GOVERNANCE_BLOCKCHAIN_NAME: &str = "clutch-node-validator-governance-test";
 const FROM_ADDRESS_KEY: &str = "0xdeb4cfb63db134698e1879ea24904df074726cc0";
 const FROM_SECRET_KEY: &str = "d2c446110cfcecbdf05b2be528e72483de5b6f7ef9c7856df2f81f48e9f2748f";
 const TO_ADDRESS_KEY: &str = "0x8f19077627cde4848b090c53c83b12956837d5e9";
-const AUTHOR_PUBLIC_KEY: &str = "0x9b6e8afff8329743cac73dbef83ca3cbf9a74c20";
-const AUTHOR_SECRET_KEY: &str = "0883ddd3d07303b87c954b0c9383f7b78f45e002520fc03a8adc80595dbf6509";
 
 #[test]
 fn author_block() {
     let authorities = vec![AUTHOR_PUBLIC_KEY.to_string()];
-    let mut blockchain = Blockchain::new(
-        BLOCKCHAIN_NAME.to_string(),
-        AUTHOR_PUBLIC_KEY.to_string(),
-        AUTHOR_SECRET_KEY.to_string(),
-        true,
-        authorities,
+    let mut blockchain = new_blockchain(
+        BLOCKCHAIN_NAME,
+        ChainSpec {
+            authorities,
+            ..ChainSpec::default()
+        },
+        TransactionPoolConfig::default(),
     );
 
     let transfer_tx = transfer_transaction(1, 20);
@@ -46,6 +53,204 @@ fn author_block() {
     blockchain.shutdown_blockchain();
 }
 
+#[test]
+fn legacy_signature_rejected_after_activation_height() {
+    let authorities = vec![AUTHOR_PUBLIC_KEY.to_string()];
+    let mut blockchain = new_blockchain(
+        LEGACY_SIGNATURE_BLOCKCHAIN_NAME,
+        ChainSpec {
+            authorities,
+            ..ChainSpec::default()
+        },
+        TransactionPoolConfig::default(),
+    );
+
+    let mut transfer_tx = transfer_transaction(1, 20);
+    transfer_tx
+        .sign_with_version(FROM_SECRET_KEY, SignatureVersion::Legacy, DEFAULT_CHAIN_ID)
+        .expect("Failed to sign transaction");
+    assert!(blockchain.add_transaction_to_pool(&transfer_tx).is_err());
+
+    transfer_tx
+        .sign_with_version(FROM_SECRET_KEY, SignatureVersion::TypedData, DEFAULT_CHAIN_ID)
+        .expect("Failed to sign transaction");
+    blockchain
+        .add_transaction_to_pool(&transfer_tx)
+        .expect("Failed to add typed data transaction to pool");
+
+    blockchain.shutdown_blockchain();
+}
+
+#[test]
+fn typed_data_signature_rejected_on_another_chain() {
+    let other_chain_id = DEFAULT_CHAIN_ID + 1;
+    let mut transfer_tx = transfer_transaction(1, 20);
+    transfer_tx
+        .sign_with_version(FROM_SECRET_KEY, SignatureVersion::TypedData, other_chain_id)
+        .expect("Failed to sign transaction");
+
+    assert!(transfer_tx.verify_signature(other_chain_id).is_ok());
+    assert!(transfer_tx.verify_signature(DEFAULT_CHAIN_ID).is_err());
+}
+
+#[test]
+fn block_timestamp_covered_by_signature() {
+    let mut block = Block::new_block(1, "00".repeat(32), vec![]);
+    block.sign(AUTHOR_PUBLIC_KEY, AUTHOR_SECRET_KEY);
+    assert!(SignedHeader::from_block(&block).verify().is_ok());
+    assert!(block.verify_signature_version(&ChainSpec::default()).is_ok());
+
+    block.timestamp += 60;
+    assert!(SignedHeader::from_block(&block).verify().is_err());
+
+    // Digest signatures leave the timestamp unsigned, so they are rejected from the activation height
+    block
+        .sign_with_version(AUTHOR_PUBLIC_KEY, AUTHOR_SECRET_KEY, SignatureVersion::Digest)
+        .expect("Failed to sign block");
+    block.timestamp += 60;
+    assert!(SignedHeader::from_block(&block).verify().is_ok());
+    assert!(block.verify_signature_version(&ChainSpec::default()).is_err());
+    assert!(block
+        .verify_signature_version(&ChainSpec {
+            timestamp_signature_activation_height: 2,
+            ..ChainSpec::default()
+        })
+        .is_ok());
+}
+
+#[test]
+fn block_limits_enforced() {
+    let authorities = vec![AUTHOR_PUBLIC_KEY.to_string()];
+    let mut blockchain = new_blockchain(
+        BLOCK_LIMITS_BLOCKCHAIN_NAME,
+        ChainSpec {
+            authorities,
+            block_limits: BlockLimits {
+                max_transactions: 1,
+                max_bytes: 1024,
+            },
+            ..ChainSpec::default()
+        },
+        TransactionPoolConfig::default(),
+    );
+
+    let latest_block = blockchain
+        .get_latest_block()
+        .expect("Failed to get the latest block");
+    let mut block = Block::new_block(
+        latest_block.index + 1,
+        latest_block.hash,
+        vec![transfer_transaction(1, 5), transfer_transaction(2, 5)],
+    );
+    block.sign(AUTHOR_PUBLIC_KEY, AUTHOR_SECRET_KEY);
+    let error = blockchain.import_block(&block).unwrap_err();
+    assert!(error.contains("exceed the limit"), "{}", error);
+
+    blockchain
+        .add_transaction_to_pool(&transfer_transaction(1, 5))
+        .expect("Failed to add transaction to pool");
+    let block = blockchain
+        .author_new_block()
+        .expect("failed to author new block");
+    assert_eq!(block.transactions.len(), 1);
+
+    blockchain.shutdown_blockchain();
+}
+
+#[test]
+fn empty_blocks_follow_chain_spec() {
+    let mut blockchain = new_blockchain(
+        EMPTY_BLOCKS_BLOCKCHAIN_NAME,
+        ChainSpec {
+            authorities: vec![AUTHOR_PUBLIC_KEY.to_string()],
+            allow_empty_blocks: true,
+            ..ChainSpec::default()
+        },
+        TransactionPoolConfig::default(),
+    );
+
+    let block = blockchain
+        .author_new_block()
+        .expect("failed to author an empty block");
+    assert!(block.transactions.is_empty());
+    assert_eq!(blockchain.get_latest_block().unwrap().hash, block.hash);
+
+    blockchain.shutdown_blockchain();
+}
+
+#[test]
+fn instant_seal_authors_without_slots() {
+    let mut blockchain = new_blockchain(
+        INSTANT_SEAL_BLOCKCHAIN_NAME,
+        ChainSpec {
+            consensus: ConsensusEngine::InstantSeal,
+            authorities: vec![AUTHOR_PUBLIC_KEY.to_string()],
+            ..ChainSpec::default()
+        },
+        TransactionPoolConfig::default(),
+    );
+
+    // Back to back blocks, no waiting for a slot
+    for nonce in 1..4 {
+        blockchain
+            .add_transaction_to_pool(&transfer_transaction(nonce, 1))
+            .expect("Failed to add transaction to pool");
+        let block = blockchain
+            .author_new_block()
+            .expect("failed to author new block");
+        assert_eq!(block.index, nonce as usize);
+    }
+
+    blockchain.shutdown_blockchain();
+}
+
+#[test]
+fn validator_proposal_changes_next_epoch_authorities() {
+    let mut blockchain = new_blockchain(
+        GOVERNANCE_BLOCKCHAIN_NAME,
+        ChainSpec {
+            authorities: vec![AUTHOR_PUBLIC_KEY.to_string()],
+            epoch_length: 1,
+            ..ChainSpec::default()
+        },
+        TransactionPoolConfig::default(),
+    );
+
+    // Only authorities can propose
+    let mut outsider_tx = Transaction::new_transaction(
+        FROM_ADDRESS_KEY.to_string(),
+        1,
+        FunctionCall::ProposeValidator(ProposeValidator {
+            validator: TO_ADDRESS_KEY.to_string(),
+        }),
+    );
+    outsider_tx.sign(FROM_SECRET_KEY);
+    assert!(blockchain.add_transaction_to_pool(&outsider_tx).is_err());
+
+    // With a single authority the proposer's own vote is a supermajority
+    let mut propose_tx = Transaction::new_transaction(
+        AUTHOR_PUBLIC_KEY.to_string(),
+        1,
+        FunctionCall::ProposeValidator(ProposeValidator {
+            validator: TO_ADDRESS_KEY.to_string(),
+        }),
+    );
+    propose_tx.sign(AUTHOR_SECRET_KEY);
+    blockchain
+        .add_transaction_to_pool(&propose_tx)
+        .expect("Failed to add transaction to pool");
+    blockchain
+        .author_new_block()
+        .expect("failed to author new block");
+
+    assert_eq!(
+        blockchain.get_authorities().unwrap(),
+        vec![AUTHOR_PUBLIC_KEY.to_string(), TO_ADDRESS_KEY.to_string()]
+    );
+
+    blockchain.shutdown_blockchain();
+}
+
 fn transfer_transaction(nonce: u64, transfer_value: u64) -> Transaction {
     let transfer = Transfer {
         to: TO_ADDRESS_KEY.to_string(),
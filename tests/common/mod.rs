@@ -0,0 +1,72 @@
+// Helpers shared by the tests that run P2P servers; each test crate uses only some of them
+#![allow(dead_code)]
+
+use std::sync::Arc;
+
+use clutch_node::node::{
+    blockchain::Blockchain,
+    chain_spec::ChainSpec,
+    consensus::ConsensusEngine,
+    p2p_server::{P2PServer, P2PServerCommand},
+    transactions::transaction_pool::TransactionPoolConfig,
+};
+use tokio::sync::{mpsc, Mutex};
+
+pub const AUTHOR_PUBLIC_KEY: &str = "0x9b6e8afff8329743cac73dbef83ca3cbf9a74c20";
+pub const AUTHOR_SECRET_KEY: &str =
+    "0883ddd3d07303b87c954b0c9383f7b78f45e002520fc03a8adc80595dbf6509";
+
+// Instant seal chain with the test author as its only authority
+pub fn chain_spec() -> ChainSpec {
+    ChainSpec {
+        consensus: ConsensusEngine::InstantSeal,
+        authorities: vec![AUTHOR_PUBLIC_KEY.to_string()],
+        ..ChainSpec::default()
+    }
+}
+
+pub fn new_blockchain(
+    name: &str,
+    chain_spec: ChainSpec,
+    transaction_pool_config: TransactionPoolConfig,
+) -> Blockchain {
+    Blockchain::new(
+        name.to_string(),
+        AUTHOR_PUBLIC_KEY.to_string(),
+        AUTHOR_SECRET_KEY.to_string(),
+        true,
+        chain_spec,
+        transaction_pool_config,
+    )
+    .expect("Failed to initialize the blockchain")
+}
+
+pub fn initialize_blockchain(name: &str, chain_spec: ChainSpec) -> Arc<Mutex<Blockchain>> {
+    Arc::new(Mutex::new(new_blockchain(
+        name,
+        chain_spec,
+        TransactionPoolConfig::default(),
+    )))
+}
+
+pub fn start_p2p_server(
+    topic_name: &str,
+    listen_addr: &str,
+    peer_addrs: &[&str],
+    blockchain: Arc<Mutex<Blockchain>>,
+) -> mpsc::Sender<P2PServerCommand> {
+    let server = P2PServer::new(topic_name, &[listen_addr], peer_addrs).unwrap();
+    run_p2p_server(server, blockchain)
+}
+
+// Runs a server configured by the test until the test ends
+pub fn run_p2p_server(
+    mut server: P2PServer,
+    blockchain: Arc<Mutex<Blockchain>>,
+) -> mpsc::Sender<P2PServerCommand> {
+    let (command_tx, command_rx) = mpsc::channel(32);
+    tokio::spawn(async move {
+        server.run(blockchain, command_rx).await.unwrap();
+    });
+    command_tx
+}
@@ -0,0 +1,69 @@
+mod common;
+
+use std::time::Duration;
+
+use clutch_node::node::{
+    chain_spec::ChainSpec,
+    consensus::ConsensusEngine,
+    dev_chain::{DevAccount, DEV_ACCOUNT_BALANCE},
+    transactions::{
+        function_call::FunctionCall, transaction::Transaction,
+        transaction_pool::TransactionPoolConfig, transfer::Transfer,
+    },
+};
+use common::{new_blockchain, AUTHOR_PUBLIC_KEY};
+
+const BLOCKCHAIN_NAME: &str = "clutch-node-dev-chain-test";
+
+#[tokio::test]
+async fn dev_chain_seals_when_transaction_enters_pool() {
+    let mut blockchain = new_blockchain(
+        BLOCKCHAIN_NAME,
+        ChainSpec {
+            consensus: ConsensusEngine::InstantSeal,
+            authorities: vec![AUTHOR_PUBLIC_KEY.to_string()],
+            genesis_allocations: DevAccount::genesis_allocations(2).unwrap(),
+            ..ChainSpec::default()
+        },
+        TransactionPoolConfig::default(),
+    );
+
+    // Same keys on every run
+    let accounts = DevAccount::derive_all(2).unwrap();
+    assert_eq!(accounts[0].address, DevAccount::derive(0).unwrap().address);
+    assert_ne!(accounts[0].address, accounts[1].address);
+    for account in accounts.iter() {
+        assert_eq!(blockchain.get_account_state(&account.address).balance, DEV_ACCOUNT_BALANCE);
+    }
+
+    let seal_trigger = blockchain
+        .seal_trigger()
+        .expect("instant seal seals on transactions");
+
+    let mut transfer_tx = Transaction::new_transaction(
+        accounts[0].address.clone(),
+        1,
+        FunctionCall::Transfer(Transfer {
+            to: accounts[1].address.clone(),
+            value: 10,
+        }),
+    );
+    transfer_tx.sign(&accounts[0].secret_key);
+    blockchain
+        .add_transaction_to_pool(&transfer_tx)
+        .expect("Failed to add transaction to pool");
+    tokio::time::timeout(Duration::from_secs(1), seal_trigger.notified())
+        .await
+        .expect("adding a transaction wakes the authoring job");
+
+    let block = blockchain
+        .author_new_block()
+        .expect("failed to author new block");
+    assert_eq!(block.transactions[0].hash, transfer_tx.hash);
+    assert_eq!(
+        blockchain.get_account_state(&accounts[1].address).balance,
+        DEV_ACCOUNT_BALANCE + 10
+    );
+
+    blockchain.shutdown_blockchain();
+}
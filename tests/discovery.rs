@@ -0,0 +1,129 @@
+mod common;
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use clutch_node::node::{
+    blockchain::Blockchain,
+    p2p_server::{peer_store::PeerStore, P2PServer, P2PServerCommand},
+};
+use common::{chain_spec, initialize_blockchain, run_p2p_server};
+use libp2p::PeerId;
+use tokio::sync::{mpsc, Mutex};
+
+const TOPIC_NAME: &str = "clutch-node-discovery-test";
+
+fn start_p2p_server(
+    listen_addr: &str,
+    peer_addrs: &[&str],
+    peer_store_path: Option<&Path>,
+    blockchain: Arc<Mutex<Blockchain>>,
+) -> mpsc::Sender<P2PServerCommand> {
+    let mut server = P2PServer::new(TOPIC_NAME, &[listen_addr], peer_addrs).unwrap();
+    if let Some(path) = peer_store_path {
+        server
+            .discovery
+            .set_peer_store(PeerStore::open(path).unwrap());
+    }
+    run_p2p_server(server, blockchain)
+}
+
+async fn wait_for_peer(
+    command_tx: &mpsc::Sender<P2PServerCommand>,
+    peer_id: &PeerId,
+    timeout: Duration,
+) -> bool {
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        let peers = P2PServer::get_connected_peers_command(command_tx.clone())
+            .await
+            .unwrap();
+        if peers.contains(peer_id) {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    false
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn node_reconnects_to_peers_from_its_peer_store() {
+    let peer_store_path = std::env::temp_dir().join(format!(
+        "clutch-node-discovery-test-{}.json",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&peer_store_path);
+    let first_blockchain = initialize_blockchain("clutch-node-discovery-test-1", chain_spec());
+    let second_blockchain = initialize_blockchain("clutch-node-discovery-test-2", chain_spec());
+    let restarted_blockchain = initialize_blockchain("clutch-node-discovery-test-3", chain_spec());
+
+    let first_tx = start_p2p_server(
+        "/ip4/127.0.0.1/tcp/4141",
+        &[],
+        None,
+        Arc::clone(&first_blockchain),
+    );
+    let first_peer_id = P2PServer::get_local_peer_id_command(first_tx.clone()).await;
+    let second_tx = start_p2p_server(
+        "/ip4/127.0.0.1/tcp/4142",
+        &["/ip4/127.0.0.1/tcp/4141"],
+        Some(&peer_store_path),
+        Arc::clone(&second_blockchain),
+    );
+    assert!(wait_for_peer(&second_tx, &first_peer_id, Duration::from_secs(10)).await);
+
+    // Saved on the next discovery tick
+    tokio::time::sleep(Duration::from_secs(6)).await;
+    let saved = PeerStore::open(&peer_store_path).unwrap();
+    assert!(saved.peers().contains(&first_peer_id));
+
+    // A node started with the saved peers and no bootstrap nodes finds the first node again
+    let restarted_tx = start_p2p_server(
+        "/ip4/127.0.0.1/tcp/4143",
+        &[],
+        Some(&peer_store_path),
+        Arc::clone(&restarted_blockchain),
+    );
+    assert!(wait_for_peer(&restarted_tx, &first_peer_id, Duration::from_secs(10)).await);
+
+    first_blockchain.lock().await.shutdown_blockchain();
+    second_blockchain.lock().await.shutdown_blockchain();
+    restarted_blockchain.lock().await.shutdown_blockchain();
+    let _ = std::fs::remove_file(&peer_store_path);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn nodes_sharing_a_bootstrap_node_find_each_other() {
+    let bootstrap_blockchain = initialize_blockchain("clutch-node-kademlia-test-1", chain_spec());
+    let first_blockchain = initialize_blockchain("clutch-node-kademlia-test-2", chain_spec());
+    let second_blockchain = initialize_blockchain("clutch-node-kademlia-test-3", chain_spec());
+
+    let _bootstrap_tx = start_p2p_server(
+        "/ip4/127.0.0.1/tcp/4144",
+        &[],
+        None,
+        Arc::clone(&bootstrap_blockchain),
+    );
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    let first_tx = start_p2p_server(
+        "/ip4/127.0.0.1/tcp/4145",
+        &["/ip4/127.0.0.1/tcp/4144"],
+        None,
+        Arc::clone(&first_blockchain),
+    );
+    let second_tx = start_p2p_server(
+        "/ip4/127.0.0.1/tcp/4146",
+        &["/ip4/127.0.0.1/tcp/4144"],
+        None,
+        Arc::clone(&second_blockchain),
+    );
+    let second_peer_id = P2PServer::get_local_peer_id_command(second_tx.clone()).await;
+
+    // Below the target peer count, the first node walks the DHT and connects to the second
+    assert!(wait_for_peer(&first_tx, &second_peer_id, Duration::from_secs(20)).await);
+
+    bootstrap_blockchain.lock().await.shutdown_blockchain();
+    first_blockchain.lock().await.shutdown_blockchain();
+    second_blockchain.lock().await.shutdown_blockchain();
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use std::{thread, time::Duration};
+
+use clutch_node::node::{
+    blocks::block::Block, chain_spec::ChainSpec,
+    equivocation::EquivocationProof, rlp_encoding::{decode, encode},
+    transactions::{function_call::FunctionCall, transaction_pool::TransactionPoolConfig},
+};
+use common::{new_blockchain, AUTHOR_PUBLIC_KEY};
+
+const BLOCKCHAIN_NAME: &str = "clutch-node-equivocation-test";
+const VALIDATOR_PUBLIC_KEY: &str = "0xdeb4cfb63db134698e1879ea24904df074726cc0";
+const VALIDATOR_SECRET_KEY: &str = "d2c446110cfcecbdf05b2be528e72483de5b6f7ef9c7856df2f81f48e9f2748f";
+
+#[test]
+fn equivocating_authority_reported_and_removed() {
+    let mut blockchain = new_blockchain(
+        BLOCKCHAIN_NAME,
+        ChainSpec {
+            authorities: vec![AUTHOR_PUBLIC_KEY.to_string(), VALIDATOR_PUBLIC_KEY.to_string()],
+            epoch_length: 1,
+            block_time: 1,
+            ..ChainSpec::default()
+        },
+        TransactionPoolConfig::default(),
+    );
+    let genesis_hash = blockchain.get_genesis_block().unwrap().hash;
+
+    // Odd one-second slots belong to the validator. Blocks from outside their author's slot are
+    // not recorded, and signing the same height again in a later slot is not an equivocation.
+    let first_block = signed_block(genesis_hash.clone(), 1001);
+    assert!(blockchain.observe_block(&signed_block("0".repeat(64), 1000)).is_none());
+    assert!(blockchain.observe_block(&first_block).is_none());
+    assert!(blockchain.observe_block(&first_block).is_none());
+    assert!(blockchain.observe_block(&signed_block("1".repeat(64), 1003)).is_none());
+
+    // Two blocks signed by the same authority in one slot
+    let proof = blockchain
+        .observe_block(&signed_block("0".repeat(64), 1001))
+        .expect("conflicting headers are an equivocation");
+    assert!(blockchain.verify_equivocation(&proof).is_ok());
+    assert_eq!(proof.offender(), VALIDATOR_PUBLIC_KEY);
+
+    let decoded_proof: EquivocationProof = decode(&encode(&proof)).unwrap();
+    assert!(blockchain.verify_equivocation(&decoded_proof).is_ok());
+
+    let mut forged_proof = proof.clone();
+    forged_proof.second.header.index = 2;
+    assert!(blockchain.verify_equivocation(&forged_proof).is_err());
+    let mut forged_proof = proof.clone();
+    forged_proof.second.header.timestamp = 1003;
+    assert!(blockchain.verify_equivocation(&forged_proof).is_err());
+
+    let report_tx = blockchain
+        .report_equivocation(&proof)
+        .expect("failed to report equivocation")
+        .expect("authorities report equivocations");
+    assert!(matches!(report_tx.data, FunctionCall::ReportEquivocation(_)));
+
+    while blockchain.current_author().unwrap() != AUTHOR_PUBLIC_KEY {
+        thread::sleep(Duration::from_millis(100));
+    }
+    let block = blockchain
+        .author_new_block()
+        .expect("failed to author new block");
+    assert_eq!(block.transactions[0].hash, report_tx.hash);
+
+    // Removed from the next epoch on, and only reported once
+    assert_eq!(blockchain.get_authorities().unwrap(), vec![AUTHOR_PUBLIC_KEY.to_string()]);
+    assert!(blockchain.report_equivocation(&proof).unwrap().is_none());
+
+    blockchain.shutdown_blockchain();
+}
+
+fn signed_block(previous_hash: String, timestamp: u64) -> Block {
+    let mut block = Block::new_block(1, previous_hash, vec![]);
+    block.timestamp = timestamp;
+    block.sign(VALIDATOR_PUBLIC_KEY, VALIDATOR_SECRET_KEY);
+    block
+}
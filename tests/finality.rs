@@ -0,0 +1,71 @@
+mod common;
+
+use std::{thread, time::Duration};
+
+use clutch_node::node::{
+    blocks::block::Block,
+    chain_spec::{ChainSpec, DEFAULT_CHAIN_ID},
+    finality::Precommit,
+    transactions::transaction_pool::TransactionPoolConfig,
+};
+use common::{new_blockchain, AUTHOR_PUBLIC_KEY, AUTHOR_SECRET_KEY};
+
+const BLOCKCHAIN_NAME: &str = "clutch-node-finality-test";
+const VALIDATOR_PUBLIC_KEY: &str = "0xdeb4cfb63db134698e1879ea24904df074726cc0";
+const VALIDATOR_SECRET_KEY: &str = "d2c446110cfcecbdf05b2be528e72483de5b6f7ef9c7856df2f81f48e9f2748f";
+const OUTSIDER_SECRET_KEY: &str = "e74e3f87268132c7b3ddb24600716fc362f4519bf9986a9436aa8a1be58c7150";
+const OUTSIDER_PUBLIC_KEY: &str = "0x8f19077627cde4848b090c53c83b12956837d5e9";
+
+#[test]
+fn block_finalized_by_supermajority_of_precommits() {
+    let mut blockchain = new_blockchain(
+        BLOCKCHAIN_NAME,
+        ChainSpec {
+            authorities: vec![AUTHOR_PUBLIC_KEY.to_string(), VALIDATOR_PUBLIC_KEY.to_string()],
+            block_time: 1,
+            allow_empty_blocks: true,
+            ..ChainSpec::default()
+        },
+        TransactionPoolConfig::default(),
+    );
+    let mut finalized_blocks = blockchain.subscribe_finalized_blocks();
+
+    while blockchain.current_author().unwrap() != AUTHOR_PUBLIC_KEY {
+        thread::sleep(Duration::from_millis(100));
+    }
+    let block = blockchain
+        .author_new_block()
+        .expect("failed to author new block");
+
+    // One of two authorities is not a supermajority
+    let precommit = blockchain
+        .precommit_block(&block)
+        .expect("failed to precommit block")
+        .expect("the author is an authority");
+    assert!(precommit.verify_signature(DEFAULT_CHAIN_ID).is_ok());
+    // Bound to the chain it was signed for
+    assert!(precommit.verify_signature(DEFAULT_CHAIN_ID + 1).is_err());
+    assert_eq!(blockchain.get_finalized_block().unwrap().index, 0);
+
+    let outsider_precommit = Precommit::new(&block, OUTSIDER_PUBLIC_KEY, OUTSIDER_SECRET_KEY, DEFAULT_CHAIN_ID);
+    assert!(blockchain.add_precommit(&outsider_precommit).is_err());
+
+    let mut conflicting_block = Block::new_block(block.index, block.previous_hash.clone(), vec![]);
+    conflicting_block.hash = "00".repeat(32);
+    let conflicting_precommit = Precommit::new(&conflicting_block, AUTHOR_PUBLIC_KEY, AUTHOR_SECRET_KEY, DEFAULT_CHAIN_ID);
+    assert!(blockchain.add_precommit(&conflicting_precommit).is_err());
+
+    let other_chain_precommit = Precommit::new(&block, VALIDATOR_PUBLIC_KEY, VALIDATOR_SECRET_KEY, DEFAULT_CHAIN_ID + 1);
+    assert!(blockchain.add_precommit(&other_chain_precommit).is_err());
+
+    let validator_precommit = Precommit::new(&block, VALIDATOR_PUBLIC_KEY, VALIDATOR_SECRET_KEY, DEFAULT_CHAIN_ID);
+    let finalized_block = blockchain
+        .add_precommit(&validator_precommit)
+        .expect("failed to add precommit")
+        .expect("two of two precommits finalize the block");
+    assert_eq!(finalized_block.hash, block.hash);
+    assert_eq!(blockchain.get_finalized_block().unwrap().hash, block.hash);
+    assert_eq!(finalized_blocks.try_recv().unwrap().hash, block.hash);
+
+    blockchain.shutdown_blockchain();
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use clutch_node::node::{
+    chain_spec::ChainSpec,
+    p2p_server::{P2PServer, P2PServerCommand},
+};
+use common::{chain_spec, initialize_blockchain, start_p2p_server};
+use libp2p::PeerId;
+use tokio::sync::mpsc;
+
+const TOPIC_NAME: &str = "clutch-node-handshake-test";
+
+async fn is_banned(command_tx: &mpsc::Sender<P2PServerCommand>, peer_id: &PeerId) -> bool {
+    P2PServer::get_peer_scores_command(command_tx.clone())
+        .await
+        .unwrap()
+        .iter()
+        .any(|peer| peer.peer_id == peer_id.to_string() && peer.banned_for.is_some())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn peer_of_another_chain_is_disconnected() {
+    let local_blockchain = initialize_blockchain("clutch-node-handshake-test-1", chain_spec());
+    let other_chain_blockchain = initialize_blockchain(
+        "clutch-node-handshake-test-2",
+        ChainSpec {
+            chain_id: 2,
+            ..chain_spec()
+        },
+    );
+
+    let local_tx =
+        start_p2p_server(TOPIC_NAME, "/ip4/127.0.0.1/tcp/4161", &[], Arc::clone(&local_blockchain));
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    // Same topic and genesis block, only the chain id differs
+    let other_chain_tx = start_p2p_server(
+        TOPIC_NAME,
+        "/ip4/127.0.0.1/tcp/4162",
+        &["/ip4/127.0.0.1/tcp/4161"],
+        Arc::clone(&other_chain_blockchain),
+    );
+    let local_peer_id = P2PServer::get_local_peer_id_command(local_tx.clone()).await;
+    let other_chain_peer_id = P2PServer::get_local_peer_id_command(other_chain_tx.clone()).await;
+
+    // Whichever node gets the other's handshake first disconnects and bans it
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let mut banned = false;
+    while Instant::now() < deadline && !banned {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        banned = is_banned(&local_tx, &other_chain_peer_id).await
+            || is_banned(&other_chain_tx, &local_peer_id).await;
+    }
+    assert!(banned);
+    let connected_peers = P2PServer::get_connected_peers_command(local_tx.clone()).await.unwrap();
+    assert!(!connected_peers.contains(&other_chain_peer_id));
+
+    local_blockchain.lock().await.shutdown_blockchain();
+    other_chain_blockchain.lock().await.shutdown_blockchain();
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use clutch_node::node::{
+    blockchain::Blockchain,
+    p2p_server::{node_key::load_or_generate_node_key, P2PServer, P2PServerCommand},
+};
+use common::{chain_spec, initialize_blockchain, run_p2p_server, AUTHOR_PUBLIC_KEY};
+use libp2p::identity::Keypair;
+use tokio::sync::{mpsc, Mutex};
+
+const TOPIC_NAME: &str = "clutch-node-identity-test";
+
+async fn start_p2p_server(
+    node_key: Keypair,
+    listen_addr: &str,
+    peer_addrs: &[&str],
+    bind_node_key_to_author: bool,
+    blockchain: Arc<Mutex<Blockchain>>,
+) -> mpsc::Sender<P2PServerCommand> {
+    let mut server = P2PServer::with_node_key(node_key, TOPIC_NAME, &[listen_addr], peer_addrs).unwrap();
+    if bind_node_key_to_author {
+        let local_peer_id = *server.behaviour.local_peer_id();
+        let node_identity = blockchain.lock().await.node_identity(&local_peer_id);
+        server.sync.set_node_identity(node_identity);
+    }
+    run_p2p_server(server, blockchain)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn peers_learn_the_authority_behind_a_bound_node_key() {
+    let node_key_path = std::env::temp_dir().join(format!("clutch-node-identity-test-{}.key", std::process::id()));
+    let _ = std::fs::remove_file(&node_key_path);
+    let authority_blockchain = initialize_blockchain("clutch-node-identity-test-1", chain_spec());
+    let observer_blockchain = initialize_blockchain("clutch-node-identity-test-2", chain_spec());
+
+    // The peer id comes from the key file, so it is known before the node starts
+    let node_key = load_or_generate_node_key(&node_key_path).unwrap();
+    let authority_peer_id = node_key.public().to_peer_id();
+    let reloaded_key = load_or_generate_node_key(&node_key_path).unwrap();
+    assert_eq!(reloaded_key.public().to_peer_id(), authority_peer_id);
+
+    let authority_tx = start_p2p_server(
+        node_key,
+        "/ip4/127.0.0.1/tcp/4151",
+        &[],
+        true,
+        Arc::clone(&authority_blockchain),
+    )
+    .await;
+    assert_eq!(P2PServer::get_local_peer_id_command(authority_tx.clone()).await, authority_peer_id);
+    let observer_tx = start_p2p_server(
+        Keypair::generate_ed25519(),
+        "/ip4/127.0.0.1/tcp/4152",
+        &[&format!("/ip4/127.0.0.1/tcp/4151/p2p/{}", authority_peer_id)],
+        false,
+        Arc::clone(&observer_blockchain),
+    )
+    .await;
+
+    // Learned from the authority's handshake
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let mut authority_peers = Vec::new();
+    while Instant::now() < deadline && authority_peers.is_empty() {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        authority_peers = P2PServer::get_authority_peers_command(observer_tx.clone()).await.unwrap();
+    }
+    assert_eq!(authority_peers.len(), 1);
+    assert_eq!(authority_peers[0].authority, AUTHOR_PUBLIC_KEY);
+    assert_eq!(authority_peers[0].peer_id, authority_peer_id.to_string());
+
+    // The observer is not bound to an authority
+    let authority_peers = P2PServer::get_authority_peers_command(authority_tx.clone()).await.unwrap();
+    assert!(authority_peers.is_empty());
+
+    authority_blockchain.lock().await.shutdown_blockchain();
+    observer_blockchain.lock().await.shutdown_blockchain();
+    let _ = std::fs::remove_file(&node_key_path);
+}
@@ -1,4 +1,6 @@
 use clutch_node::node::blockchain::Blockchain;
+use clutch_node::node::chain_spec::ChainSpec;
+use clutch_node::node::transactions::transaction_pool::TransactionPoolConfig;
 use clutch_node::node::p2p_server::commands::DirectMessageType;
 use clutch_node::node::p2p_server::get_block_bodies::GetBlockBodies;
 use clutch_node::node::p2p_server::get_block_header::GetBlockHeaders;
@@ -47,8 +49,13 @@ fn initialize_blockchain(name: String) -> Blockchain {
         "0x9b6e8afff8329743cac73dbef83ca3cbf9a74c20".to_string(),
         "0883ddd3d07303b87c954b0c9383f7b78f45e002520fc03a8adc80595dbf6509".to_string(),
         true,
-        vec!["0x9b6e8afff8329743cac73dbef83ca3cbf9a74c20".to_string()],
+        ChainSpec {
+            authorities: vec!["0x9b6e8afff8329743cac73dbef83ca3cbf9a74c20".to_string()],
+            ..ChainSpec::default()
+        },
+        TransactionPoolConfig::default(),
     )
+    .expect("Failed to initialize the blockchain")
 }
 
 #[tokio::test]
@@ -0,0 +1,78 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use clutch_node::node::{
+    chain_spec::ChainSpec,
+    p2p_server::{GossipMessageType, P2PServer},
+    rlp_encoding::encode,
+};
+use common::{initialize_blockchain, start_p2p_server};
+
+const TOPIC_NAME: &str = "clutch-node-peer-reputation-test";
+
+// Empty blocks give the test a valid block to gossip
+fn chain_spec() -> ChainSpec {
+    ChainSpec {
+        allow_empty_blocks: true,
+        ..common::chain_spec()
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn peer_gossiping_undecodable_messages_is_banned() {
+    let spamming_blockchain =
+        initialize_blockchain("clutch-node-peer-reputation-test-1", chain_spec());
+    let honest_blockchain =
+        initialize_blockchain("clutch-node-peer-reputation-test-2", chain_spec());
+
+    let spamming_tx = start_p2p_server(
+        TOPIC_NAME,
+        "/ip4/127.0.0.1/tcp/4131",
+        &[],
+        Arc::clone(&spamming_blockchain),
+    );
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    let honest_tx = start_p2p_server(
+        TOPIC_NAME,
+        "/ip4/127.0.0.1/tcp/4132",
+        &["/ip4/127.0.0.1/tcp/4131"],
+        Arc::clone(&honest_blockchain),
+    );
+    // Connected and subscribed to the topic
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    let spamming_peer_id = P2PServer::get_local_peer_id_command(spamming_tx.clone()).await;
+
+    // Ten undecodable transactions take the peer to the ban threshold
+    for nonce in 0..10u8 {
+        P2PServer::gossip_message_command(
+            spamming_tx.clone(),
+            GossipMessageType::Transaction,
+            &[0xff, nonce],
+        )
+        .await;
+    }
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    let peer_scores = P2PServer::get_peer_scores_command(honest_tx.clone()).await.unwrap();
+    let spamming_peer = peer_scores
+        .iter()
+        .find(|peer| peer.peer_id == spamming_peer_id.to_string())
+        .expect("misbehaving peer has a score");
+    assert!(spamming_peer.score <= -100);
+    assert!(spamming_peer.banned_for.is_some());
+
+    // Valid messages of a banned peer are ignored as well
+    let block = spamming_blockchain
+        .lock()
+        .await
+        .author_new_block()
+        .expect("failed to author new block");
+    P2PServer::gossip_message_command(spamming_tx.clone(), GossipMessageType::Block, &encode(&block)).await;
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    assert_eq!(honest_blockchain.lock().await.get_latest_block().unwrap().index, 0);
+
+    spamming_blockchain.lock().await.shutdown_blockchain();
+    honest_blockchain.lock().await.shutdown_blockchain();
+}
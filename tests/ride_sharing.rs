@@ -1,14 +1,16 @@
 use clutch_node::node::{
     blockchain::Blockchain,
     blocks::block::Block,
+    chain_spec::ChainSpec,
     coordinate,
     transactions::{
         function_call::FunctionCall, ride_acceptance::RideAcceptance, ride_cancel::RideCancel,
         ride_offer::RideOffer, ride_pay::RidePay, ride_request::RideRequest,
-        transaction::Transaction,
+        transaction::Transaction, transaction_pool::TransactionPoolConfig,
     },
 };
 use serial_test::serial;
+use std::thread;
 
 use ::tracing::{error, info};
 
@@ -62,6 +64,8 @@ fn import_blocks(blockchain: &mut Blockchain) {
     ];
 
     for block_creator in blocks.iter() {
+        // One block per slot
+        thread::sleep(blockchain.duration_until_next_slot());
         let mut block = block_creator();
         if let Err(e) = import_block(blockchain, &mut block) {
             error!("Error importing block: {}", e);
@@ -74,11 +78,14 @@ fn author_blocks(blockchain: &mut Blockchain) {
     let ride_request_tx = ride_request_transcation(1, 7);
     add_transaction_to_pool(&blockchain, ride_request_tx);
 
+    // Wait for a slot of this node's author
+    thread::sleep(blockchain.duration_until_next_slot());
+    while blockchain.current_author().unwrap() != AUTHOR_1_PUBLIC_KEY {
+        thread::sleep(blockchain.duration_until_next_slot());
+    }
+
     match blockchain.author_new_block() {
-        Ok(mut block) => match import_block(blockchain, &mut block) {
-            Ok(_) => info!("Successfully imported the new block."),
-            Err(e) => error!("Failed to import the new block: {}", e),
-        },
+        Ok(block) => info!("Successfully authored block {}.", block.index),
         Err(e) => error!("Failed to author new block: {}", e),
     }
 }
@@ -105,8 +112,14 @@ fn new_blockchain() -> Blockchain {
         AUTHOR_1_PUBLIC_KEY.to_string(),
         AUTHOR_1_SECRET_KEY.to_string(),
         true,
-        authorities,
-    );
+        ChainSpec {
+            authorities,
+            block_time: 1,
+            ..ChainSpec::default()
+        },
+        TransactionPoolConfig::default(),
+    )
+    .expect("Failed to initialize the blockchain");
     blockchain
 }
 
@@ -136,7 +149,7 @@ fn current_author_keys(blockchain: &Blockchain) -> Option<(&str, &str)> {
         (AUTHOR_3_PUBLIC_KEY, AUTHOR_3_SECRET_KEY),
     ];
 
-    let current_author = blockchain.current_author();
+    let current_author = blockchain.current_author().ok()?;
 
     for &(public_key, secret_key) in &author_keys {
         if current_author == public_key {
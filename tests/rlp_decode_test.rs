@@ -5,6 +5,7 @@ mod tests {
     use hex;    
     use clutch_node::node::{coordinate, rlp_encoding};
     use clutch_node::node::transactions::transaction::Transaction;
+    use clutch_node::node::chain_spec::DEFAULT_CHAIN_ID;
     use std::str::from_utf8;
     const PASSENGER_ADDRESS_KEY: &str = "0xdeb4cfb63db134698e1879ea24904df074726cc0";
     const PASSENGER_SECRET_KEY: &str ="d2c446110cfcecbdf05b2be528e72483de5b6f7ef9c7856df2f81f48e9f2748f";    
@@ -108,4 +109,43 @@ fn test_rlp_encode_ride_request_transaction() {
         Err(e) => println!("Failed to decode our own transaction: {:?}", e),
     }
 }
-} 
\ No newline at end of file
+
+#[test]
+fn test_raw_transaction_recovers_sender() {
+    let ride_request = RideRequest {
+        pickup_location: coordinate::Coordinates {
+            latitude: 27.223374842000805,
+            longitude: 56.365535283043855,
+        },
+        dropoff_location: coordinate::Coordinates {
+            latitude: 27.225817157860583,
+            longitude: 56.40913096554422,
+        },
+        fare: 1000,
+    };
+    let mut tx = Transaction::new_transaction(
+        PASSENGER_ADDRESS_KEY.to_string(),
+        1,
+        FunctionCall::RideRequest(ride_request),
+    );
+    tx.sign(PASSENGER_SECRET_KEY);
+
+    // The raw format omits 'from'; it must be recovered from the signature
+    let encoded = rlp_encoding::encode(&tx.to_raw_transaction());
+    let decoded = Transaction::from_raw_bytes(&encoded, DEFAULT_CHAIN_ID).expect("Failed to decode raw transaction");
+    assert_eq!(decoded.from, PASSENGER_ADDRESS_KEY);
+    assert_eq!(decoded.hash, tx.hash);
+
+    // Legacy encoding with an explicit 'from' is still accepted
+    let legacy_encoded = rlp_encoding::encode(&tx);
+    let legacy_decoded =
+        Transaction::from_raw_bytes(&legacy_encoded, DEFAULT_CHAIN_ID).expect("Failed to decode legacy transaction");
+    assert_eq!(legacy_decoded.from, PASSENGER_ADDRESS_KEY);
+
+    // Changing the nonce breaks the binding between hash and content
+    let mut tampered = tx.to_raw_transaction();
+    tampered.nonce = 2;
+    let tampered_encoded = rlp_encoding::encode(&tampered);
+    assert!(Transaction::from_raw_bytes(&tampered_encoded, DEFAULT_CHAIN_ID).is_err());
+}
+}
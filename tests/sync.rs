@@ -0,0 +1,157 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use clutch_node::node::{
+    blockchain::Blockchain,
+    chain_spec::ChainSpec,
+    p2p_server::{GossipMessageType, P2PServer},
+    rlp_encoding::encode,
+};
+use common::{initialize_blockchain, start_p2p_server, AUTHOR_PUBLIC_KEY};
+use tokio::sync::Mutex;
+
+const OTHER_AUTHORITY: &str = "0xdeb4cfb63db134698e1879ea24904df074726cc0";
+const TOPIC_NAME: &str = "clutch-node-sync-test";
+
+// Empty blocks let the tests author as many blocks as they need
+fn chain_spec(authority: &str) -> ChainSpec {
+    ChainSpec {
+        authorities: vec![authority.to_string()],
+        allow_empty_blocks: true,
+        ..common::chain_spec()
+    }
+}
+
+async fn author_blocks(blockchain: &Arc<Mutex<Blockchain>>, count: usize) {
+    let blockchain = blockchain.lock().await;
+    for _ in 0..count {
+        blockchain.author_new_block().expect("failed to author new block");
+    }
+}
+
+async fn wait_for_block(blockchain: &Arc<Mutex<Blockchain>>, index: usize, timeout: Duration) -> usize {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let latest_index = blockchain.lock().await.get_latest_block().unwrap().index;
+        if latest_index >= index || Instant::now() > deadline {
+            return latest_index;
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn node_syncs_from_several_peers_until_caught_up() {
+    let first_blockchain =
+        initialize_blockchain("clutch-node-sync-test-1", chain_spec(AUTHOR_PUBLIC_KEY));
+    let second_blockchain =
+        initialize_blockchain("clutch-node-sync-test-2", chain_spec(AUTHOR_PUBLIC_KEY));
+    let syncing_blockchain =
+        initialize_blockchain("clutch-node-sync-test-3", chain_spec(AUTHOR_PUBLIC_KEY));
+
+    // Two peers with the same chain, more blocks than fit in one header request
+    author_blocks(&first_blockchain, 150).await;
+    {
+        let first = first_blockchain.lock().await;
+        let second = second_blockchain.lock().await;
+        for block in first.get_blocks_with_limit_and_skip(1, 0, 150).unwrap() {
+            second.import_block(&block).expect("failed to import block");
+        }
+    }
+
+    let _first_tx =
+        start_p2p_server(TOPIC_NAME, "/ip4/127.0.0.1/tcp/4101", &[], Arc::clone(&first_blockchain));
+    let _second_tx = start_p2p_server(
+        TOPIC_NAME,
+        "/ip4/127.0.0.1/tcp/4102",
+        &[],
+        Arc::clone(&second_blockchain),
+    );
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    let _syncing_tx = start_p2p_server(
+        TOPIC_NAME,
+        "/ip4/127.0.0.1/tcp/4103",
+        &["/ip4/127.0.0.1/tcp/4101", "/ip4/127.0.0.1/tcp/4102"],
+        Arc::clone(&syncing_blockchain),
+    );
+
+    let latest_index = wait_for_block(&syncing_blockchain, 150, Duration::from_secs(30)).await;
+    assert_eq!(latest_index, 150);
+    assert_eq!(
+        syncing_blockchain.lock().await.get_latest_block().unwrap().hash,
+        first_blockchain.lock().await.get_latest_block().unwrap().hash
+    );
+
+    // Blocks authored later are picked up from the next handshake
+    author_blocks(&first_blockchain, 10).await;
+    let latest_index = wait_for_block(&syncing_blockchain, 160, Duration::from_secs(30)).await;
+    assert_eq!(latest_index, 160);
+
+    first_blockchain.lock().await.shutdown_blockchain();
+    second_blockchain.lock().await.shutdown_blockchain();
+    syncing_blockchain.lock().await.shutdown_blockchain();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn gossiped_block_ahead_of_head_fills_the_gap() {
+    let first_blockchain =
+        initialize_blockchain("clutch-node-gossip-gap-test-1", chain_spec(AUTHOR_PUBLIC_KEY));
+    let second_blockchain =
+        initialize_blockchain("clutch-node-gossip-gap-test-2", chain_spec(AUTHOR_PUBLIC_KEY));
+
+    let first_tx =
+        start_p2p_server(TOPIC_NAME, "/ip4/127.0.0.1/tcp/4111", &[], Arc::clone(&first_blockchain));
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    let _second_tx = start_p2p_server(
+        TOPIC_NAME,
+        "/ip4/127.0.0.1/tcp/4112",
+        &["/ip4/127.0.0.1/tcp/4111"],
+        Arc::clone(&second_blockchain),
+    );
+    // Connected, handshaken at the same height and subscribed to the topic
+    tokio::time::sleep(Duration::from_secs(3)).await;
+
+    // Only the last of five blocks is gossiped, well before the next handshake
+    author_blocks(&first_blockchain, 5).await;
+    let block = first_blockchain.lock().await.get_latest_block().unwrap();
+    P2PServer::gossip_message_command(first_tx.clone(), GossipMessageType::Block, &encode(&block)).await;
+
+    let latest_index = wait_for_block(&second_blockchain, 5, Duration::from_secs(5)).await;
+    assert_eq!(latest_index, 5);
+    assert_eq!(second_blockchain.lock().await.get_latest_block().unwrap().hash, block.hash);
+
+    first_blockchain.lock().await.shutdown_blockchain();
+    second_blockchain.lock().await.shutdown_blockchain();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn headers_from_non_authorities_are_rejected_before_bodies() {
+    // Same genesis, but the serving node's author is not an authority of the syncing node
+    let serving_blockchain =
+        initialize_blockchain("clutch-node-invalid-headers-test-1", chain_spec(AUTHOR_PUBLIC_KEY));
+    let syncing_blockchain =
+        initialize_blockchain("clutch-node-invalid-headers-test-2", chain_spec(OTHER_AUTHORITY));
+    author_blocks(&serving_blockchain, 5).await;
+
+    let _serving_tx = start_p2p_server(
+        TOPIC_NAME,
+        "/ip4/127.0.0.1/tcp/4121",
+        &[],
+        Arc::clone(&serving_blockchain),
+    );
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    let _syncing_tx = start_p2p_server(
+        TOPIC_NAME,
+        "/ip4/127.0.0.1/tcp/4122",
+        &["/ip4/127.0.0.1/tcp/4121"],
+        Arc::clone(&syncing_blockchain),
+    );
+
+    let latest_index = wait_for_block(&syncing_blockchain, 1, Duration::from_secs(5)).await;
+    assert_eq!(latest_index, 0);
+
+    serving_blockchain.lock().await.shutdown_blockchain();
+    syncing_blockchain.lock().await.shutdown_blockchain();
+}
@@ -0,0 +1,286 @@
+mod common;
+
+use std::thread;
+
+use clutch_node::node::{
+    chain_spec::ChainSpec,
+    transactions::{
+        function_call::FunctionCall, transaction::Transaction,
+        transaction_pool::TransactionPoolConfig, transfer::Transfer,
+    },
+};
+use common::{new_blockchain, AUTHOR_PUBLIC_KEY};
+
+const BLOCKCHAIN_NAME: &str = "clutch-node-transaction-pool-test";
+const REVALIDATION_BLOCKCHAIN_NAME: &str = "clutch-node-transaction-pool-revalidation-test";
+const REPLACEMENT_BLOCKCHAIN_NAME: &str = "clutch-node-transaction-pool-replacement-test";
+const PASSENGER_ADDRESS_KEY: &str = "0xdeb4cfb63db134698e1879ea24904df074726cc0";
+const PASSENGER_SECRET_KEY: &str =
+    "d2c446110cfcecbdf05b2be528e72483de5b6f7ef9c7856df2f81f48e9f2748f";
+const DRIVER_ADDRESS_KEY: &str = "0x8f19077627cde4848b090c53c83b12956837d5e9";
+const DRIVER_SECRET_KEY: &str = "e74e3f87268132c7b3ddb24600716fc362f4519bf9986a9436aa8a1be58c7150";
+const TO_ADDRESS_KEY: &str = "0xa91101310bee451ca0e219aba08d8d4dd929f16c";
+
+#[test]
+fn pool_orders_by_fee_and_holds_future_nonces() {
+    let mut blockchain = new_blockchain(
+        BLOCKCHAIN_NAME,
+        ChainSpec {
+            authorities: vec![AUTHOR_PUBLIC_KEY.to_string()],
+            block_time: 1,
+            ..ChainSpec::default()
+        },
+        TransactionPoolConfig {
+            max_transactions: 8,
+            max_transactions_per_account: 2,
+        },
+    );
+
+    // Nonce 2 waits in the pool until nonce 1 arrives, then both go into the same block
+    let funding_tx = transfer_transaction(
+        PASSENGER_ADDRESS_KEY,
+        PASSENGER_SECRET_KEY,
+        DRIVER_ADDRESS_KEY,
+        1,
+        0,
+        10,
+    );
+    let queued_tx = transfer_transaction(
+        PASSENGER_ADDRESS_KEY,
+        PASSENGER_SECRET_KEY,
+        TO_ADDRESS_KEY,
+        2,
+        1,
+        1,
+    );
+    blockchain
+        .add_transaction_to_pool(&queued_tx)
+        .expect("Failed to add future nonce transaction to pool");
+    blockchain
+        .add_transaction_to_pool(&funding_tx)
+        .expect("Failed to add transaction to pool");
+
+    let block = blockchain
+        .author_new_block()
+        .expect("Failed to author new block");
+    assert_eq!(
+        transaction_hashes(&block.transactions),
+        vec![funding_tx.hash.clone(), queued_tx.hash.clone()]
+    );
+    assert!(blockchain.get_transactions_from_pool().unwrap().is_empty());
+
+    // Committed nonces are rejected
+    assert!(blockchain.add_transaction_to_pool(&funding_tx).is_err());
+
+    // The higher fee goes first, and the driver spends what the block above paid in
+    let passenger_tx = transfer_transaction(
+        PASSENGER_ADDRESS_KEY,
+        PASSENGER_SECRET_KEY,
+        TO_ADDRESS_KEY,
+        3,
+        1,
+        1,
+    );
+    let driver_tx = transfer_transaction(
+        DRIVER_ADDRESS_KEY,
+        DRIVER_SECRET_KEY,
+        TO_ADDRESS_KEY,
+        1,
+        3,
+        1,
+    );
+    blockchain
+        .add_transaction_to_pool(&passenger_tx)
+        .expect("Failed to add transaction to pool");
+    blockchain
+        .add_transaction_to_pool(&driver_tx)
+        .expect("Failed to add transaction to pool");
+
+    // One block per slot
+    thread::sleep(blockchain.duration_until_next_slot());
+    let block = blockchain
+        .author_new_block()
+        .expect("Failed to author new block");
+    assert_eq!(
+        transaction_hashes(&block.transactions),
+        vec![driver_tx.hash.clone(), passenger_tx.hash.clone()]
+    );
+    assert!(blockchain.get_transactions_from_pool().unwrap().is_empty());
+
+    let driver_state = blockchain.get_account_state(&DRIVER_ADDRESS_KEY.to_string());
+    assert_eq!(driver_state.balance, 10 - 1 - 3);
+
+    // Per-account limit
+    for nonce in 4..6 {
+        let tx = transfer_transaction(
+            PASSENGER_ADDRESS_KEY,
+            PASSENGER_SECRET_KEY,
+            DRIVER_ADDRESS_KEY,
+            nonce,
+            0,
+            1,
+        );
+        blockchain
+            .add_transaction_to_pool(&tx)
+            .expect("Failed to add transaction to pool");
+    }
+    let over_limit_tx = transfer_transaction(
+        PASSENGER_ADDRESS_KEY,
+        PASSENGER_SECRET_KEY,
+        DRIVER_ADDRESS_KEY,
+        6,
+        0,
+        1,
+    );
+    assert!(blockchain.add_transaction_to_pool(&over_limit_tx).is_err());
+
+    blockchain.shutdown_blockchain();
+}
+
+#[test]
+fn pending_transaction_replaced_or_cancelled() {
+    let mut blockchain = new_blockchain(
+        REPLACEMENT_BLOCKCHAIN_NAME,
+        ChainSpec {
+            authorities: vec![AUTHOR_PUBLIC_KEY.to_string()],
+            ..ChainSpec::default()
+        },
+        TransactionPoolConfig::default(),
+    );
+
+    let pending_tx = transfer_transaction(
+        PASSENGER_ADDRESS_KEY,
+        PASSENGER_SECRET_KEY,
+        DRIVER_ADDRESS_KEY,
+        1,
+        2,
+        10,
+    );
+    blockchain
+        .add_transaction_to_pool(&pending_tx)
+        .expect("Failed to add transaction to pool");
+
+    // Same nonce without a higher fee is rejected
+    let same_fee_tx = transfer_transaction(
+        PASSENGER_ADDRESS_KEY,
+        PASSENGER_SECRET_KEY,
+        DRIVER_ADDRESS_KEY,
+        1,
+        2,
+        5,
+    );
+    assert!(blockchain.add_transaction_to_pool(&same_fee_tx).is_err());
+
+    let replacement_tx = transfer_transaction(
+        PASSENGER_ADDRESS_KEY,
+        PASSENGER_SECRET_KEY,
+        DRIVER_ADDRESS_KEY,
+        1,
+        3,
+        5,
+    );
+    blockchain
+        .add_transaction_to_pool(&replacement_tx)
+        .expect("Failed to replace transaction");
+    assert_eq!(
+        transaction_hashes(&blockchain.get_transactions_from_pool().unwrap()),
+        vec![replacement_tx.hash.clone()]
+    );
+
+    // A cancel only needs to match the pending fee
+    let mut cancel_tx =
+        Transaction::new_cancel_transaction(PASSENGER_ADDRESS_KEY.to_string(), 1, 3);
+    cancel_tx.sign(PASSENGER_SECRET_KEY);
+    blockchain
+        .add_transaction_to_pool(&cancel_tx)
+        .expect("Failed to cancel transaction");
+    assert_eq!(
+        transaction_hashes(&blockchain.get_transactions_from_pool().unwrap()),
+        vec![cancel_tx.hash.clone()]
+    );
+
+    let block = blockchain
+        .author_new_block()
+        .expect("Failed to author new block");
+    assert_eq!(
+        transaction_hashes(&block.transactions),
+        vec![cancel_tx.hash.clone()]
+    );
+
+    let passenger_state = blockchain.get_account_state(&PASSENGER_ADDRESS_KEY.to_string());
+    assert_eq!(passenger_state.balance, 30 - 3);
+    assert_eq!(
+        blockchain.get_current_nonce(&PASSENGER_ADDRESS_KEY.to_string()),
+        Ok(1)
+    );
+
+    blockchain.shutdown_blockchain();
+}
+
+#[test]
+fn pool_revalidated_after_block() {
+    let mut blockchain = new_blockchain(
+        REVALIDATION_BLOCKCHAIN_NAME,
+        ChainSpec {
+            authorities: vec![AUTHOR_PUBLIC_KEY.to_string()],
+            ..ChainSpec::default()
+        },
+        TransactionPoolConfig::default(),
+    );
+
+    // Nonces 2 and 3 are only checked against the state once they can go into a block
+    let transactions: Vec<Transaction> = (1..4)
+        .map(|nonce| {
+            transfer_transaction(
+                PASSENGER_ADDRESS_KEY,
+                PASSENGER_SECRET_KEY,
+                DRIVER_ADDRESS_KEY,
+                nonce,
+                0,
+                20,
+            )
+        })
+        .collect();
+    for transaction in transactions.iter() {
+        blockchain
+            .add_transaction_to_pool(transaction)
+            .expect("Failed to add transaction to pool");
+    }
+
+    blockchain
+        .author_new_block()
+        .expect("Failed to author new block");
+
+    // Nonce 2 cannot be paid for after nonce 1 in the same block and is dropped, nonce 3 waits
+    // for a new nonce 2
+    assert_eq!(
+        transaction_hashes(&blockchain.get_transactions_from_pool().unwrap()),
+        vec![transactions[2].hash.clone()]
+    );
+    blockchain.shutdown_blockchain();
+}
+
+fn transfer_transaction(
+    from: &str,
+    secret_key: &str,
+    to: &str,
+    nonce: u64,
+    fee: u64,
+    value: u64,
+) -> Transaction {
+    let mut transaction = Transaction::new_transaction_with_fee(
+        from.to_string(),
+        nonce,
+        fee,
+        FunctionCall::Transfer(Transfer {
+            to: to.to_string(),
+            value,
+        }),
+    );
+    transaction.sign(secret_key);
+    transaction
+}
+
+fn transaction_hashes(transactions: &[Transaction]) -> Vec<String> {
+    transactions.iter().map(|tx| tx.hash.clone()).collect()
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use clutch_node::node::{
+    blockchain::Blockchain,
+    transactions::{function_call::FunctionCall, transaction::Transaction, transfer::Transfer},
+};
+use common::{chain_spec, initialize_blockchain, start_p2p_server};
+
+const PASSENGER_ADDRESS_KEY: &str = "0xdeb4cfb63db134698e1879ea24904df074726cc0";
+const PASSENGER_SECRET_KEY: &str = "d2c446110cfcecbdf05b2be528e72483de5b6f7ef9c7856df2f81f48e9f2748f";
+const DRIVER_ADDRESS_KEY: &str = "0x8f19077627cde4848b090c53c83b12956837d5e9";
+const TOPIC_NAME: &str = "clutch-node-transaction-pool-sync-test";
+
+fn transfer_transaction(nonce: u64, value: u64) -> Transaction {
+    let mut transaction = Transaction::new_transaction_with_fee(
+        PASSENGER_ADDRESS_KEY.to_string(),
+        nonce,
+        1,
+        FunctionCall::Transfer(Transfer {
+            to: DRIVER_ADDRESS_KEY.to_string(),
+            value,
+        }),
+    );
+    transaction.sign(PASSENGER_SECRET_KEY);
+    transaction
+}
+
+fn pooled_hashes(blockchain: &Blockchain) -> Vec<String> {
+    let mut hashes: Vec<String> = blockchain
+        .get_transactions_from_pool()
+        .unwrap()
+        .iter()
+        .map(|tx| tx.hash.clone())
+        .collect();
+    hashes.sort();
+    hashes
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn joining_node_fetches_pooled_transactions() {
+    let pool_blockchain =
+        initialize_blockchain("clutch-node-transaction-pool-sync-test-1", chain_spec());
+    let joining_blockchain =
+        initialize_blockchain("clutch-node-transaction-pool-sync-test-2", chain_spec());
+
+    // Pooled before the joining node connects, so it never sees them gossiped
+    {
+        let blockchain = pool_blockchain.lock().await;
+        for nonce in 1..=3 {
+            blockchain
+                .add_transaction_to_pool(&transfer_transaction(nonce, nonce))
+                .expect("Failed to add transaction to pool");
+        }
+    }
+    let expected_hashes = pooled_hashes(&*pool_blockchain.lock().await);
+
+    let _pool_tx =
+        start_p2p_server(TOPIC_NAME, "/ip4/127.0.0.1/tcp/4171", &[], Arc::clone(&pool_blockchain));
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    let _joining_tx = start_p2p_server(
+        TOPIC_NAME,
+        "/ip4/127.0.0.1/tcp/4172",
+        &["/ip4/127.0.0.1/tcp/4171"],
+        Arc::clone(&joining_blockchain),
+    );
+
+    let deadline = Instant::now() + Duration::from_secs(15);
+    let mut hashes = Vec::new();
+    while Instant::now() < deadline && hashes != expected_hashes {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        hashes = pooled_hashes(&*joining_blockchain.lock().await);
+    }
+    assert_eq!(hashes, expected_hashes);
+
+    pool_blockchain.lock().await.shutdown_blockchain();
+    joining_blockchain.lock().await.shutdown_blockchain();
+}
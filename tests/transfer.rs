@@ -4,7 +4,11 @@ use ::tracing::{error, info};
 use clutch_node::node::{
     blockchain::Blockchain,
     blocks::block::Block,
-    transactions::{function_call::FunctionCall, transaction::Transaction, transfer::Transfer},
+    chain_spec::ChainSpec,
+    transactions::{
+        function_call::FunctionCall, transaction::Transaction,
+        transaction_pool::TransactionPoolConfig, transfer::Transfer,
+    },
 };
 
 const BLOCKCHAIN_NAME: &str = "clutch-node-test";
@@ -22,8 +26,13 @@ fn transfer_founds() {
         AUTHOR_PUBLIC_KEY.to_string(),
         AUTHOR_SECRET_KEY.to_string(),
         true,
-        authorities,
-    );
+        ChainSpec {
+            authorities,
+            ..ChainSpec::default()
+        },
+        TransactionPoolConfig::default(),
+    )
+    .expect("Failed to initialize the blockchain");
 
     let blocks = [|| transfer_block(1, 1, 20)];
 